@@ -4,7 +4,7 @@ use proc_macro2::{Ident, TokenStream};
 use quote::TokenStreamExt;
 use syn::{
     parse::{Parse, ParseStream},
-    parse_macro_input, ItemFn,
+    parse_macro_input, Expr, ItemFn, LitStr, Token, Type,
 };
 
 /// Create a new component from an async function definition.
@@ -12,26 +12,103 @@ use syn::{
 /// # Examples
 ///
 /// ```ignore
+/// // Handlers get the interaction and the command arguments. Routed components also get
+/// // the route parameters, and select menus what was picked; the ones a handler doesn't
+/// // use can be left out.
 /// #[component]
-/// async fn respond_button((interaction, args): (ComponentInteraction, CommandArguments)) -> anyhow::Result<()> {
-///     interaction.defer_ephemeral(&args.context.http).await?;
+/// async fn respond_button(interaction: ComponentInteraction, args: CommandArguments) -> anyhow::Result<()> {
+///     interaction.defer_ephemeral(&args.http).await?;
 ///     // ...
 /// }
 ///
+/// // A lone identifier is the function to run when it's time to ignore the component's
+/// // events, something like disabling the button or removing it.
 /// #[component(exit_button_cleanup)]
-/// async fn exit_button((interaction, args): (ComponentInteraction, CommandArguments)) -> anyhow::Result<()> {
+/// async fn exit_button(interaction: ComponentInteraction, args: CommandArguments) -> anyhow::Result<()> {
 ///     // ...
 /// }
 ///
-/// async fn exit_button_cleanup((id, http, cache): (String, Arc<Http>, Arc<Cache>)) -> anyhow::Result<()> {
-///     // Do something when it's time to ignore these events, something like disable the button or remove it...
+/// async fn exit_button_cleanup(id: String, http: Arc<Http>, cache: Arc<Cache>) -> anyhow::Result<()> {
+///     // ...
+/// }
+///
+/// // Named arguments also take a function to run when the component is loaded after a restart.
+/// #[component(cleanup = exit_button_cleanup, restore = exit_button_restore)]
+/// async fn persistent_exit_button(interaction: ComponentInteraction, args: CommandArguments) -> anyhow::Result<()> {
+///     // ...
+/// }
+///
+/// async fn exit_button_restore(id: String, state: Vec<u8>) -> anyhow::Result<()> {
+///     // Deserialize the state that was registered with the component...
+/// }
+///
+/// // Routed components handle every ID matching the pattern without being inserted.
+/// #[component(route = "coolthing/{channel}/{message}/{action}")]
+/// async fn routed_button(
+///     interaction: ComponentInteraction,
+///     args: CommandArguments,
+///     params: RouteParams,
+/// ) -> anyhow::Result<()> {
+///     let message: u64 = params.get("message")?;
+///     // ...
+/// }
+///
+/// #[component(route = "coolthing-pick")]
+/// async fn coolthing_pick(
+///     interaction: ComponentInteraction,
+///     args: CommandArguments,
+///     _params: RouteParams,
+///     selection: Selection,
+/// ) -> anyhow::Result<()> {
+///     let picked: String = selection.one()?;
+///     // ...
+/// }
+///
+/// // Stateful components own their state, it's passed as the first argument. They're
+/// // inserted with `with_state` and the state is dropped once they're cleaned up. The
+/// // cleanup function gets the state too, and the restore function builds it again.
+/// #[component(state = Mutex<Counter>, route = "counter/{id}", cleanup = counter_cleanup, restore = counter_restore)]
+/// async fn counter_button(
+///     counter: &Mutex<Counter>,
+///     interaction: ComponentInteraction,
+///     args: CommandArguments,
+/// ) -> anyhow::Result<()> {
+///     counter.lock().await.count += 1;
+///     // ...
+/// }
+///
+/// async fn counter_cleanup(counter: &Mutex<Counter>, id: String, http: Arc<Http>, cache: Arc<Cache>) -> anyhow::Result<()> {
+///     // ...
 /// }
 ///
-/// // Somehwhere else.
+/// async fn counter_restore(id: String, state: Vec<u8>) -> anyhow::Result<Mutex<Counter>> {
+///     // ...
+/// }
+///
+/// // Only whoever the policy allows can use the component, anyone else is told it isn't
+/// // theirs. Registrations can have their own policy instead.
+/// #[component(route = "coolthing-vote/{choice}", authorize = Authorization::Role(VOTER))]
+/// async fn coolthing_vote(interaction: ComponentInteraction, args: CommandArguments) -> anyhow::Result<()> {
+///     // ...
+/// }
 ///
-/// // Insert the components into the component map so the system knows about it.
-/// component_map.insert(format!("coolthing_respond:{channel_id}/{message_id}"), &respond_button, None).await;
-/// component_map.insert(format!("coolthing_exit:{channel_id}/{message_id}"), &respond_button, None).await;
+/// // Modal handlers are always routed and get the submitted fields.
+/// #[component(modal, route = "coolthing-form/{channel}")]
+/// async fn coolthing_form(
+///     interaction: ModalInteraction,
+///     args: CommandArguments,
+///     fields: ModalFields,
+/// ) -> anyhow::Result<()> {
+///     let title: String = fields.get("title")?;
+///     // ...
+/// }
+///
+/// // Somewhere else, insert the components that aren't routed into the component map so
+/// // the system knows about them.
+/// let registration = Registration::new(guild_id, channel_id);
+/// component_map.insert(format!("coolthing_respond:{channel_id}/{message_id}"), Arc::new(respond_button), None, registration.clone()).await;
+/// component_map.insert(format!("coolthing_exit:{channel_id}/{message_id}"), Arc::new(exit_button), None, registration.clone()).await;
+/// component_map.insert(format!("counter/{id}"), counter_button.with_state(Mutex::new(Counter::default())), None, registration).await;
 /// ```
 #[proc_macro_attribute]
 pub fn component(args: CompilerTokenStream, tokens: CompilerTokenStream) -> CompilerTokenStream {
@@ -40,6 +117,12 @@ pub fn component(args: CompilerTokenStream, tokens: CompilerTokenStream) -> Comp
     if function.sig.asyncness.is_none() {
         panic!("Function isn't an asyncronous one!");
     }
+    if args.modal {
+        return modal(args, function);
+    }
+    if args.state.is_some() {
+        return stateful(args, function);
+    }
 
     let cleanup = {
         let mut cleanup = TokenStream::new();
@@ -54,11 +137,50 @@ pub fn component(args: CompilerTokenStream, tokens: CompilerTokenStream) -> Comp
         cleanup
     };
 
+    let restore = {
+        let mut restore = TokenStream::new();
+        if let Some(ident) = args.restore_ident {
+            restore.append_all(quote::quote! {
+                #[inline]
+                async fn restore(
+                    &self,
+                    id: String,
+                    state: Vec<u8>,
+                ) -> ::anyhow::Result<Option<crate::componet::SharedComponent>> {
+                    #ident(id, state).await?;
+                    Ok(None)
+                }
+            })
+        }
+        restore
+    };
+
+    let route = {
+        let mut route = TokenStream::new();
+        if let Some(pattern) = args.route {
+            route.append_all(quote::quote! {
+                #[inline]
+                fn route(&self) -> Option<&'static str> { Some(#pattern) }
+            })
+        }
+        route
+    };
+
+    let authorization = authorization(args.authorize);
+
     let ident = function.sig.ident;
-    let inputs = function.sig.inputs;
+    let mut inputs = function.sig.inputs;
+    // Handlers that don't care about route parameters or select menus can leave them out.
+    if inputs.len() == 2 {
+        inputs.push(syn::parse_quote!(_params: crate::componet::RouteParams));
+    }
+    if inputs.len() == 3 {
+        inputs.push(syn::parse_quote!(_selection: crate::componet::Selection));
+    }
     let vis = function.vis;
     let ret = function.sig.output;
     let statements = &function.block.stmts;
+    let kind = ident.to_string();
     let struct_ident = Ident::new(
         &format!("{}_component", ident).to_case(Case::UpperCamel),
         ident.span(),
@@ -68,11 +190,193 @@ pub fn component(args: CompilerTokenStream, tokens: CompilerTokenStream) -> Comp
 
         #[::async_trait::async_trait]
         impl Component for #struct_ident {
+            #[inline]
+            fn kind(&self) -> &'static str { #kind }
+
+            #route
+            #authorization
+
             async fn run(&self, #inputs) #ret {
                 #(#statements)*
             }
 
             #cleanup
+            #restore
+        }
+
+        #[allow(non_upper_case_globals)]
+        #vis const #ident:#struct_ident = #struct_ident;
+    };
+    t.into()
+}
+
+/// The component's `authorization` returning `authorize`, if there is one.
+fn authorization(authorize: Option<Expr>) -> TokenStream {
+    let mut authorization = TokenStream::new();
+    if let Some(authorize) = authorize {
+        authorization.append_all(quote::quote! {
+            #[inline]
+            fn authorization(&self) -> crate::componet::Authorization { #authorize }
+        })
+    }
+    authorization
+}
+
+/// Create a component owning its state, see [`component`].
+fn stateful(args: ComponentArgs, function: ItemFn) -> CompilerTokenStream {
+    let state = args.state.expect("stateful components have a state");
+
+    let cleanup = {
+        let mut cleanup = TokenStream::new();
+        if let Some(ident) = args.cleanup_ident {
+            cleanup.append_all(quote::quote! {
+                async fn cleanup(&self, id: String, http: Arc<Http>, cache: Arc<Cache>) -> ::anyhow::Result<()> {
+                    let Some(state) = self.0.as_deref() else {
+                        return Ok(());
+                    };
+                    #ident(state, id, http, cache).await
+                }
+            })
+        }
+        cleanup
+    };
+
+    let ident = function.sig.ident;
+    let kind = ident.to_string();
+    // Without a way to build the state again, registrations are forgotten after a restart.
+    let restore = match args.restore_ident {
+        Some(restore) => {
+            quote::quote! {
+                let state = #restore(id, state).await?;
+                Ok(Some(self.with_state(state)))
+            }
+        }
+        None => quote::quote! { ::anyhow::bail!("'{}' can't be restored", #kind) },
+    };
+
+    let route = {
+        let mut route = TokenStream::new();
+        if let Some(pattern) = args.route {
+            route.append_all(quote::quote! {
+                #[inline]
+                fn route(&self) -> Option<&'static str> { Some(#pattern) }
+            })
+        }
+        route
+    };
+
+    let authorization = authorization(args.authorize);
+
+    let mut inputs = function.sig.inputs;
+    // The state comes first, the rest can be left out like for any other component.
+    if inputs.len() == 3 {
+        inputs.push(syn::parse_quote!(_params: crate::componet::RouteParams));
+    }
+    if inputs.len() == 4 {
+        inputs.push(syn::parse_quote!(_selection: crate::componet::Selection));
+    }
+    let vis = function.vis;
+    let ret = function.sig.output;
+    let statements = &function.block.stmts;
+    let struct_ident = Ident::new(
+        &format!("{}_component", ident).to_case(Case::UpperCamel),
+        ident.span(),
+    );
+    let t = quote::quote! {
+        /// Without a state it's only the kind, answering that it has expired.
+        #vis struct #struct_ident(Option<::std::sync::Arc<#state>>);
+
+        impl #struct_ident {
+            /// This component owning `state`, ready to be inserted into the component map.
+            #vis fn with_state(&self, state: #state) -> crate::componet::SharedComponent {
+                ::std::sync::Arc::new(Self(Some(::std::sync::Arc::new(state))))
+            }
+
+            async fn handle(#inputs) #ret {
+                #(#statements)*
+            }
+        }
+
+        #[::async_trait::async_trait]
+        impl Component for #struct_ident {
+            #[inline]
+            fn kind(&self) -> &'static str { #kind }
+
+            #route
+            #authorization
+
+            async fn run(
+                &self,
+                interaction: ::serenity::all::ComponentInteraction,
+                args: crate::commands::CommandArguments,
+                params: crate::componet::RouteParams,
+                selection: crate::componet::Selection,
+            ) -> ::anyhow::Result<()> {
+                let Some(state) = self.0.as_deref() else {
+                    return crate::componet::expired(&interaction, &args.http).await;
+                };
+                Self::handle(state, interaction, args, params, selection).await
+            }
+
+            #[inline]
+            fn state(&self) -> Option<::std::sync::Arc<dyn ::std::any::Any + Send + Sync>> {
+                self.0.clone().map(|x| x as _)
+            }
+
+            async fn restore(
+                &self,
+                id: String,
+                state: Vec<u8>,
+            ) -> ::anyhow::Result<Option<crate::componet::SharedComponent>> {
+                #restore
+            }
+
+            #cleanup
+        }
+
+        #[allow(non_upper_case_globals)]
+        #vis const #ident:#struct_ident = #struct_ident(None);
+    };
+    t.into()
+}
+
+/// Create a modal handler, see [`component`].
+fn modal(args: ComponentArgs, function: ItemFn) -> CompilerTokenStream {
+    let Some(pattern) = args.route else {
+        panic!("Modals need a route!");
+    };
+    if args.cleanup_ident.is_some()
+        || args.restore_ident.is_some()
+        || args.state.is_some()
+        || args.authorize.is_some()
+    {
+        panic!("Modals can't have state, an authorization policy, be cleaned up or restored!");
+    }
+
+    let ident = function.sig.ident;
+    let mut inputs = function.sig.inputs;
+    // Handlers that don't care about route parameters can leave them out.
+    if inputs.len() == 3 {
+        inputs.push(syn::parse_quote!(_params: crate::componet::RouteParams));
+    }
+    let vis = function.vis;
+    let ret = function.sig.output;
+    let statements = &function.block.stmts;
+    let struct_ident = Ident::new(
+        &format!("{}_modal", ident).to_case(Case::UpperCamel),
+        ident.span(),
+    );
+    let t = quote::quote! {
+        #vis struct #struct_ident;
+
+        #[::async_trait::async_trait]
+        impl crate::componet::Modal for #struct_ident {
+            #[inline]
+            fn route(&self) -> &'static str { #pattern }
+
+            async fn run(&self, #inputs) #ret {
+                #(#statements)*
+            }
         }
 
         #[allow(non_upper_case_globals)]
@@ -84,12 +388,67 @@ pub fn component(args: CompilerTokenStream, tokens: CompilerTokenStream) -> Comp
 struct ComponentArgs {
     // The name of the cleanup function.
     cleanup_ident: Option<Ident>,
+    // The name of the restore function.
+    restore_ident: Option<Ident>,
+    // The pattern of IDs the component handles.
+    route:         Option<LitStr>,
+    // The type of the state the component owns.
+    state:         Option<Type>,
+    // Who may use the component.
+    authorize:     Option<Expr>,
+    // Whether it's a modal handler.
+    modal:         bool,
 }
 
 impl Parse for ComponentArgs {
-    #[inline]
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let cleanup_ident = input.parse().ok();
-        Ok(Self { cleanup_ident })
+        let mut args = Self {
+            cleanup_ident: None,
+            restore_ident: None,
+            route:         None,
+            state:         None,
+            authorize:     None,
+            modal:         false,
+        };
+
+        // A lone identifier is the cleanup function.
+        let fork = input.fork();
+        let lone = fork.parse::<Ident>().ok().filter(|_| fork.is_empty());
+        if let Some(ident) = lone.filter(|x| x != "modal") {
+            input.parse::<Ident>()?;
+            args.cleanup_ident = Some(ident);
+            return Ok(args);
+        }
+
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            if key == "modal" {
+                args.modal = true;
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+                continue;
+            }
+
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "cleanup" => args.cleanup_ident = Some(input.parse()?),
+                "restore" => args.restore_ident = Some(input.parse()?),
+                "route" => args.route = Some(input.parse()?),
+                "state" => args.state = Some(input.parse()?),
+                "authorize" => args.authorize = Some(input.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "expected `modal`, `cleanup`, `restore`, `route`, `state` or `authorize`",
+                    ))
+                }
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(args)
     }
 }
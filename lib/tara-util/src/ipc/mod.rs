@@ -9,12 +9,12 @@ use socket::SocketExt;
 use tokio::{fs, sync::Mutex};
 use tracing::{debug, error, info, warn};
 
-use crate::{current_process_instance_count, error::IpcErr, paths};
+use crate::{current_process_instance_count, error::IpcErr, paths, presence::Presence};
 
 pub mod socket;
 
 /// An action reqested by the client to be performed by Tara
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ActionMessage {
     /// Closes the [`Client`]'s IPC connection
     EndTransmission,
@@ -25,6 +25,23 @@ pub enum ActionMessage {
         /// How old can logs be before they get filtered out
         lower_cutoff: DateTime<Utc>,
     },
+    /// Show `Presence` instead of the configured presence until Tara restarts.
+    SetPresence(Presence),
+    /// Go back to the configured presence.
+    ResetPresence,
+    /// Export everything Tara stores about a guild, see [`ResponseMessage::GuildBundle`].
+    ExportGuild { guild_id: u64, format: BundleFormat },
+    /// Replace everything Tara stores about a guild with an exported bundle, in either
+    /// format. The bundle is checked against the guild before anything is changed.
+    ImportGuild { guild_id: u64, bundle: String },
+}
+
+/// How an exported guild is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BundleFormat {
+    #[default]
+    Json,
+    Toml,
 }
 
 /// The server's response to a requested action
@@ -35,6 +52,8 @@ pub enum ResponseMessage {
     /// The error message is sent as a [`String`]
     ActionFailed(String),
     CommandLogs(Vec<super::logging::LoggedCommandEvent>),
+    /// An exported guild, written in the requested [`BundleFormat`].
+    GuildBundle(String),
 }
 
 impl<T: std::error::Error> From<T> for ResponseMessage {
@@ -174,7 +193,7 @@ impl Client {
         let mut responses = Vec::with_capacity(actions.len());
         let mut connection = self.connection.lock().await;
         for action in actions {
-            connection.write_serde(*action).await?;
+            connection.write_serde(action.clone()).await?;
             responses.push(connection.read_serde().await?);
         }
 
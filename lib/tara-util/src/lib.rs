@@ -5,6 +5,8 @@ pub mod error;
 pub mod ipc;
 pub mod logging;
 pub mod paths;
+/// How Tara presents itself on Discord, shared by the configuration and IPC.
+pub mod presence;
 
 /// Gets the number of instances of the current process
 ///
@@ -7,6 +7,7 @@ use lazy_static::lazy_static;
 #[cfg(target_os = "linux")]
 mod defaults {
     pub const FALLBACK_CONFIG_FILE: &str = "/etc/tara.d/tara.toml";
+    pub const FALLBACK_DATABASE_DIRECTORY: &str = "/var/lib/tara";
     pub const FALLBACK_ERROR_MESSAGES_FILE: &str = "/etc/tara.d/error_messages.json";
     pub const FALLBACK_SOCKET_DIRECTORY: &str = "/var";
 }
@@ -50,6 +51,18 @@ pub static ref TARA_CONFIGURATION_FILE: Option<PathBuf> = {
     paths.into_iter().find(|path| path.is_file())
 };
 
+/// Where a new configuration file is written, the first location
+/// [`TARA_CONFIGURATION_FILE`] looks in.
+pub static ref NEW_CONFIGURATION_FILE: Option<PathBuf> = {
+    if let Some(project_dirs) = TARA_PROJECT_DIR.as_ref() {
+        Some(project_dirs.config_dir().join("tara.toml"))
+    } else if !defaults::FALLBACK_CONFIG_FILE.is_empty() {
+        Some(PathBuf::from(defaults::FALLBACK_CONFIG_FILE))
+    } else {
+        None
+    }
+};
+
 /// # File Locations
 ///
 /// ## Linux
@@ -77,6 +90,32 @@ pub static ref ERROR_MESSAGES_FILE: Option<PathBuf> = {
     paths.into_iter().find(|path| path.is_file())
 };
 
+/// The SQLite database used when no database URL is configured.
+///
+/// # File Locations
+///
+/// ## Linux
+///
+/// 1. `$XDG_DATA_HOME/Tara/tara.db` or `$HOME/.local/share/Tara/tara.db`
+/// 2. `/var/lib/tara/tara.db`
+///
+/// ## macOS
+///
+/// 1. `$HOME/Library/Application Support/com.github.El-Wumbus.Tara/tara.db`
+///
+/// ## Windows
+///
+/// 1. `%APPDATA%\Tara\data\tara.db`
+pub static ref DATABASE_FILE: Option<PathBuf> = {
+    if let Some(project_dirs) = TARA_PROJECT_DIR.as_ref() {
+        Some(project_dirs.data_dir().join("tara.db"))
+    } else if !defaults::FALLBACK_DATABASE_DIRECTORY.is_empty() {
+        Some(PathBuf::from(defaults::FALLBACK_DATABASE_DIRECTORY).join("tara.db"))
+    } else {
+        None
+    }
+};
+
 pub static ref TARA_IPC_SOCKET_FILE: String = {
     use interprocess::local_socket::NameTypeSupport;
 
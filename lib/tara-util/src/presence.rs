@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// What Tara shows as its status and activity, configured in `[presence]` and changeable
+/// at runtime over IPC.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Presence {
+    /// The online status.
+    pub status:     Status,
+    /// The activities to rotate through. Their text can have template variables in it,
+    /// like `{guilds}`, which are filled in every time the activity is shown.
+    pub activities: Vec<Activity>,
+    /// How many seconds each activity is shown for before the next one.
+    pub interval:   u64,
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Self {
+            status:     Status::Online,
+            activities: vec![Activity {
+                kind: ActivityKind::Watching,
+                text: String::from("El-Wumbus/Tara on GitHub"),
+            }],
+            interval:   300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    #[default]
+    Online,
+    Idle,
+    DoNotDisturb,
+    Invisible,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Activity {
+    pub kind: ActivityKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Playing,
+    Listening,
+    Watching,
+    Competing,
+    /// A custom status, just the text.
+    Custom,
+}
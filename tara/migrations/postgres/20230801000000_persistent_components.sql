@@ -0,0 +1,6 @@
+-- Components are persisted so they keep working after a restart. `kind` is the name of the
+-- handler and `state` is whatever it needs to pick up where it left off (bincode encoded).
+-- Components sent in DMs don't have a guild.
+ALTER TABLE registered_components ALTER COLUMN guild_id DROP NOT NULL;
+ALTER TABLE registered_components ADD COLUMN IF NOT EXISTS kind TEXT NOT NULL DEFAULT '';
+ALTER TABLE registered_components ADD COLUMN IF NOT EXISTS state BYTEA NOT NULL DEFAULT '';
@@ -0,0 +1,3 @@
+-- Who may use a component, JSON encoded. Components registered before this can be used by
+-- whoever their handler allows.
+ALTER TABLE registered_components ADD COLUMN IF NOT EXISTS authorization TEXT;
@@ -0,0 +1,9 @@
+-- The settings a guild changed from their defaults, one row per setting. The value is the
+-- setting's value JSON encoded.
+CREATE TABLE IF NOT EXISTS guild_settings (
+    guild_id BIGINT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (guild_id, key),
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
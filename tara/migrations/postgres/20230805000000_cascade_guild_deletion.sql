@@ -0,0 +1,8 @@
+-- Everything that belongs to a guild is deleted with it when Tara leaves the guild.
+ALTER TABLE roles DROP CONSTRAINT IF EXISTS roles_guild_id_fkey;
+ALTER TABLE roles ADD CONSTRAINT roles_guild_id_fkey
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE;
+
+ALTER TABLE registered_components DROP CONSTRAINT IF EXISTS registered_components_guild_id_fkey;
+ALTER TABLE registered_components ADD CONSTRAINT registered_components_guild_id_fkey
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE;
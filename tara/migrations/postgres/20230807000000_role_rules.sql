@@ -0,0 +1,21 @@
+-- A self-assignable role can require another role first, and can be temporary: members
+-- that take it lose it again after `lifetime` seconds.
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS required_role BIGINT;
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS lifetime BIGINT;
+
+-- Roles every member gets when they join.
+CREATE TABLE IF NOT EXISTS auto_roles (
+    id BIGINT PRIMARY KEY NOT NULL,
+    guild_id BIGINT NOT NULL,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
+
+-- When members lose the temporary roles they took.
+CREATE TABLE IF NOT EXISTS temporary_roles (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    expiry_date TIMESTAMP NOT NULL,
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
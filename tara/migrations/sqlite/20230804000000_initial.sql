@@ -0,0 +1,37 @@
+-- The same tables as the Postgres migrations up to 20230803000000_guild_settings.sql.
+-- Binary blobs are bincode encoded and JSON is kept in TEXT, just like there.
+
+CREATE TABLE IF NOT EXISTS guilds (
+    id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT
+);
+
+-- Self-assignable roles related to their respective guilds
+CREATE TABLE IF NOT EXISTS roles (
+    id INTEGER PRIMARY KEY NOT NULL,
+    guild_id INTEGER NOT NULL,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id)
+);
+
+-- Components are persisted so they keep working after a restart. `kind` is the name of the
+-- handler, `state` is whatever it needs to pick up where it left off and `authorization` is
+-- who may use it. Components sent in DMs don't have a guild.
+CREATE TABLE IF NOT EXISTS registered_components (
+    componet_id TEXT PRIMARY KEY NOT NULL,
+    guild_id INTEGER,
+    channel_id INTEGER NOT NULL,
+    expiry_date TEXT, -- An optional chrono::NaiveDateTime in UTC (when to delete this record)
+    kind TEXT NOT NULL DEFAULT '',
+    state BLOB NOT NULL DEFAULT x'',
+    authorization TEXT,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id)
+);
+
+-- The settings a guild changed from their defaults, the value is JSON encoded.
+CREATE TABLE IF NOT EXISTS guild_settings (
+    guild_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (guild_id, key),
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
@@ -0,0 +1,27 @@
+-- Everything that belongs to a guild is deleted with it when Tara leaves the guild. SQLite
+-- can't change a foreign key, so the tables are rebuilt with the new one.
+CREATE TABLE roles_new (
+    id INTEGER PRIMARY KEY NOT NULL,
+    guild_id INTEGER NOT NULL,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
+INSERT INTO roles_new (id, guild_id) SELECT id, guild_id FROM roles;
+DROP TABLE roles;
+ALTER TABLE roles_new RENAME TO roles;
+
+CREATE TABLE registered_components_new (
+    componet_id TEXT PRIMARY KEY NOT NULL,
+    guild_id INTEGER,
+    channel_id INTEGER NOT NULL,
+    expiry_date TEXT,
+    kind TEXT NOT NULL DEFAULT '',
+    state BLOB NOT NULL DEFAULT x'',
+    authorization TEXT,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
+INSERT INTO registered_components_new
+    (componet_id, guild_id, channel_id, expiry_date, kind, state, authorization)
+    SELECT componet_id, guild_id, channel_id, expiry_date, kind, state, authorization
+    FROM registered_components;
+DROP TABLE registered_components;
+ALTER TABLE registered_components_new RENAME TO registered_components;
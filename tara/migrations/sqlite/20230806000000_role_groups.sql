@@ -0,0 +1,14 @@
+-- Self-assignable roles can be sorted into groups, which limit how many of their roles a
+-- member can have. A role is in at most one group, named by `roles.group_name`.
+CREATE TABLE IF NOT EXISTS role_groups (
+    guild_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    min_roles INTEGER NOT NULL DEFAULT 0,
+    max_roles INTEGER,
+    exclusive BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (guild_id, name),
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
+
+ALTER TABLE roles ADD COLUMN group_name TEXT;
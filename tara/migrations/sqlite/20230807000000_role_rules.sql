@@ -0,0 +1,21 @@
+-- A self-assignable role can require another role first, and can be temporary: members
+-- that take it lose it again after `lifetime` seconds.
+ALTER TABLE roles ADD COLUMN required_role INTEGER;
+ALTER TABLE roles ADD COLUMN lifetime INTEGER;
+
+-- Roles every member gets when they join.
+CREATE TABLE IF NOT EXISTS auto_roles (
+    id INTEGER PRIMARY KEY NOT NULL,
+    guild_id INTEGER NOT NULL,
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
+
+-- When members lose the temporary roles they took.
+CREATE TABLE IF NOT EXISTS temporary_roles (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    expiry_date TEXT NOT NULL,
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
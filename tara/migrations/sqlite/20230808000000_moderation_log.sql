@@ -0,0 +1,16 @@
+-- Everything done to members by automod or moderators. Cases are numbered from 1 in
+-- every guild.
+CREATE TABLE IF NOT EXISTS moderation_log (
+    guild_id INTEGER NOT NULL,
+    number INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    moderator_id INTEGER,
+    action TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    channel_id INTEGER,
+    content TEXT,
+    duration INTEGER,
+    created_date TEXT NOT NULL,
+    PRIMARY KEY (guild_id, number),
+    FOREIGN KEY (guild_id) REFERENCES guilds(id) ON DELETE CASCADE
+);
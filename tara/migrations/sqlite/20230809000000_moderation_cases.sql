@@ -0,0 +1,13 @@
+-- Bans and timeouts can be timed, they're undone at `expiry_date`. It's cleared once
+-- they're undone, or when they're superseded by another ban or timeout.
+ALTER TABLE moderation_log ADD COLUMN expiry_date TEXT;
+
+-- What moderators noted about a case after the fact.
+CREATE TABLE IF NOT EXISTS case_notes (
+    guild_id INTEGER NOT NULL,
+    number INTEGER NOT NULL,
+    moderator_id INTEGER NOT NULL,
+    note TEXT NOT NULL,
+    created_date TEXT NOT NULL,
+    FOREIGN KEY (guild_id, number) REFERENCES moderation_log(guild_id, number) ON DELETE CASCADE
+);
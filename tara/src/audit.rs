@@ -0,0 +1,449 @@
+//! The audit log: compact embeds about what happened in a guild, like edited and deleted
+//! messages, members coming and going, role changes and voice moves, posted in the
+//! guild's audit channel. Guilds pick which [`Event`]s are posted with their settings.
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use serenity::{
+    all::{ChannelId, GuildId, Member, Message, MessageId, Role, RoleId, Timestamp, User, UserId},
+    builder::{CreateEmbed, CreateEmbedFooter, CreateMessage},
+    http::Http,
+};
+use truncrate::TruncateToBoundary;
+
+use crate::{settings::GuildSettings, Result};
+
+/// Embed field values can't be longer.
+const MAX_FIELD: usize = 1024;
+/// How many messages [`Recent`] remembers across all guilds.
+const MAX_REMEMBERED: usize = 1000;
+
+/// A kind of event the audit log can post.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    MessageEdits,
+    MessageDeletes,
+    Joins,
+    Leaves,
+    /// Roles given to or taken from members, and roles being created, changed or deleted.
+    Roles,
+    /// Members joining, leaving or moving between voice channels.
+    Voice,
+}
+
+impl Event {
+    pub const ALL: [Self; 6] = [
+        Self::MessageEdits,
+        Self::MessageDeletes,
+        Self::Joins,
+        Self::Leaves,
+        Self::Roles,
+        Self::Voice,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MessageEdits => "message_edits",
+            Self::MessageDeletes => "message_deletes",
+            Self::Joins => "joins",
+            Self::Leaves => "leaves",
+            Self::Roles => "roles",
+            Self::Voice => "voice",
+        }
+    }
+}
+
+impl FromStr for Event {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.as_str() == s)
+            .ok_or_else(|| format!("`{s}` isn't an audit log event"))
+    }
+}
+
+/// What's remembered about a message, to show what it said once it's edited or deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remembered {
+    pub author_id:   UserId,
+    pub content:     String,
+    pub attachments: usize,
+}
+
+/// The most recent messages sent in guilds. Discord doesn't say what edited or deleted
+/// messages said, and Serenity forgets deleted messages before handlers get to them.
+#[derive(Debug, Clone, Default)]
+pub struct Recent(Arc<Mutex<RecentMessages>>);
+
+#[derive(Debug, Default)]
+struct RecentMessages {
+    messages: HashMap<MessageId, Remembered>,
+    /// Oldest first, so the oldest can be forgotten.
+    order:    VecDeque<MessageId>,
+}
+
+impl Recent {
+    /// Remember `message`, unless it's from a bot or not in a guild.
+    pub fn remember(&self, message: &Message) {
+        if message.author.bot || message.guild_id.is_none() {
+            return;
+        }
+        let remembered = Remembered {
+            author_id:   message.author.id,
+            content:     message.content.clone(),
+            attachments: message.attachments.len(),
+        };
+        let mut recent = self.0.lock().unwrap_or_else(|x| x.into_inner());
+        if recent.messages.insert(message.id, remembered).is_none() {
+            recent.order.push_back(message.id);
+        }
+        while recent.order.len() > MAX_REMEMBERED {
+            if let Some(oldest) = recent.order.pop_front() {
+                recent.messages.remove(&oldest);
+            }
+        }
+    }
+
+    /// Change what message `id` says to `content`, returning what it said before.
+    pub fn edit(&self, id: MessageId, content: &str) -> Option<Remembered> {
+        let mut recent = self.0.lock().unwrap_or_else(|x| x.into_inner());
+        let remembered = recent.messages.get_mut(&id)?;
+        let before = remembered.clone();
+        remembered.content = String::from(content);
+        Some(before)
+    }
+
+    /// Forget message `id`, returning what it said.
+    pub fn forget(&self, id: MessageId) -> Option<Remembered> {
+        let mut recent = self.0.lock().unwrap_or_else(|x| x.into_inner());
+        let remembered = recent.messages.remove(&id)?;
+        recent.order.retain(|x| *x != id);
+        Some(remembered)
+    }
+}
+
+/// Post `embed` about `event` in the audit channel of `guild_id`, unless it doesn't have
+/// one or doesn't want `event` posted.
+pub async fn post(
+    http: &Http,
+    settings: &GuildSettings,
+    guild_id: GuildId,
+    event: Event,
+    embed: CreateEmbed,
+) -> Result<()> {
+    let values = settings.get(guild_id).await?;
+    let Some(channel_id) = values.audit_channel() else {
+        return Ok(());
+    };
+    if !values.audit_events().contains(&event) {
+        return Ok(());
+    }
+
+    let embed = embed.timestamp(Timestamp::now());
+    channel_id
+        .send_message(http, CreateMessage::new().embed(embed))
+        .await?;
+    Ok(())
+}
+
+/// `content` cut down to fit a field, or a placeholder when there's none.
+fn field(content: &str) -> String {
+    if content.is_empty() {
+        return String::from("*Nothing*");
+    }
+    let cut = content.truncate_to_boundary(MAX_FIELD - 1);
+    if cut.len() < content.len() {
+        format!("{cut}…")
+    } else {
+        String::from(cut)
+    }
+}
+
+fn footer(text: String) -> CreateEmbedFooter { CreateEmbedFooter::new(text) }
+
+/// A message by `author_id` in `channel_id` was edited from `before`, when it was
+/// remembered, to `after`. Edits that didn't change the text, like links getting embeds,
+/// are `None`.
+pub fn message_edit(
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    author_id: UserId,
+    before: Option<&str>,
+    after: &str,
+) -> Option<CreateEmbed> {
+    if before == Some(after) {
+        return None;
+    }
+    let link = format!("https://discord.com/channels/{guild_id}/{channel_id}/{message_id}");
+    Some(
+        CreateEmbed::new()
+            .title("Message edited")
+            .description(format!("<@{author_id}> in <#{channel_id}> · [Jump]({link})"))
+            .field(
+                "Before",
+                before.map_or_else(|| String::from("*Too old to show*"), field),
+                false,
+            )
+            .field("After", field(after), false)
+            .footer(footer(format!("Message {message_id}"))),
+    )
+}
+
+/// Message `message_id` in `channel_id` was deleted. What it said is only known when it
+/// was `remembered`.
+pub fn message_delete(
+    channel_id: ChannelId,
+    message_id: MessageId,
+    remembered: Option<&Remembered>,
+) -> CreateEmbed {
+    let embed = CreateEmbed::new()
+        .title("Message deleted")
+        .footer(footer(format!("Message {message_id}")));
+    match remembered {
+        Some(message) => {
+            let mut content = field(&message.content);
+            if message.attachments > 0 {
+                let _ = write!(content, "\n*{} attachments*", message.attachments);
+            }
+            embed
+                .description(format!("<@{}> in <#{channel_id}>", message.author_id))
+                .field("Content", content, false)
+        }
+        None => embed.description(format!("In <#{channel_id}>, it's too old to show")),
+    }
+}
+
+/// `user` joined the guild.
+pub fn member_join(user: &User) -> CreateEmbed {
+    CreateEmbed::new()
+        .title("Member joined")
+        .description(format!("<@{}> ({})", user.id, user.name))
+        .field(
+            "Account created",
+            format!("<t:{}:R>", user.id.created_at().unix_timestamp()),
+            true,
+        )
+        .footer(footer(format!("User {}", user.id)))
+}
+
+/// `user` left the guild, or was kicked or banned. The roles they had are known when
+/// they were cached as `member`.
+pub fn member_leave(user: &User, member: Option<&Member>) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .title("Member left")
+        .description(format!("<@{}> ({})", user.id, user.name))
+        .footer(footer(format!("User {}", user.id)));
+    if let Some(member) = member {
+        if let Some(joined) = member.joined_at {
+            embed = embed.field("Joined", format!("<t:{}:R>", joined.unix_timestamp()), true);
+        }
+        if !member.roles.is_empty() {
+            embed = embed.field("Roles", field(&mentions(&member.roles)), false);
+        }
+    }
+    embed
+}
+
+/// `user` went from having the roles `before` to `after`. `None` when that's the same.
+pub fn member_roles(user: &User, before: &[RoleId], after: &[RoleId]) -> Option<CreateEmbed> {
+    let added = after
+        .iter()
+        .copied()
+        .filter(|x| !before.contains(x))
+        .collect::<Vec<_>>();
+    let removed = before
+        .iter()
+        .copied()
+        .filter(|x| !after.contains(x))
+        .collect::<Vec<_>>();
+    if added.is_empty() && removed.is_empty() {
+        return None;
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title("Member roles changed")
+        .description(format!("<@{}> ({})", user.id, user.name))
+        .footer(footer(format!("User {}", user.id)));
+    if !added.is_empty() {
+        embed = embed.field("Added", field(&mentions(&added)), false);
+    }
+    if !removed.is_empty() {
+        embed = embed.field("Removed", field(&mentions(&removed)), false);
+    }
+    Some(embed)
+}
+
+/// `role` was created.
+pub fn role_create(role: &Role) -> CreateEmbed {
+    CreateEmbed::new()
+        .title("Role created")
+        .description(format!("<@&{}> ({})", role.id, role.name))
+        .footer(footer(format!("Role {}", role.id)))
+}
+
+/// `role_id` was deleted, it's `role` when it was cached.
+pub fn role_delete(role_id: RoleId, role: Option<&Role>) -> CreateEmbed {
+    let name = role.map_or_else(|| String::from("*Not cached*"), |x| x.name.clone());
+    CreateEmbed::new()
+        .title("Role deleted")
+        .description(name)
+        .footer(footer(format!("Role {role_id}")))
+}
+
+/// `role` was changed from `before`. `None` when its name, color and permissions stayed
+/// the same, since reordering roles changes every role below.
+pub fn role_update(before: &Role, role: &Role) -> Option<CreateEmbed> {
+    let mut changes = Vec::new();
+    if before.name != role.name {
+        changes.push(("Name", before.name.clone(), role.name.clone()));
+    }
+    if before.colour != role.colour {
+        changes.push(("Color", before.colour.hex(), role.colour.hex()));
+    }
+    if before.permissions != role.permissions {
+        let names = |x: &Role| x.permissions.get_permission_names().join(", ");
+        changes.push(("Permissions", names(before), names(role)));
+    }
+    if changes.is_empty() {
+        return None;
+    }
+
+    let embed = CreateEmbed::new()
+        .title("Role changed")
+        .description(format!("<@&{}>", role.id))
+        .footer(footer(format!("Role {}", role.id)));
+    Some(changes.into_iter().fold(embed, |embed, (what, before, after)| {
+        embed.field(what, field(&format!("{before} → {after}")), false)
+    }))
+}
+
+/// `user_id` went from the voice channel `before` to `after`. `None` when they stayed,
+/// like when they muted themselves.
+pub fn voice(user_id: UserId, before: Option<ChannelId>, after: Option<ChannelId>) -> Option<CreateEmbed> {
+    let (title, description) = match (before, after) {
+        (before, after) if before == after => return None,
+        (None, Some(after)) => ("Joined voice", format!("<@{user_id}> joined <#{after}>")),
+        (Some(before), None) => ("Left voice", format!("<@{user_id}> left <#{before}>")),
+        (Some(before), Some(after)) => {
+            (
+                "Moved voice channels",
+                format!("<@{user_id}> moved from <#{before}> to <#{after}>"),
+            )
+        }
+        (None, None) => return None,
+    };
+    Some(
+        CreateEmbed::new()
+            .title(title)
+            .description(description)
+            .footer(footer(format!("User {user_id}"))),
+    )
+}
+
+fn mentions(roles: &[RoleId]) -> String {
+    roles
+        .iter()
+        .map(|x| format!("<@&{x}>"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    fn json(embed: CreateEmbed) -> Value { serde_json::to_value(embed).unwrap() }
+
+    #[test]
+    fn test_message_edit() {
+        let ids = (
+            GuildId::new(1),
+            ChannelId::new(2),
+            MessageId::new(3),
+            UserId::new(4),
+        );
+        let edit = |before, after| message_edit(ids.0, ids.1, ids.2, ids.3, before, after);
+
+        assert!(edit(Some("hi"), "hi").is_none());
+        let embed = json(edit(Some("hi"), "hello").unwrap());
+        assert_eq!(embed["fields"][0]["value"], "hi");
+        assert_eq!(embed["fields"][1]["value"], "hello");
+        assert!(embed["description"]
+            .as_str()
+            .unwrap()
+            .contains("https://discord.com/channels/1/2/3"));
+
+        let embed = json(edit(None, &"a".repeat(2000)).unwrap());
+        assert_eq!(embed["fields"][0]["value"], "*Too old to show*");
+        assert!(embed["fields"][1]["value"].as_str().unwrap().chars().count() <= MAX_FIELD);
+    }
+
+    #[test]
+    fn test_recent() {
+        let recent = Recent::default();
+        let message = |id: u64, bot: bool| {
+            serde_json::from_value::<Message>(serde_json::json!({
+                "id": id.to_string(),
+                "channel_id": "2",
+                "guild_id": "1",
+                "author": { "id": "4", "username": "tester", "discriminator": "0001", "avatar": null, "bot": bot },
+                "content": "hi",
+                "timestamp": "2023-08-10T00:00:00Z",
+                "tts": false,
+                "mention_everyone": false,
+                "mentions": [],
+                "mention_roles": [],
+                "attachments": [],
+                "embeds": [],
+                "pinned": false,
+                "type": 0,
+            }))
+            .unwrap()
+        };
+
+        recent.remember(&message(3, true));
+        assert_eq!(recent.forget(MessageId::new(3)), None);
+
+        recent.remember(&message(3, false));
+        assert_eq!(recent.edit(MessageId::new(3), "hello").unwrap().content, "hi");
+        assert_eq!(recent.forget(MessageId::new(3)).unwrap().content, "hello");
+        assert_eq!(recent.forget(MessageId::new(3)), None);
+
+        for id in 1..=MAX_REMEMBERED as u64 + 1 {
+            recent.remember(&message(id, false));
+        }
+        assert_eq!(recent.forget(MessageId::new(1)), None);
+        assert!(recent.forget(MessageId::new(2)).is_some());
+    }
+
+    #[test]
+    fn test_voice() {
+        let (user, a, b) = (UserId::new(1), ChannelId::new(2), ChannelId::new(3));
+
+        assert!(voice(user, Some(a), Some(a)).is_none());
+        assert!(voice(user, None, None).is_none());
+        assert_eq!(json(voice(user, None, Some(a)).unwrap())["title"], "Joined voice");
+        assert_eq!(json(voice(user, Some(a), None).unwrap())["title"], "Left voice");
+        assert_eq!(
+            json(voice(user, Some(a), Some(b)).unwrap())["description"],
+            "<@1> moved from <#2> to <#3>"
+        );
+    }
+
+    #[test]
+    fn test_event_names() {
+        for event in Event::ALL {
+            assert_eq!(event.as_str().parse::<Event>().unwrap(), event);
+        }
+        assert!("typing".parse::<Event>().is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Automod: messages that are profane, sexual, offensive or spam, or that use one of a
+//! guild's own words, are deleted, and their author is warned or timed out depending on
+//! the guild's settings. Everything automod does goes into the moderation log.
+use std::{fmt, str::FromStr};
+
+use chrono::Utc;
+use rustrict::{Censor, Type};
+use serenity::{
+    all::{Message, Timestamp},
+    builder::{CreateMessage, EditMember},
+    http::Http,
+};
+
+use crate::{
+    database::Database,
+    moderation::{self, Action, Case, Note},
+    settings::GuildSettings,
+    Error, Result,
+};
+
+/// A kind of message automod can flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Profane,
+    Sexual,
+    Offensive,
+    Spam,
+}
+
+impl Category {
+    pub const ALL: [Self; 4] = [Self::Profane, Self::Sexual, Self::Offensive, Self::Spam];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Profane => "profane",
+            Self::Sexual => "sexual",
+            Self::Offensive => "offensive",
+            Self::Spam => "spam",
+        }
+    }
+
+    fn kind(self) -> Type {
+        match self {
+            Self::Profane => Type::PROFANE,
+            Self::Sexual => Type::SEXUAL,
+            Self::Offensive => Type::OFFENSIVE,
+            Self::Spam => Type::SPAM,
+        }
+    }
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.as_str() == s)
+            .ok_or_else(|| format!("`{s}` isn't an automod category"))
+    }
+}
+
+/// Why a message was flagged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Flag {
+    Category(Category),
+    /// One of the guild's own words.
+    Word(String),
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Category(x) => write!(f, "The message was {}", x.as_str()),
+            Self::Word(x) => write!(f, "The message used the blocked word `{x}`"),
+        }
+    }
+}
+
+/// Whether `content` falls into one of `categories` or uses one of `words`. Only mild
+/// cases are let through, and words only match whole words.
+pub fn flag(content: &str, categories: &[Category], words: &[String]) -> Option<Flag> {
+    let padded = normalize(content);
+    if let Some(word) = words.iter().find(|x| {
+        let word = normalize(x);
+        !word.trim().is_empty() && padded.contains(&word)
+    }) {
+        return Some(Flag::Word(word.clone()));
+    }
+
+    let analysis = Censor::from_str(content).analyze();
+    categories
+        .iter()
+        .copied()
+        .find(|x| analysis.is(x.kind() & Type::MODERATE_OR_HIGHER))
+        .map(Flag::Category)
+}
+
+/// `text` in lowercase with everything but letters and digits turned into single spaces,
+/// and a space on either end, so words can be found with `contains`.
+fn normalize(text: &str) -> String {
+    let words = text
+        .to_lowercase()
+        .split(|x: char| !x.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(" {words} ")
+}
+
+/// Check `message` against its guild's automod settings and act on it. Returns whether
+/// the message was flagged, in which case it's gone.
+pub async fn moderate(
+    http: &Http,
+    database: &Database,
+    settings: &GuildSettings,
+    message: &Message,
+) -> Result<bool> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(false);
+    };
+    if message.author.bot || message.content.is_empty() {
+        return Ok(false);
+    }
+    let values = settings.get(guild_id).await?;
+    let Some(action) = values.automod_action() else {
+        return Ok(false);
+    };
+    if values.automod_exempt_channels().contains(&message.channel_id) {
+        return Ok(false);
+    }
+    let exempt = values.automod_exempt_roles();
+    if let Some(member) = &message.member {
+        if member.roles.iter().any(|x| exempt.contains(x)) {
+            return Ok(false);
+        }
+    }
+    let Some(flag) = flag(
+        &message.content,
+        &values.automod_categories(),
+        values.automod_words(),
+    ) else {
+        return Ok(false);
+    };
+
+    let duration = values.automod_timeout();
+    let until = Utc::now().timestamp() + i64::try_from(duration.as_secs()).unwrap_or_default();
+    let until = Timestamp::from_unix_timestamp(until).map_err(|_| Error::InternalLogic)?;
+
+    message.delete(http).await?;
+    let mut case = Case {
+        channel_id: Some(message.channel_id),
+        content: Some(message.content.clone()),
+        ..Case::new(guild_id, message.author.id, action, flag.to_string())
+    };
+    if action == Action::Timeout {
+        case = case.lasting(duration);
+    }
+    // The message is gone either way, so that's recorded before anything else can fail.
+    let case = moderation::record(http, database, settings, case).await?;
+
+    let (what, followed_up) = match action {
+        Action::Warn => {
+            let warning = format!("<@{}>, your message was removed. {flag}.", message.author.id);
+            let sent = message
+                .channel_id
+                .send_message(http, CreateMessage::new().content(warning))
+                .await;
+            ("warn the member", sent.map(|_| ()))
+        }
+        Action::Timeout => {
+            let edited = guild_id
+                .edit_member(
+                    http,
+                    message.author.id,
+                    EditMember::new().disable_communication_until_datetime(until),
+                )
+                .await;
+            ("time the member out", edited.map(|_| ()))
+        }
+        _ => return Ok(true),
+    };
+    if let Err(e) = followed_up {
+        note_failure(http, database, &case, &format!("Automod couldn't {what}: {e}")).await;
+    }
+    Ok(true)
+}
+
+/// Note `failure` on `case`, when automod only got as far as deleting the message, so the
+/// log doesn't claim more than happened.
+async fn note_failure(http: &Http, database: &Database, case: &Case, failure: &str) {
+    tracing::warn!("{failure} (case {} of {})", case.number, case.guild_id);
+    let note = match http.get_current_user().await {
+        Ok(tara) => {
+            Note {
+                moderator_id: tara.id,
+                text:         failure.to_string(),
+                created:      Utc::now(),
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Couldn't note the failure on case {}: {e}", case.number);
+            return;
+        }
+    };
+    if let Err(e) = database.add_case_note(case.guild_id, case.number, &note).await {
+        tracing::warn!("Couldn't note the failure on case {}: {e}", case.number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag() {
+        let words = [String::from("pineapple pizza"), String::from("heck")];
+
+        assert_eq!(
+            flag("I like PINEAPPLE  pizza!", &[], &words),
+            Some(Flag::Word(String::from("pineapple pizza")))
+        );
+        assert_eq!(
+            flag("what the heck", &[], &words),
+            Some(Flag::Word(String::from("heck")))
+        );
+        // Only whole words match.
+        assert_eq!(flag("check this out", &[], &words), None);
+
+        assert_eq!(
+            flag("fuck you", &Category::ALL, &[]),
+            Some(Flag::Category(Category::Profane))
+        );
+        assert_eq!(flag("fuck you", &[Category::Spam], &[]), None);
+        assert_eq!(flag("have a nice day", &Category::ALL, &[]), None);
+    }
+
+    #[test]
+    fn test_category_names() {
+        for category in Category::ALL {
+            assert_eq!(category.as_str().parse::<Category>().unwrap(), category);
+        }
+        assert!("rude".parse::<Category>().is_err());
+    }
+}
@@ -1,28 +1,40 @@
-use std::num::NonZeroU64;
-
 use serenity::{
-    all::{CommandDataOption, CommandDataOptionValue, CommandInteraction, RoleId},
-    builder::{CreateActionRow, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage},
+    all::{CommandDataOption, CommandDataOptionValue, CommandInteraction},
+    builder::{
+        CreateActionRow, CreateAutocompleteResponse, CreateEmbed, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateModal, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption,
+    },
     http::Http,
 };
 use tracing::{event, Level};
+use truncrate::TruncateToBoundary;
+
+use crate::{Error, Result};
 
-#[must_use]
 /// Gets the suboptions of a subcommand or subcommandgroup.
 ///
-/// # Panics
+/// # Errors
 ///
-/// Panics if `option.value` isn't a [`CommandDataOptionValue::SubCommand`] or
-/// [`CommandDataOptionValue::SubCommandGroup`]
-pub fn suboptions(option: &CommandDataOption) -> &Vec<CommandDataOption> {
-    let mut val = None;
+/// Returns [`Error::ExpectedSuboption`] if `option.value` isn't a
+/// [`CommandDataOptionValue::SubCommand`] or [`CommandDataOptionValue::SubCommandGroup`]
+pub fn suboptions(option: &CommandDataOption) -> Result<&Vec<CommandDataOption>> {
     match &option.value {
         CommandDataOptionValue::SubCommand(options) | CommandDataOptionValue::SubCommandGroup(options) => {
-            val = Some(options);
+            Ok(options)
         }
-        _ => (),
+        _ => Err(Error::ExpectedSuboption),
     }
-    val.unwrap()
+}
+
+/// Gets the first option out of `options`, this is the subcommand for commands that
+/// have them.
+///
+/// # Errors
+///
+/// Returns [`Error::ExpectedSuboption`] if there are no options.
+pub fn first_option(options: &[CommandDataOption]) -> Result<&CommandDataOption> {
+    options.first().ok_or(Error::ExpectedSuboption)
 }
 
 #[must_use]
@@ -56,13 +68,17 @@ pub enum CommandResponse {
     Embed(Box<CreateEmbed>),
     EmbedWithComponents(Box<CreateEmbed>, Vec<CreateActionRow>),
     Message(CreateInteractionResponseMessage),
+    /// Open a modal, its submission is handled by the [`crate::componet::Modal`] routed
+    /// to its custom ID.
+    Modal(Box<CreateModal>),
     None,
 }
 
 impl CommandResponse {
     pub fn new_string(s: impl Into<String>) -> Self { Self::from(s.into()) }
 
-    pub async fn send(self, command: &CommandInteraction, http: &Http) {
+    /// The message to respond with, if the command didn't already respond on its own.
+    pub fn into_message(self) -> Option<CreateInteractionResponseMessage> {
         let message = CreateInteractionResponseMessage::new();
         let response_message = match self {
             CommandResponse::String(s) => message.content(s),
@@ -72,9 +88,23 @@ impl CommandResponse {
                 message.embed(*embed).components(components)
             }
             CommandResponse::Message(message) => message,
-            CommandResponse::None => return,
+            CommandResponse::Modal(_) | CommandResponse::None => return None,
+        };
+        Some(response_message)
+    }
+
+    /// The response to send, if the command didn't already respond on its own.
+    pub fn into_response(self) -> Option<CreateInteractionResponse> {
+        match self {
+            CommandResponse::Modal(modal) => Some(CreateInteractionResponse::Modal(*modal)),
+            response => response.into_message().map(CreateInteractionResponse::Message),
+        }
+    }
+
+    pub async fn send(self, command: &CommandInteraction, http: &Http) {
+        let Some(response) = self.into_response() else {
+            return;
         };
-        let response = CreateInteractionResponse::Message(response_message);
         if let Err(e) = command.create_response(http, response).await {
             event!(
                 Level::ERROR,
@@ -89,12 +119,62 @@ impl From<String> for CommandResponse {
     fn from(value: String) -> Self { Self::String(value) }
 }
 
+/// Discord allows at most this many options in a select menu, and suggestions.
+const SELECT_MENU_MAX_OPTIONS: usize = 25;
+/// Discord allows at most this many characters in a select menu option's label and value.
+const SELECT_OPTION_MAX_LENGTH: usize = 100;
+
+/// A string select menu offering `options` as `(label, value)` pairs. Only the first 25
+/// are offered, labels are truncated to fit and options with values too long to fit are
+/// left out.
+pub fn string_select(
+    custom_id: impl Into<String>,
+    placeholder: &str,
+    options: impl IntoIterator<Item = (String, String)>,
+) -> CreateActionRow {
+    let options = options
+        .into_iter()
+        .filter(|(_, value)| value.len() <= SELECT_OPTION_MAX_LENGTH)
+        .take(SELECT_MENU_MAX_OPTIONS)
+        .map(|(label, value)| {
+            CreateSelectMenuOption::new(label.truncate_to_boundary(SELECT_OPTION_MAX_LENGTH), value)
+        })
+        .collect();
+    let menu =
+        CreateSelectMenu::new(custom_id, CreateSelectMenuKind::String { options }).placeholder(placeholder);
+    CreateActionRow::SelectMenu(menu)
+}
+
+/// Autocomplete suggestions offering `choices` as `(name, value)` pairs, with the same
+/// limits as [`string_select`].
+pub fn suggestions(choices: impl IntoIterator<Item = (String, String)>) -> CreateAutocompleteResponse {
+    choices
+        .into_iter()
+        .filter(|(_, value)| value.len() <= SELECT_OPTION_MAX_LENGTH)
+        .take(SELECT_MENU_MAX_OPTIONS)
+        .fold(CreateAutocompleteResponse::new(), |response, (name, value)| {
+            response.add_string_choice(name.truncate_to_boundary(SELECT_OPTION_MAX_LENGTH), value)
+        })
+}
+
+/// Replace the message a component is on with `embed`, removing its content and
+/// components. Used once a choice was made in a select menu.
+pub fn replace_with_embed(embed: CreateEmbed) -> CreateInteractionResponse {
+    CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content("")
+            .embed(embed)
+            .components(Vec::new()),
+    )
+}
+
 pub fn hex_color_code_to_rgb(color_code: &str) -> Option<(u8, u8, u8)> {
-    if color_code.len() != 7 || &color_code[..1] != "#" {
+    let hex = color_code.strip_prefix('#')?;
+    if hex.len() != 6 {
         return None;
     }
 
-    let value = u32::from_str_radix(&color_code[1..], 16).ok()?;
+    let value = u32::from_str_radix(hex, 16).ok()?;
 
     let red = ((value >> 16) & 255) as u8;
     let green = ((value >> 8) & 255) as u8;
@@ -114,7 +194,7 @@ pub mod unsplash {
     use url::Url;
 
     use super::hex_color_code_to_rgb;
-    use crate::{Error, Result};
+    use crate::{settings::SafeSearch, Error, Result};
 
     const UNSPLASH_REFFERAL_QUERY: &str = "utm_source=Tara&utm_medium=referral";
 
@@ -187,28 +267,36 @@ pub mod unsplash {
     }
 
     impl UnsplashImage {
-        pub async fn random(client_id: &str) -> Result<Self> {
-            let response = reqwest::get(format!(
-                "https://api.unsplash.com/photos/random?client_id={client_id}"
-            ))
-            .await?
-            .text()
-            .await?;
+        /// Get a random image from the Unsplash API at `base_url`.
+        pub async fn random(base_url: &str, client_id: &str) -> Result<Self> {
+            let response = reqwest::get(format!("{base_url}/photos/random?client_id={client_id}"))
+                .await?
+                .text()
+                .await?;
             let image = serde_json::from_str(&response).map_err(|e| Error::JsonParse(e.to_string()))?;
 
             Ok(image)
         }
 
+        /// Search the Unsplash API at `base_url` for images matching `query`. Strict safe
+        /// search asks Unsplash to filter harder than its default.
         pub async fn search(
+            base_url: &str,
             client_id: &str,
             query: &str,
             color: Option<String>,
             orientation: Option<String>,
+            safe_search: SafeSearch,
         ) -> Result<Vec<Self>> {
             let color = color.map_or_else(String::new, |x| format!("&color={x}"));
             let orientation = orientation.map_or_else(String::new, |x| format!("&orientation={x}"));
+            let content_filter = match safe_search {
+                SafeSearch::Strict => "&content_filter=high",
+                SafeSearch::Off | SafeSearch::Moderate => "",
+            };
             let response = reqwest::get(format!(
-                "https://api.unsplash.com/search/photos?client_id={client_id}&query={query}{color}{orientation}"
+                "{base_url}/search/photos?client_id={client_id}&\
+                 query={query}{color}{orientation}{content_filter}"
             ))
             .await?
             .text()
@@ -220,21 +308,26 @@ pub mod unsplash {
         }
     }
 
+    /// Append Unsplash's referral query to `url`, it's returned unchanged if it isn't a
+    /// valid URL.
+    fn with_referral(url: &str) -> String {
+        let Ok(mut url) = Url::from_str(url) else {
+            return url.to_string();
+        };
+        let query = url.query().map_or(String::from(UNSPLASH_REFFERAL_QUERY), |x| {
+            format!("{x}&{UNSPLASH_REFFERAL_QUERY}")
+        });
+        url.set_query(Some(&query));
+        url.to_string()
+    }
+
     impl From<&UnsplashImage> for CreateEmbed {
         fn from(value: &UnsplashImage) -> Self {
-            let image = {
-                let mut image = Url::from_str(&value.urls.regular).unwrap();
-                let image_query = image.query().map_or(String::from(UNSPLASH_REFFERAL_QUERY), |x| {
-                    format!("{x}&{UNSPLASH_REFFERAL_QUERY}")
-                });
-                image.set_query(Some(&image_query));
-                image
-            };
+            let image = with_referral(&value.urls.regular);
 
-            let color = {
-                let (r, g, b) = hex_color_code_to_rgb(&value.color).unwrap();
-                Color::from_rgb(r, g, b)
-            };
+            // Unsplash gives us colors like "#60544D", but don't trust it.
+            let color = hex_color_code_to_rgb(&value.color)
+                .map_or(Color::default(), |(r, g, b)| Color::from_rgb(r, g, b));
 
             let username = value.user.username.as_str();
             let user_url = format!("https://unsplash.com/@{username}?{UNSPLASH_REFFERAL_QUERY}");
@@ -250,14 +343,10 @@ pub mod unsplash {
                     profile_image.full,
                 ];
 
-                icons.into_iter().find_map(|icon| icon).map(|icon_url| {
-                    let mut url = Url::from_str(&icon_url).unwrap();
-                    let query = url.query().map_or(String::from(UNSPLASH_REFFERAL_QUERY), |x| {
-                        format!("{x}&{UNSPLASH_REFFERAL_QUERY}")
-                    });
-                    url.set_query(Some(&query));
-                    url.as_str().to_string()
-                })
+                icons
+                    .into_iter()
+                    .find_map(|icon| icon)
+                    .map(|icon_url| with_referral(&icon_url))
             };
 
             let mut embed_author =
@@ -276,7 +365,7 @@ pub mod unsplash {
 
             let mut embed = CreateEmbed::new()
                 .author(embed_author)
-                .image(image.as_str())
+                .image(image)
                 .color(color)
                 .footer(CreateEmbedFooter::new(format!("From Unsplash.{public_domain}")));
 
@@ -289,12 +378,54 @@ pub mod unsplash {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(super) struct ExistingRole {
-    pub(super) id: i64,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_select() {
+        let long = "a".repeat(150);
+        let options = (0..30).map(|x| (x.to_string(), x.to_string())).chain([
+            (long.clone(), String::from("long label")),
+            (String::from("long value"), long),
+        ]);
+        let menu = serde_json::to_value(string_select("pick", "Pick one", options)).unwrap();
+        let options = menu["components"][0]["options"].as_array().unwrap();
+
+        assert_eq!(menu["components"][0]["custom_id"], "pick");
+        assert_eq!(options.len(), 25);
+        assert_eq!(options[0]["label"], "0");
+
+        let menu = serde_json::to_value(string_select(
+            "pick",
+            "Pick one",
+            [
+                (long.clone(), String::from("long label")),
+                (String::from("long value"), long),
+            ],
+        ))
+        .unwrap();
+        let options = menu["components"][0]["options"].as_array().unwrap();
+        assert_eq!(options.len(), 1);
+        assert_eq!(options[0]["label"].as_str().unwrap().len(), 100);
+    }
 
-impl ExistingRole {
-    #[inline]
-    pub(super) fn id(self) -> RoleId { RoleId(NonZeroU64::new(self.id as u64).unwrap()) }
+    #[test]
+    fn test_suggestions() {
+        let choices = (0..30).map(|x| (x.to_string(), x.to_string()));
+        let response = serde_json::to_value(suggestions(choices)).unwrap();
+        let choices = response["choices"].as_array().unwrap();
+        assert_eq!(choices.len(), 25);
+        assert_eq!(choices[0]["value"], "0");
+
+        let long = "a".repeat(150);
+        let response = serde_json::to_value(suggestions([
+            (long.clone(), String::from("long name")),
+            (String::from("long value"), long),
+        ]))
+        .unwrap();
+        let choices = response["choices"].as_array().unwrap();
+        assert_eq!(choices.len(), 1);
+        assert_eq!(choices[0]["name"].as_str().unwrap().len(), 100);
+    }
 }
@@ -30,12 +30,12 @@ pub struct ExchangeRatesResponse {
 }
 
 impl ExchangeRatesResponse {
-    /// Makes an http reqest using the `api_key` and saves this JSON
-    /// data to `ECHANGE_RATE_FILE`
-    pub async fn fetch(api_key: &str) -> Result<Self> {
+    /// Makes an http reqest to the currency API at `base_url` using the `api_key` and
+    /// saves this JSON data to `ECHANGE_RATE_FILE`
+    pub async fn fetch(base_url: &str, api_key: &str) -> Result<Self> {
         // Construct request URL
         let url = format!(
-            "https://api.currencyapi.com/v3/latest?apikey={api_key}&currencies={}",
+            "{base_url}/v3/latest?apikey={api_key}&currencies={}",
             CURRENCIES_URL_PART.join("%2C")
         );
 
@@ -60,6 +60,9 @@ pub struct Converter {
     /// The exchange rates
     exchange_rates: ExchangeRates,
 
+    /// The base URL of the currency API
+    base_url: String,
+
     /// The api key for the currency API
     api_key: String,
 
@@ -68,9 +71,10 @@ pub struct Converter {
 }
 
 impl Converter {
-    pub async fn new(api_key: String, max_age: Duration) -> Result<Self> {
+    pub async fn new(base_url: String, api_key: String, max_age: Duration) -> Result<Self> {
         Ok(Self {
-            exchange_rates: ExchangeRates::fetch(&api_key).await?,
+            exchange_rates: ExchangeRates::fetch(&base_url, &api_key).await?,
+            base_url,
             api_key,
             max_age,
         })
@@ -101,7 +105,7 @@ impl Currency {
 
         if diff > max_age {
             let key = converter.api_key.as_str();
-            converter.exchange_rates = ExchangeRates::fetch(key).await?;
+            converter.exchange_rates = ExchangeRates::fetch(&converter.base_url, key).await?;
         }
         Ok(converter)
     }
@@ -144,8 +148,8 @@ macro_rules! make_currency_structures {
         }
 
         impl ExchangeRates {
-            pub async fn fetch(api_key: &str) -> Result<Self> {
-                let resp = ExchangeRatesResponse::fetch(api_key).await?;
+            pub async fn fetch(base_url: &str, api_key: &str) -> Result<Self> {
+                let resp = ExchangeRatesResponse::fetch(base_url, api_key).await?;
                 Ok(Self {
                     /// When the exchange rates were last fetched
                     when: Utc::now(),
@@ -288,6 +292,7 @@ mod tests {
                 PKR:  1.0,
                 CNY:  1.0,
             },
+            base_url:       String::new(),
             api_key:        String::new(),
             max_age:        Duration::days(69),
         };
@@ -373,6 +378,7 @@ mod tests {
                 PKR:  1.0,
                 CNY:  1.0,
             },
+            base_url:       String::new(),
             api_key:        String::new(),
             max_age:        Duration::days(69),
         };
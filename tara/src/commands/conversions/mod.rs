@@ -77,14 +77,19 @@ impl DiscordCommand for Conversions {
     }
 
     async fn run(&self, command: Arc<CommandInteraction>, args: CommandArguments) -> Result<CommandResponse> {
-        use super::common::suboptions;
-        let option = &command.data.options[0];
+        use super::common::{first_option, suboptions};
+        let option = first_option(&command.data.options)?;
         match &*option.name {
             "temperature" => {
-                let options = suboptions(option);
+                let options = suboptions(option)?;
                 // Get the options
-                let (CommandDataOptionValue::String(input), CommandDataOptionValue::String(output)) =
-                    (&options[0].value, &options[1].value)
+                let (
+                    Some(CommandDataOptionValue::String(input)),
+                    Some(CommandDataOptionValue::String(output)),
+                ) = (
+                    options.first().map(|x| &x.value),
+                    options.get(1).map(|x| &x.value),
+                )
                 else {
                     return Err(Error::InternalLogic);
                 };
@@ -106,10 +111,15 @@ impl DiscordCommand for Conversions {
                     Some(x) => x,
                 };
 
-                let options = suboptions(option);
+                let options = suboptions(option)?;
                 // Get the options
-                let (CommandDataOptionValue::String(input), CommandDataOptionValue::String(output)) =
-                    (&options[0].value, &options[1].value)
+                let (
+                    Some(CommandDataOptionValue::String(input)),
+                    Some(CommandDataOptionValue::String(output)),
+                ) = (
+                    options.first().map(|x| &x.value),
+                    options.get(1).map(|x| &x.value),
+                )
                 else {
                     return Err(Error::InternalLogic);
                 };
@@ -117,7 +127,14 @@ impl DiscordCommand for Conversions {
 
                 let converter = match CURRENCY_CONVERTER.lock().await.clone() {
                     Some(x) => x,
-                    None => currency::Converter::new(api_key, chrono::Duration::hours(6)).await?,
+                    None => {
+                        currency::Converter::new(
+                            args.config.endpoints.currency.clone(),
+                            api_key,
+                            chrono::Duration::hours(6),
+                        )
+                        .await?
+                    }
                 };
 
                 let (r, c) = currency::run(converter, input, output).await?;
@@ -140,3 +157,48 @@ impl DiscordCommand for Conversions {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::commands::harness::{self, fixture, string, subcommand, Stub, StubServer};
+
+    #[tokio::test]
+    async fn test_temperature() {
+        let response = harness::run(
+            "conversions",
+            json!([subcommand(
+                "temperature",
+                json!([string("value", "212F"), string("target", "C")])
+            )]),
+            harness::arguments(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(harness::message(response)["content"], "100.00 Celsius");
+    }
+
+    #[tokio::test]
+    async fn test_currency() {
+        let server = StubServer::start().await;
+        server
+            .route("/v3/latest", Stub::json(fixture!("currencyapi/latest.json")))
+            .await;
+
+        let response = harness::run(
+            "conversions",
+            json!([subcommand(
+                "currency",
+                json!([string("input", "10 USD"), string("output", "euros")])
+            )]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            harness::message(response)["content"],
+            "10.00 US Dollar [USD] → 9.00 Euro(s) [EUR]"
+        );
+    }
+}
@@ -58,12 +58,15 @@ impl DiscordCommand for Define {
             (word, audio)
         };
 
-        let words = get_word_definition(word.to_string()).await?;
+        let words = get_word_definition(&args.config.endpoints.dictionary, word.to_string()).await?;
         let max_content_length = defaults::content_character_limit_default();
 
         // Create an embed from everything
         let mut total_length = 0usize;
-        let word = words[0].clone();
+        let word = words
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::UndefinedWord(format!("\"{word}\" was not found")))?;
         let phonetic_text = word.phonetics.iter().find_map(|phonetic| phonetic.text.clone());
 
         // let phonetic_audio = word.phonetics.iter().filter_map(|phonetic| phonetic.audio)
@@ -115,7 +118,7 @@ impl DiscordCommand for Define {
                     break;
                 }
                 attachments.push(
-                    CreateAttachment::url(&args.context.http, audio_url)
+                    CreateAttachment::url(&args.http, audio_url)
                         .await
                         .map_err(|e| Error::SerenityHttpRequest(Box::new(e)))?,
                 );
@@ -174,7 +177,7 @@ struct NoWord {
     resolution: String,
 }
 
-/// Get the definition(s) for the provided `word`.
+/// Get the definition(s) for the provided `word` from the dictionary API at `base_url`.
 ///
 /// # Errors
 ///
@@ -182,9 +185,9 @@ struct NoWord {
 ///
 /// - An HTTP request fails
 /// - An API returns invalid or unexpected JSON
-async fn get_word_definition(word: String) -> Result<Words> {
+async fn get_word_definition(base_url: &str, word: String) -> Result<Words> {
     let word_ = urlencoding::encode(word.to_lowercase().trim()).to_string();
-    let request_url = format!("https://api.dictionaryapi.dev/api/v2/entries/en/{word_}");
+    let request_url = format!("{base_url}/api/v2/entries/en/{word_}");
 
     // Make the API call, parse the json to a `Page`.
     let http_response = reqwest::get(&request_url).await.map_err(Error::HttpRequest)?;
@@ -211,3 +214,95 @@ async fn get_word_definition(word: String) -> Result<Words> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        commands::harness::{self, assert_err, boolean, fixture, string, Stub, StubServer},
+        Error,
+    };
+
+    #[tokio::test]
+    async fn test_define() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/api/v2/entries/en/hello",
+                Stub::json(fixture!("dictionaryapi/hello.json")),
+            )
+            .await;
+
+        let response = harness::run(
+            "define",
+            json!([string("word", " Hello ")]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        let message = harness::message(response);
+        let embed = &message["embeds"][0];
+
+        assert_eq!(embed["title"], "hello (/həˈləʊ/)");
+        assert_eq!(embed["fields"][0]["name"], "[noun] hello");
+        assert_eq!(embed["fields"][1]["name"], "[interjection] hello");
+        assert_eq!(
+            embed["fields"][1]["value"],
+            "A greeting (salutation) said when meeting someone or acknowledging someone’s arrival or \
+             presence.\n> Hello, everyone."
+        );
+        assert_eq!(server.requests().await, ["GET /api/v2/entries/en/hello"]);
+    }
+
+    #[tokio::test]
+    async fn test_define_audio() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/api/v2/entries/en/hello",
+                Stub::json(fixture!("dictionaryapi/hello.json")),
+            )
+            .await
+            .route("/media/", Stub::bytes("audio/mpeg", vec![0xFF, 0xFB]))
+            .await;
+
+        harness::run(
+            "define",
+            json!([string("word", "hello"), boolean("audio", true)]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+
+        // Only phonetics that have audio are downloaded
+        assert_eq!(
+            server.requests().await,
+            [
+                "GET /api/v2/entries/en/hello",
+                "GET /media/pronunciations/en/hello-uk.mp3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_define_undefined_word() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/api/v2/entries/en/",
+                Stub::json(fixture!("dictionaryapi/no_definitions.json")).status(404),
+            )
+            .await;
+
+        assert_err!(
+            harness::run(
+                "define",
+                json!([string("word", "hellooo")]),
+                harness::arguments_with(&server)
+            )
+            .await,
+            Error::UndefinedWord(_)
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use component_macro::component;
+use serenity::{
+    all::{CommandInteraction, InputTextStyle, ModalInteraction},
+    builder::{
+        CreateActionRow, CreateCommand, CreateEmbed, CreateInputText, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateModal,
+    },
+    model::Permissions,
+};
+use url::Url;
+
+use super::{common::hex_color_code_to_rgb, CommandArguments, CommandResponse, DiscordCommand};
+use crate::{
+    componet::{DynModal, ModalFields},
+    Result,
+};
+
+pub const COMMAND: Embed = Embed;
+
+/// The modals opened by `/embed`.
+pub(super) const MODALS: &[DynModal] = &[&embed_builder];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Embed;
+
+#[async_trait]
+impl DiscordCommand for Embed {
+    fn register(&self) -> CreateCommand {
+        CreateCommand::new(self.name())
+            .description("Write an embed and send it to this channel")
+            .default_member_permissions(Permissions::MANAGE_MESSAGES)
+            .dm_permission(false)
+    }
+
+    async fn run(
+        &self,
+        _command: Arc<CommandInteraction>,
+        _args: CommandArguments,
+    ) -> Result<CommandResponse> {
+        let input = |style, label: &str, id: &str, required| {
+            CreateActionRow::InputText(CreateInputText::new(style, label, id).required(required))
+        };
+        let modal = CreateModal::new("embed-builder", "Create an embed").components(vec![
+            input(InputTextStyle::Short, "Title", "title", true),
+            input(InputTextStyle::Paragraph, "Description", "description", true),
+            input(InputTextStyle::Short, "Color (#RRGGBB)", "color", false),
+            input(InputTextStyle::Short, "Image URL", "image", false),
+        ]);
+
+        Ok(CommandResponse::Modal(Box::new(modal)))
+    }
+
+    fn name(&self) -> &'static str { "embed" }
+
+    fn help(&self) -> Option<String> {
+        Some(String::from(
+            "Opens a form to write an embed with a title, description, and optionally a color and an image. \
+             The embed is sent to the channel the command was used in.",
+        ))
+    }
+}
+
+#[component(modal, route = "embed-builder")]
+async fn embed_builder(
+    interaction: ModalInteraction,
+    args: CommandArguments,
+    fields: ModalFields,
+) -> anyhow::Result<()> {
+    let problem = |content: String| {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .ephemeral(true),
+        )
+    };
+
+    let embed = match build(&fields) {
+        Ok(embed) => embed,
+        Err(e) => {
+            interaction.create_response(&args.http, problem(e)).await?;
+            return Ok(());
+        }
+    };
+    // Discord can still refuse the embed, the form has to be answered either way.
+    let sent = interaction
+        .create_response(
+            &args.http,
+            CreateInteractionResponse::Message(CreateInteractionResponseMessage::new().embed(embed)),
+        )
+        .await;
+    if let Err(e) = sent {
+        interaction
+            .create_response(
+                &args.http,
+                problem(format!("Discord didn't accept the embed: {e}")),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// The embed described by the fields of the form, or what's wrong with them.
+fn build(fields: &ModalFields) -> std::result::Result<CreateEmbed, String> {
+    let text = |name| fields.get::<String>(name).map_err(|e| e.to_string());
+    let mut embed = CreateEmbed::new()
+        .title(text("title")?)
+        .description(text("description")?);
+
+    if let Some(image) = fields.optional::<String>("image").map_err(|e| e.to_string())? {
+        let image = image.trim();
+        match Url::parse(image) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => embed = embed.image(image),
+            _ => {
+                return Err(format!(
+                    "'{image}' isn't a link to an image like `https://example.com/cat.png`."
+                ))
+            }
+        }
+    }
+    if let Some(color) = fields.optional::<String>("color").map_err(|e| e.to_string())? {
+        match hex_color_code_to_rgb(color.trim()) {
+            Some(rgb) => embed = embed.color(rgb),
+            None => return Err(format!("'{color}' isn't a color like `#FF8800`.")),
+        }
+    }
+    Ok(embed)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use serenity::all::ActionRow;
+
+    use super::build;
+    use crate::{
+        commands::{common::CommandResponse, harness},
+        componet::ModalFields,
+    };
+
+    #[tokio::test]
+    async fn test_embed_opens_modal() {
+        let response = harness::run("embed", json!([]), harness::arguments())
+            .await
+            .unwrap();
+        let CommandResponse::Modal(modal) = response else {
+            panic!("expected a modal, got {response:?}");
+        };
+        let modal = serde_json::to_value(modal).unwrap();
+
+        assert_eq!(modal["custom_id"], "embed-builder");
+        assert_eq!(modal["components"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_build() {
+        let fields = |image: &str, color: &str| {
+            let rows: Vec<ActionRow> = serde_json::from_value(json!([
+                {"type": 1, "components": [{"type": 4, "custom_id": "title", "value": "Hello"}]},
+                {"type": 1, "components": [{"type": 4, "custom_id": "description", "value": "World"}]},
+                {"type": 1, "components": [{"type": 4, "custom_id": "color", "value": color}]},
+                {"type": 1, "components": [{"type": 4, "custom_id": "image", "value": image}]},
+            ]))
+            .unwrap();
+            ModalFields::new(&rows)
+        };
+
+        let embed =
+            serde_json::to_value(build(&fields("https://example.com/cat.png", "#FF8800")).unwrap()).unwrap();
+        assert_eq!(embed["title"], "Hello");
+        assert_eq!(embed["color"], 0xFF8800);
+        assert_eq!(embed["image"]["url"], "https://example.com/cat.png");
+        assert!(build(&fields("", "")).is_ok());
+
+        assert!(build(&fields("cat.png", "")).unwrap_err().contains("cat.png"));
+        assert!(build(&fields("ftp://example.com/cat.png", "")).is_err());
+        assert!(build(&fields("", "orange")).unwrap_err().contains("orange"));
+
+        let rows: Vec<ActionRow> = serde_json::from_value(json!([
+            {"type": 1, "components": [{"type": 4, "custom_id": "title", "value": "Hello"}]},
+        ]))
+        .unwrap();
+        assert!(build(&ModalFields::new(&rows)).is_err());
+    }
+}
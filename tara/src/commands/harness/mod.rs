@@ -0,0 +1,289 @@
+//! Everything needed to run commands in tests without a gateway connection or the
+//! network. Discord and every web API are replaced by a local [`StubServer`] answering
+//! with recorded responses from `tests/fixtures`.
+use std::{sync::Arc, time::Duration};
+
+use serde_json::{json, Value};
+use serenity::{
+    all::{ApplicationId, CommandInteraction, GuildId, Permissions},
+    client::Cache,
+    http::{Http, HttpBuilder},
+    model::guild::Guild,
+    prelude::{RwLock, TypeMap},
+};
+use sqlx::postgres::PgPoolOptions;
+
+pub(super) use self::stub::{Stub, StubServer};
+use super::{run_isolated, CommandArguments, CommandResponse, COMMANDS};
+use crate::{
+    componet::ComponentMap,
+    config::{endpoints::Endpoints, Configuration},
+    database::{self, Database, PostgresStorage},
+    settings::GuildSettings,
+    Result,
+};
+
+mod stub;
+
+/// The contents of a recorded response in `tests/fixtures`.
+macro_rules! fixture {
+    ($path:literal) => {
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/", $path))
+    };
+}
+pub(super) use fixture;
+
+/// Assert that `$result` is an error matching `$pattern`, and that it isn't there because
+/// the command panicked.
+macro_rules! assert_err {
+    ($result:expr, $pattern:pat) => {{
+        let result = $result;
+        assert!(
+            !matches!(result, Err($crate::Error::Incident(_))),
+            "command panicked: {result:?}"
+        );
+        assert!(matches!(result, Err($pattern)), "unexpected result: {result:?}");
+    }};
+}
+pub(super) use assert_err;
+
+/// The application ID the stubbed Discord API belongs to.
+pub(super) const APPLICATION_ID: u64 = 2;
+
+/// A `/name` interaction sent from a DM with the raw Discord `options`.
+pub(super) fn interaction(name: &str, options: Value) -> CommandInteraction {
+    serde_json::from_value(interaction_json(name, options)).expect("valid interaction fixture")
+}
+
+/// A `/name` interaction sent in `guild_id` by a member with `permissions`.
+fn member_interaction(
+    name: &str,
+    options: Value,
+    guild_id: GuildId,
+    permissions: Permissions,
+) -> CommandInteraction {
+    let mut interaction = interaction_json(name, options);
+    let user = interaction["user"].take();
+    interaction["guild_id"] = json!(guild_id.to_string());
+    interaction["member"] = json!({
+        "user": user,
+        "roles": [],
+        "joined_at": "2023-07-20T22:16:23.000000+00:00",
+        "deaf": false,
+        "mute": false,
+        "flags": 0,
+        "permissions": permissions.bits().to_string(),
+    });
+    interaction
+        .as_object_mut()
+        .expect("interactions are objects")
+        .remove("user");
+    serde_json::from_value(interaction).expect("valid interaction fixture")
+}
+
+fn interaction_json(name: &str, options: Value) -> Value {
+    json!({
+        "id": "1",
+        "application_id": APPLICATION_ID.to_string(),
+        "type": 2,
+        "token": "token",
+        "version": 1,
+        "locale": "en-US",
+        "channel_id": "3",
+        "entitlements": [],
+        "user": {
+            "id": "4",
+            "username": "tester",
+            "discriminator": "0001",
+            "avatar": null,
+        },
+        "data": {
+            "id": "5",
+            "name": name,
+            "type": 1,
+            "options": options,
+        },
+    })
+}
+
+/// A guild with the `roles` made by [`role`], as it would be found in the cache.
+pub(super) fn guild(id: u64, roles: Vec<Value>) -> Guild {
+    serde_json::from_value(json!({
+        "id": id.to_string(),
+        "name": "Test Guild",
+        "owner_id": "4",
+        "verification_level": 0,
+        "default_message_notifications": 0,
+        "explicit_content_filter": 0,
+        "mfa_level": 0,
+        "nsfw_level": 0,
+        "premium_tier": 0,
+        "system_channel_flags": 0,
+        "premium_progress_bar_enabled": false,
+        "preferred_locale": "en-US",
+        "features": [],
+        "roles": roles,
+        "emojis": [],
+        "stickers": [],
+        "joined_at": "2023-07-20T22:16:23.000000+00:00",
+        "large": false,
+        "member_count": 1,
+        "members": [],
+        "voice_states": [],
+        "channels": [],
+        "threads": [],
+        "presences": [],
+        "stage_instances": [],
+        "guild_scheduled_events": [],
+    }))
+    .expect("valid guild fixture")
+}
+
+/// A role in a [`guild`].
+pub(super) fn role(id: u64, name: &str, unicode_emoji: Option<&str>) -> Value {
+    json!({
+        "id": id.to_string(),
+        "name": name,
+        "color": 0,
+        "hoist": false,
+        "managed": false,
+        "mentionable": false,
+        "permissions": "0",
+        "position": 1,
+        "icon": null,
+        "unicode_emoji": unicode_emoji,
+    })
+}
+
+fn arguments_from(http: Http, config: Configuration, database: Database) -> CommandArguments {
+    CommandArguments {
+        http: Arc::new(http),
+        cache: Arc::new(Cache::new()),
+        data: Arc::new(RwLock::new(TypeMap::new())),
+        guild: None,
+        config: Arc::new(config),
+        component_map: ComponentMap::new(None),
+        settings: GuildSettings::new(database.clone()),
+        database,
+    }
+}
+
+/// A database that's never reached, for commands that shouldn't touch it.
+fn unreachable_database() -> Database {
+    let pool = PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(100))
+        .connect_lazy("postgres://tara@localhost/tara_test")
+        .expect("valid database URL");
+    Arc::new(PostgresStorage::new(pool))
+}
+
+/// Arguments with the default configuration. Nothing here connects to anything until
+/// it's used.
+pub(super) fn arguments() -> CommandArguments {
+    arguments_from(Http::new(""), Configuration::default(), unreachable_database())
+}
+
+/// Arguments with Discord and every web API pointed at `server`, and every API key
+/// filled in.
+pub(super) fn arguments_with(server: &StubServer) -> CommandArguments {
+    let http = HttpBuilder::new("")
+        .proxy(server.url())
+        .application_id(ApplicationId::new(APPLICATION_ID))
+        .build();
+
+    let mut config = Configuration::default();
+    config.endpoints = Endpoints::all(server.url());
+    config.secrets.currency_api_key = Some(String::from("currency-key"));
+    config.secrets.omdb_api_key = Some(String::from("omdb-key"));
+    config.secrets.unsplash_key = Some(String::from("unsplash-key"));
+
+    arguments_from(http, config, unreachable_database())
+}
+
+/// Connect to the database at `TARA_TEST_POSTGRES`, or a new in-memory SQLite database
+/// when it's not set, and run the migrations.
+pub(super) async fn database() -> Database {
+    let url = std::env::var("TARA_TEST_POSTGRES").unwrap_or_else(|_| String::from("sqlite::memory:"));
+    let database = database::open(&url)
+        .await
+        .expect("the test database is reachable");
+    database
+        .migrate()
+        .await
+        .expect("migrations apply to the test database");
+    database
+}
+
+/// [`arguments_with`] in `guild`, using `database`. The guild is inserted into the
+/// database, just like it would be on `guild_create`.
+pub(super) async fn guild_arguments(
+    server: &StubServer,
+    guild: Guild,
+    database: Database,
+) -> CommandArguments {
+    database
+        .add_guild(guild.id, Some(&guild.name))
+        .await
+        .expect("guild can be inserted");
+
+    let mut args = arguments_with(server);
+    args.guild = Some(guild);
+    args.settings = GuildSettings::new(database.clone());
+    args.database = database;
+    args
+}
+
+/// Run the `/name` command like the event handler would.
+pub(super) async fn run(name: &str, options: Value, args: CommandArguments) -> Result<CommandResponse> {
+    let cmd = *COMMANDS.get(name).expect("command exists");
+    run_isolated(cmd, Arc::new(interaction(name, options)), args).await
+}
+
+/// [`run`] the `/name` command as a member with `permissions` of the guild in `args`.
+pub(super) async fn run_as(
+    name: &str,
+    options: Value,
+    permissions: Permissions,
+    args: CommandArguments,
+) -> Result<CommandResponse> {
+    let cmd = *COMMANDS.get(name).expect("command exists");
+    let guild_id = args.guild.as_ref().expect("the arguments are for a guild").id;
+    let interaction = member_interaction(name, options, guild_id, permissions);
+    run_isolated(cmd, Arc::new(interaction), args).await
+}
+
+/// The JSON Discord would get as the message in response to the command.
+pub(super) fn message(response: CommandResponse) -> Value {
+    let message = response
+        .into_message()
+        .expect("the command responds with a message");
+    serde_json::to_value(message).expect("messages serialize")
+}
+
+pub(super) fn subcommand(name: &str, options: Value) -> Value {
+    json!({"name": name, "type": 1, "options": options})
+}
+
+pub(super) fn subcommand_group(name: &str, options: Value) -> Value {
+    json!({"name": name, "type": 2, "options": options})
+}
+
+pub(super) fn string(name: &str, value: &str) -> Value { json!({"name": name, "type": 3, "value": value}) }
+
+pub(super) fn integer(name: &str, value: i64) -> Value { json!({"name": name, "type": 4, "value": value}) }
+
+pub(super) fn boolean(name: &str, value: bool) -> Value { json!({"name": name, "type": 5, "value": value}) }
+
+pub(super) fn user_option(name: &str, id: u64) -> Value {
+    json!({"name": name, "type": 6, "value": id.to_string()})
+}
+
+pub(super) fn role_option(name: &str, id: u64) -> Value {
+    json!({"name": name, "type": 8, "value": id.to_string()})
+}
+
+pub(super) fn channel_option(name: &str, id: u64) -> Value {
+    json!({"name": name, "type": 7, "value": id.to_string()})
+}
+
+pub(super) fn number(name: &str, value: f64) -> Value { json!({"name": name, "type": 10, "value": value}) }
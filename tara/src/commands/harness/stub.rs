@@ -0,0 +1,142 @@
+//! A tiny HTTP server that stands in for Discord and the web APIs commands use, so tests
+//! never touch the network.
+use std::sync::Arc;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+    task::JoinHandle,
+};
+
+/// Replaced with the server's URL in every text response, for fixtures that link back to
+/// the server (e.g. an image API returning the URL of an image).
+pub const STUB_URL: &str = "{{stub}}";
+
+/// A canned response.
+#[derive(Debug, Clone)]
+pub struct Stub {
+    status:       u16,
+    content_type: &'static str,
+    body:         Vec<u8>,
+}
+
+impl Stub {
+    pub fn json(body: impl Into<String>) -> Self { Self::bytes("application/json", body.into().into_bytes()) }
+
+    pub fn html(body: impl Into<String>) -> Self {
+        Self::bytes("text/html; charset=utf-8", body.into().into_bytes())
+    }
+
+    pub fn bytes(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    /// Respond with `status` instead of `200 OK`.
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+type Routes = Arc<Mutex<Vec<(String, Stub)>>>;
+
+/// A local HTTP server answering requests with [`Stub`]s. Requests that don't match a
+/// route get an empty JSON object with `404 Not Found`.
+pub struct StubServer {
+    url:      String,
+    routes:   Routes,
+    requests: Arc<Mutex<Vec<String>>>,
+    task:     JoinHandle<()>,
+}
+
+impl StubServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("stub server can bind to localhost");
+        let url = format!("http://{}", listener.local_addr().expect("bound address"));
+        let routes = Routes::default();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let task = tokio::spawn({
+            let url = url.clone();
+            let routes = routes.clone();
+            let requests = requests.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve(stream, url.clone(), routes.clone(), requests.clone()));
+                }
+            }
+        });
+
+        Self {
+            url,
+            routes,
+            requests,
+            task,
+        }
+    }
+
+    /// The base URL of the server, without a trailing slash.
+    pub fn url(&self) -> &str { &self.url }
+
+    /// Answer requests whose path and query start with `prefix` using `stub`. When
+    /// several routes match, the longest prefix wins.
+    pub async fn route(&self, prefix: &str, stub: Stub) -> &Self {
+        self.routes.lock().await.push((prefix.to_string(), stub));
+        self
+    }
+
+    /// The request line (e.g. `GET /random`) of every request received so far.
+    pub async fn requests(&self) -> Vec<String> { self.requests.lock().await.clone() }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) { self.task.abort(); }
+}
+
+async fn serve(mut stream: TcpStream, url: String, routes: Routes, requests: Arc<Mutex<Vec<String>>>) {
+    // Only the head of the request is read, request bodies are ignored.
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|x| x == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => head.extend_from_slice(&buffer[..n]),
+        }
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or("/");
+    requests.lock().await.push(format!("{method} {target}"));
+
+    let stub = routes
+        .lock()
+        .await
+        .iter()
+        .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or_else(|| Stub::json("{}").status(404), |(_, stub)| stub.clone());
+
+    let body = match String::from_utf8(stub.body) {
+        Ok(text) => text.replace(STUB_URL, &url).into_bytes(),
+        Err(e) => e.into_bytes(),
+    };
+    let head = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        stub.status,
+        stub.content_type,
+        body.len()
+    );
+
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&body).await;
+    let _ = stream.shutdown().await;
+}
@@ -48,14 +48,14 @@ impl DiscordCommand for Help {
     }
 
     async fn run(&self, command: Arc<CommandInteraction>, args: CommandArguments) -> Result<CommandResponse> {
-        let command_name = command.data.options[0]
+        let command_name = super::common::first_option(&command.data.options)?
             .value
             .as_str()
             .ok_or(Error::InternalLogic)?
             .to_lowercase();
 
         if GLOBAL_COMMANDS.read().await.is_empty() {
-            let global_commands = serenity::all::Command::get_global_commands(&args.context.http).await?;
+            let global_commands = serenity::all::Command::get_global_commands(&args.http).await?;
             let mut lock = GLOBAL_COMMANDS.write().await;
             *lock = global_commands;
         }
@@ -139,3 +139,52 @@ fn _option_to_field(option: &CommandOption, suboption_depth: usize) -> (String,
     }
     (name, description, false, suboption_depth)
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        commands::harness::{self, assert_err, fixture, string, Stub, StubServer, APPLICATION_ID},
+        Error,
+    };
+
+    #[tokio::test]
+    async fn test_help() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                &format!("/api/v10/applications/{APPLICATION_ID}/commands"),
+                Stub::json(fixture!("discord/global_commands.json")),
+            )
+            .await;
+        let args = harness::arguments_with(&server);
+
+        let response = harness::run("help", json!([string("command", "define")]), args.clone())
+            .await
+            .unwrap();
+        let embed = &harness::message(response)["embeds"][0];
+        assert_eq!(embed["title"], "Define");
+        assert_eq!(embed["description"], "Define an english word");
+        assert_eq!(embed["fields"][0]["name"], "*`word`\\**");
+        assert_eq!(embed["fields"][1]["name"], "*`audio`*");
+
+        let response = harness::run("help", json!([string("command", "conversions")]), args.clone())
+            .await
+            .unwrap();
+        let embed = &harness::message(response)["embeds"][0];
+        assert_eq!(embed["fields"][0]["name"], "**`temperature`**");
+        assert_eq!(embed["fields"][1]["name"], "Additional Help");
+
+        assert_err!(
+            harness::run("help", json!([string("command", "nonexistent")]), args).await,
+            Error::CommandMisuse(_)
+        );
+
+        // The commands are only fetched once
+        assert_eq!(
+            server.requests().await,
+            [format!("GET /api/v10/applications/{APPLICATION_ID}/commands")]
+        );
+    }
+}
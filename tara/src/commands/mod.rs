@@ -4,29 +4,42 @@ use async_trait::async_trait;
 use lazy_static::lazy_static;
 use serenity::{
     all::{CommandInteraction, Guild},
-    builder::CreateCommand,
-    prelude::Context,
+    builder::{CreateCommand, CreateInteractionResponse},
+    client::Cache,
+    http::Http,
+    prelude::{Context, RwLock, TypeMap},
 };
-use sqlx::{Pool, Postgres};
 use tara_util::logging::CommandLogger;
-use tracing::info;
+use tracing::{error, info, warn};
 
-use crate::{commands::common::CommandResponse, componet, config, logging, Result};
+use crate::{
+    commands::common::CommandResponse, componet, config, database::Database, incident, logging, settings,
+    Error, Result,
+};
 
 mod common;
 mod conversions;
 mod define;
+mod embed;
+#[cfg(test)]
+mod harness;
 mod help;
+mod moderation;
 mod movie;
 #[cfg(feature = "music")]
 mod music;
+mod paginator;
 mod random;
 mod role;
 mod search;
 mod series;
 mod settings;
+#[cfg(test)]
+mod tests;
 mod wiki;
 
+pub(crate) use role::panel::refresh as refresh_role_panels;
+
 type Command = &'static (dyn DiscordCommand + Sync + Send);
 
 macro_rules! cmd {
@@ -50,6 +63,8 @@ lazy_static! {
             cmd!(help::COMMAND),
             cmd!(movie::COMMAND),
             cmd!(series::COMMAND),
+            cmd!(embed::COMMAND),
+            cmd!(moderation::COMMAND),
             #[cfg(feature = "music")]
             cmd!(music::COMMAND),
         ];
@@ -62,13 +77,62 @@ lazy_static! {
     };
 }
 
+/// Components registered by commands, by the module that registers them. They're found
+/// by kind when restoring registrations after a restart.
+pub(crate) static COMPONENTS: &[&[componet::DynComponent]] = &[
+    paginator::COMPONENTS,
+    wiki::COMPONENTS,
+    movie::COMPONENTS,
+    series::COMPONENTS,
+    role::COMPONENTS,
+    #[cfg(feature = "music")]
+    music::COMPONENTS,
+];
+
+/// Modals opened by commands, by the module that opens them.
+pub(crate) static MODALS: &[&[componet::DynModal]] = &[embed::MODALS, paginator::MODALS];
+
 #[derive(Clone)]
 pub struct CommandArguments {
-    pub(super) context:       Arc<Context>,
+    pub(super) http:          Arc<Http>,
+    pub(super) cache:         Arc<Cache>,
+    /// The client's [`TypeMap`], shared with [`Context::data`].
+    pub(super) data:          Arc<RwLock<TypeMap>>,
     pub(super) guild:         Option<Guild>,
     pub(super) config:        Arc<config::Configuration>,
     pub(super) component_map: componet::ComponentMap,
-    pub(super) database:      Pool<Postgres>,
+    pub(super) database:      Database,
+    pub(super) settings:      settings::GuildSettings,
+}
+
+impl CommandArguments {
+    pub(super) fn new(
+        context: &Context,
+        guild: Option<Guild>,
+        config: Arc<config::Configuration>,
+        component_map: componet::ComponentMap,
+        database: Database,
+        settings: settings::GuildSettings,
+    ) -> Self {
+        Self {
+            http: context.http.clone(),
+            cache: context.cache.clone(),
+            data: context.data.clone(),
+            guild,
+            config,
+            component_map,
+            database,
+            settings,
+        }
+    }
+
+    /// The settings of the guild the command is used in, or the defaults outside of one.
+    pub(super) async fn guild_settings(&self) -> Result<Arc<settings::Values>> {
+        match &self.guild {
+            Some(guild) => self.settings.get(guild.id).await,
+            None => Ok(Arc::default()),
+        }
+    }
 }
 
 
@@ -86,6 +150,16 @@ pub trait DiscordCommand {
     /// Additonal helpful information pertaining to usage to be displayed by the `/help`
     /// command.
     fn help(&self) -> Option<String> { None }
+
+    /// Suggestions for the option that's being typed in `command`, as `(name, value)`
+    /// pairs. Only commands with options marked `set_autocomplete` are asked.
+    async fn autocomplete(
+        &self,
+        _command: Arc<CommandInteraction>,
+        _args: CommandArguments,
+    ) -> Result<Vec<(String, String)>> {
+        Ok(Vec::new())
+    }
 }
 
 /// Run a command specified by its name.
@@ -98,7 +172,8 @@ pub async fn run_command(
     error_messages: Arc<config::ErrorMessages>,
     logger: CommandLogger,
     component_map: componet::ComponentMap,
-    database: Pool<Postgres>,
+    database: Database,
+    settings: settings::GuildSettings,
 ) {
     let command_event = logging::logged_command_event_from_interaction(&context.cache, &command);
     logger.enqueue(command_event).await;
@@ -113,15 +188,8 @@ pub async fn run_command(
         return;
     };
 
-    let context = Arc::new(context);
     let command = Arc::new(command);
-    let command_arguments = CommandArguments {
-        context: context.clone(),
-        guild,
-        config: config.clone(),
-        component_map,
-        database,
-    };
+    let command_arguments = CommandArguments::new(&context, guild, config, component_map, database, settings);
 
     // Run the command.
     let user = &command.user;
@@ -135,7 +203,7 @@ pub async fn run_command(
         command.data.name, command.data.id, user.name, user.id,
     );
 
-    match cmd.run(command.clone(), command_arguments).await {
+    match run_isolated(*cmd, command.clone(), command_arguments).await {
         Ok(response) => response.send(&command, &context.http).await,
         Err(e) => {
             let error_message = pick_error_message(&error_messages);
@@ -153,8 +221,67 @@ pub async fn run_command(
     }
 }
 
+/// Answer an autocomplete interaction with the suggestions of the command it's for.
+pub async fn autocomplete(
+    context: Context,
+    command: CommandInteraction,
+    guild: Option<Guild>,
+    config: Arc<config::Configuration>,
+    component_map: componet::ComponentMap,
+    database: Database,
+    settings: settings::GuildSettings,
+) {
+    let Some(cmd) = COMMANDS.get(command.data.name.as_str()) else {
+        return;
+    };
+
+    let command = Arc::new(command);
+    let command_arguments = CommandArguments::new(&context, guild, config, component_map, database, settings);
+    let choices = match suggest_isolated(*cmd, command.clone(), command_arguments).await {
+        Ok(choices) => choices,
+        Err(e) => {
+            warn!("Couldn't autocomplete \"{}\": {e}", command.data.name);
+            Vec::new()
+        }
+    };
+
+    let response = CreateInteractionResponse::Autocomplete(common::suggestions(choices));
+    if let Err(e) = command.create_response(&context.http, response).await {
+        error!("Couldn't send suggestions for \"{}\": {e}", command.data.name);
+    }
+}
+
+/// Run `cmd` on its own task so that a panic inside of it becomes an
+/// [`Error::Incident`] instead of taking the interaction down with it.
+async fn run_isolated(
+    cmd: Command,
+    command: Arc<CommandInteraction>,
+    args: CommandArguments,
+) -> Result<CommandResponse> {
+    let what = format!("Command \"{}\"", command.data.name);
+    incident::catch_panic(what, async move { cmd.run(command, args).await })
+        .await
+        .unwrap_or_else(|incident| Err(Error::Incident(incident.id)))
+}
+
+/// Ask `cmd` for suggestions on its own task, like [`run_isolated`], so a panic becomes
+/// an [`Error::Incident`].
+async fn suggest_isolated(
+    cmd: Command,
+    command: Arc<CommandInteraction>,
+    args: CommandArguments,
+) -> Result<Vec<(String, String)>> {
+    let what = format!("Autocompletion of \"{}\"", command.data.name);
+    incident::catch_panic(what, async move { cmd.autocomplete(command, args).await })
+        .await
+        .unwrap_or_else(|incident| Err(Error::Incident(incident.id)))
+}
+
 /// Randomly select an error message pre/postfix
-fn pick_error_message(error_messages: &config::ErrorMessages) -> &(String, String) {
+fn pick_error_message(error_messages: &config::ErrorMessages) -> (&str, &str) {
     use rand::seq::SliceRandom;
-    error_messages.messages.choose(&mut rand::thread_rng()).unwrap()
+    error_messages.messages.choose(&mut rand::thread_rng()).map_or(
+        ("There was an error", "Please try again."),
+        |(prefix, postfix)| (prefix.as_str(), postfix.as_str()),
+    )
 }
@@ -0,0 +1,719 @@
+//! `/mod`: warn, time out, kick, ban and unban members, and look through the moderation
+//! log those actions are recorded in.
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::StatusCode;
+use serenity::{
+    all::{
+        CommandDataOption, CommandDataOptionValue, CommandInteraction, CommandOptionType, Guild, GuildId,
+        RoleId, Timestamp, UserId,
+    },
+    builder::{
+        CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponseMessage,
+        EditMember,
+    },
+    http::Http,
+    model::Permissions,
+};
+use truncrate::TruncateToBoundary;
+
+use super::{
+    common::{first_option, suboptions},
+    paginator::{PageLoader, Paginator},
+    CommandArguments, CommandResponse, DiscordCommand,
+};
+use crate::{
+    database::Database,
+    moderation::{self, Action, Case, Note},
+    Error, Result,
+};
+
+pub const COMMAND: Mod = Mod;
+
+/// Discord doesn't time members out for longer.
+const MAX_TIMEOUT: Duration = Duration::from_secs(28 * 24 * 60 * 60);
+
+const CASES_PER_PAGE: usize = 10;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Mod;
+
+#[async_trait]
+impl DiscordCommand for Mod {
+    fn register(&self) -> CreateCommand {
+        let member = |description| {
+            CreateCommandOption::new(CommandOptionType::User, "member", description).required(true)
+        };
+        let reason = || {
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "reason",
+                "Why, this is recorded in the case",
+            )
+            .max_length(512)
+            .required(true)
+        };
+        let dm =
+            || CreateCommandOption::new(CommandOptionType::Boolean, "dm", "Send them a DM with the reason");
+        let number = || {
+            CreateCommandOption::new(CommandOptionType::Integer, "number", "The number of the case")
+                .min_int_value(1)
+                .required(true)
+        };
+        let subcommand =
+            |name, description| CreateCommandOption::new(CommandOptionType::SubCommand, name, description);
+
+        let options = vec![
+            subcommand("warn", "Warn a member")
+                .add_sub_option(member("The member to warn"))
+                .add_sub_option(reason())
+                .add_sub_option(dm()),
+            subcommand("timeout", "Stop a member from talking for a while")
+                .add_sub_option(member("The member to time out"))
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "duration",
+                        "How long, like `1h 30m`. At most 28 days",
+                    )
+                    .max_length(32)
+                    .required(true),
+                )
+                .add_sub_option(reason())
+                .add_sub_option(dm()),
+            subcommand("kick", "Kick a member out of the server")
+                .add_sub_option(member("The member to kick"))
+                .add_sub_option(reason())
+                .add_sub_option(dm()),
+            subcommand("ban", "Ban someone from the server")
+                .add_sub_option(member("Who to ban, they don't have to be a member"))
+                .add_sub_option(reason())
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "duration",
+                        "How long, like `7d`. The ban is for good when this isn't given",
+                    )
+                    .max_length(32),
+                )
+                .add_sub_option(dm()),
+            subcommand("unban", "Lift someone's ban")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::User, "user", "Who to unban").required(true),
+                )
+                .add_sub_option(reason()),
+            subcommand("cases", "List the moderation log, newest first").add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "member",
+                    "Only list the cases of this member",
+                ),
+            ),
+            CreateCommandOption::new(
+                CommandOptionType::SubCommandGroup,
+                "case",
+                "Look at or change a case",
+            )
+            .add_sub_option(subcommand("view", "Show a case").add_sub_option(number()))
+            .add_sub_option(
+                subcommand("edit", "Change the reason of a case")
+                    .add_sub_option(number())
+                    .add_sub_option(reason()),
+            )
+            .add_sub_option(
+                subcommand("note", "Add a note to a case")
+                    .add_sub_option(number())
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::String, "note", "What to add")
+                            .max_length(900)
+                            .required(true),
+                    ),
+            ),
+        ];
+
+        CreateCommand::new(self.name())
+            .description("Moderate the server's members")
+            .default_member_permissions(Permissions::MODERATE_MEMBERS)
+            .dm_permission(false)
+            .set_options(options)
+    }
+
+    async fn run(&self, command: Arc<CommandInteraction>, args: CommandArguments) -> Result<CommandResponse> {
+        let option = first_option(&command.data.options)?;
+        let guild = args.guild.as_ref().ok_or(Error::InternalLogic)?;
+
+        // The command's default permission only covers the lightest actions.
+        let (permission, name) = match &*option.name {
+            "kick" => (Permissions::KICK_MEMBERS, "Kick Members"),
+            "ban" | "unban" => (Permissions::BAN_MEMBERS, "Ban Members"),
+            _ => (Permissions::MODERATE_MEMBERS, "Moderate Members"),
+        };
+        let granted = command
+            .member
+            .as_ref()
+            .and_then(|x| x.permissions)
+            .unwrap_or_else(Permissions::empty);
+        if !granted.contains(permission) {
+            return Err(Error::MissingPermissions(String::from(name)));
+        }
+
+        match &*option.name {
+            "cases" => list(&command, option, guild, &args).await,
+            "case" => case(first_option(suboptions(option)?)?, &command, guild, &args).await,
+            _ => act(option, &command, guild, &args).await,
+        }
+    }
+
+    fn name(&self) -> &'static str { "mod" }
+
+    fn help(&self) -> Option<String> {
+        Some(String::from(
+            "Every action is recorded as a numbered case in the moderation log and posted in the log \
+             channel. Timed bans and timeouts are lifted on their own. Kicking needs the Kick Members \
+             permission, banning and unbanning the Ban Members permission, and everything else Moderate \
+             Members.",
+        ))
+    }
+}
+
+/// The value of the option called `name`.
+fn find<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a CommandDataOptionValue> {
+    options.iter().find(|x| x.name == name).map(|x| &x.value)
+}
+
+/// Run one of the subcommands that act on a member, and record the case.
+async fn act(
+    option: &CommandDataOption,
+    command: &CommandInteraction,
+    guild: &Guild,
+    args: &CommandArguments,
+) -> Result<CommandResponse> {
+    let options = suboptions(option)?;
+    let action = option.name.parse::<Action>().map_err(|_| Error::InternalLogic)?;
+    let user_id = find(options, "member")
+        .or_else(|| find(options, "user"))
+        .and_then(CommandDataOptionValue::as_user_id)
+        .ok_or(Error::InternalLogic)?;
+    let reason = find(options, "reason")
+        .and_then(CommandDataOptionValue::as_str)
+        .ok_or(Error::InternalLogic)?;
+    let dm = find(options, "dm")
+        .and_then(CommandDataOptionValue::as_bool)
+        .unwrap_or(false);
+    if user_id == command.user.id {
+        return Err(Error::CommandMisuse(String::from("You can't moderate yourself")));
+    }
+    let tara_id = args.cache.current_user().id;
+    let moderator_roles = command.member.as_ref().map_or(&[][..], |x| x.roles.as_slice());
+    let member_roles = command
+        .data
+        .resolved
+        .members
+        .get(&user_id)
+        .map(|x| x.roles.as_slice());
+    check_hierarchy(
+        guild,
+        tara_id,
+        (command.user.id, moderator_roles),
+        (user_id, member_roles),
+    )?;
+
+    let mut case = Case::new(guild.id, user_id, action, reason).by(command.user.id);
+    if let Some(text) = find(options, "duration").and_then(CommandDataOptionValue::as_str) {
+        case = case.lasting(duration(text, action)?);
+    }
+
+    // Kicked and banned members can't be reached once they're gone.
+    let notice = notice(&case, guild);
+    let dm_first = matches!(action, Action::Kick | Action::Ban);
+    let mut sent = true;
+    if dm && dm_first {
+        sent = send_dm(&args.http, user_id, &notice).await;
+    }
+    take(&case, &args.http).await?;
+    if dm && !dm_first {
+        sent = send_dm(&args.http, user_id, &notice).await;
+    }
+
+    let case = moderation::record(&args.http, &args.database, &args.settings, case).await?;
+    let mut message = CreateInteractionResponseMessage::new().embed(case.embed());
+    if !sent {
+        message = message.content("*They couldn't be sent a DM.*");
+    }
+    Ok(CommandResponse::Message(message))
+}
+
+/// Refuse to act on the owner of `guild`, on Tara, or on a member whose highest role
+/// isn't below the moderator's, unless the moderator owns the guild. Discord only checks
+/// the member's roles against Tara's. The member's roles are `None` when they aren't in
+/// the guild.
+fn check_hierarchy(
+    guild: &Guild,
+    tara_id: UserId,
+    (moderator_id, moderator_roles): (UserId, &[RoleId]),
+    (user_id, member_roles): (UserId, Option<&[RoleId]>),
+) -> Result<()> {
+    if user_id == guild.owner_id {
+        return Err(Error::CommandMisuse(String::from(
+            "The owner of the server can't be moderated",
+        )));
+    }
+    if user_id == tara_id {
+        return Err(Error::CommandMisuse(String::from("I can't moderate myself")));
+    }
+    let highest = |roles: &[RoleId]| {
+        roles
+            .iter()
+            .filter_map(|x| guild.roles.get(x))
+            .map(|x| x.position)
+            .max()
+            .unwrap_or_default()
+    };
+    match member_roles {
+        Some(roles) if moderator_id != guild.owner_id && highest(roles) >= highest(moderator_roles) => {
+            Err(Error::CommandMisuse(format!(
+                "<@{user_id}>'s highest role isn't below yours"
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// How long a timed action lasts, from text like `1h 30m`.
+fn duration(text: &str, action: Action) -> Result<Duration> {
+    let duration = humantime::parse_duration(text)
+        .map_err(|e| Error::CommandMisuse(format!("`{text}` isn't a duration like `1h 30m`: {e}")))?;
+    // Timed actions are checked once a minute.
+    if duration.as_secs() < 60 {
+        return Err(Error::CommandMisuse(String::from(
+            "Timed actions last a minute at least",
+        )));
+    }
+    if action == Action::Timeout && duration > MAX_TIMEOUT {
+        return Err(Error::CommandMisuse(String::from(
+            "Members can be timed out for 28 days at most",
+        )));
+    }
+    Ok(duration)
+}
+
+/// Take the action of `case` on Discord.
+async fn take(case: &Case, http: &Http) -> Result<()> {
+    let reason = case.reason.as_str();
+    let taken = match case.action {
+        Action::Warn => Ok(()),
+        Action::Timeout => {
+            let until = case
+                .expiry
+                .and_then(|x| Timestamp::from_unix_timestamp(x.timestamp()).ok())
+                .ok_or(Error::InternalLogic)?;
+            let edit = EditMember::new()
+                .disable_communication_until_datetime(until)
+                .audit_log_reason(reason);
+            case.guild_id
+                .edit_member(http, case.user_id, edit)
+                .await
+                .map(|_| ())
+        }
+        Action::Kick => case.guild_id.kick_with_reason(http, case.user_id, reason).await,
+        Action::Ban => case.guild_id.ban_with_reason(http, case.user_id, 0, reason).await,
+        Action::Unban => case.guild_id.unban(http, case.user_id).await,
+        Action::Delete => return Err(Error::InternalLogic),
+    };
+
+    match taken {
+        Ok(()) => Ok(()),
+        Err(serenity::Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {
+            let what = if case.action == Action::Unban {
+                "banned"
+            } else {
+                "in this server"
+            };
+            Err(Error::CommandMisuse(format!("<@{}> isn't {what}", case.user_id)))
+        }
+        Err(e) => Err(Error::Moderation(Box::new(e))),
+    }
+}
+
+/// What the member of `case` is told when they're sent a DM.
+fn notice(case: &Case, guild: &Guild) -> String {
+    let what = match case.action {
+        Action::Delete | Action::Warn => "warned in",
+        Action::Timeout => "timed out in",
+        Action::Kick => "kicked from",
+        Action::Ban => "banned from",
+        Action::Unban => "unbanned from",
+    };
+    let mut notice = format!("You were {what} **{}**", guild.name);
+    if let Some(duration) = case.duration {
+        notice.push_str(&format!(
+            " for {}",
+            humantime::format_duration(Duration::from_secs(duration))
+        ));
+    }
+    format!("{notice}: {}", case.reason)
+}
+
+/// Send `text` to `user_id` in a DM, returning whether it could be. Members can turn off
+/// DMs from servers.
+async fn send_dm(http: &Http, user_id: UserId, text: &str) -> bool {
+    let sent = match user_id.create_dm_channel(http).await {
+        Ok(channel) => channel.id.say(http, text).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = &sent {
+        tracing::debug!("Couldn't DM {user_id}: {e}");
+    }
+    sent.is_ok()
+}
+
+/// The `cases` subcommand.
+async fn list(
+    command: &CommandInteraction,
+    option: &CommandDataOption,
+    guild: &Guild,
+    args: &CommandArguments,
+) -> Result<CommandResponse> {
+    let member = find(suboptions(option)?, "member").and_then(CommandDataOptionValue::as_user_id);
+    let loader = CasePages {
+        database: args.database.clone(),
+        guild_id: guild.id,
+        member,
+    };
+    let count = loader.cases().await?.len().div_ceil(CASES_PER_PAGE);
+    if count == 0 {
+        return Ok(CommandResponse::new_string("There are no cases."));
+    }
+
+    Paginator::lazy(count, loader).send(command, args).await?;
+    Ok(CommandResponse::None)
+}
+
+/// The pages of `/mod cases`, built from the moderation log as it is when they're turned
+/// to rather than all up front.
+struct CasePages {
+    database: Database,
+    guild_id: GuildId,
+    /// Only list the cases of this member.
+    member:   Option<UserId>,
+}
+
+impl CasePages {
+    /// The cases to list, newest first.
+    async fn cases(&self) -> Result<Vec<Case>> {
+        Ok(self
+            .database
+            .cases(self.guild_id)
+            .await?
+            .into_iter()
+            .filter(|x| self.member.map_or(true, |member| x.user_id == member))
+            .rev()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl PageLoader for CasePages {
+    async fn page(&self, index: usize) -> anyhow::Result<CreateEmbed> {
+        let cases = self.cases().await?;
+        page(&cases, index).ok_or_else(|| anyhow::anyhow!("No page {index} of cases"))
+    }
+}
+
+/// Page `index` of `cases`, [`CASES_PER_PAGE`] cases on every page.
+fn page(cases: &[Case], index: usize) -> Option<CreateEmbed> {
+    let count = cases.len().div_ceil(CASES_PER_PAGE);
+    let lines = cases
+        .chunks(CASES_PER_PAGE)
+        .nth(index)?
+        .iter()
+        .map(|x| {
+            format!(
+                "`#{}` **{}** <@{}> <t:{}:d>: {}",
+                x.number,
+                x.action,
+                x.user_id,
+                x.created.timestamp(),
+                x.reason.truncate_to_boundary(100)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(
+        CreateEmbed::new()
+            .title("Cases")
+            .description(lines)
+            .footer(CreateEmbedFooter::new(format!("Page {} of {count}", index + 1))),
+    )
+}
+
+/// Run one of the `case` subcommands, which all show the case afterwards.
+async fn case(
+    option: &CommandDataOption,
+    command: &CommandInteraction,
+    guild: &Guild,
+    args: &CommandArguments,
+) -> Result<CommandResponse> {
+    let options = suboptions(option)?;
+    let number = find(options, "number")
+        .and_then(CommandDataOptionValue::as_i64)
+        .and_then(|x| u32::try_from(x).ok())
+        .ok_or(Error::InternalLogic)?;
+    let text = |name| {
+        find(options, name)
+            .and_then(CommandDataOptionValue::as_str)
+            .ok_or(Error::InternalLogic)
+    };
+    let missing = || Error::CommandMisuse(format!("There's no case {number}"));
+
+    let found = match &*option.name {
+        "view" => true,
+        "edit" => args.database.edit_case(guild.id, number, text("reason")?).await?,
+        "note" => {
+            let note = Note {
+                moderator_id: command.user.id,
+                text:         String::from(text("note")?),
+                created:      Utc::now(),
+            };
+            args.database.add_case_note(guild.id, number, &note).await?
+        }
+        _ => return Err(Error::InternalLogic),
+    };
+    if !found {
+        return Err(missing());
+    }
+
+    let case = args.database.case(guild.id, number).await?.ok_or_else(missing)?;
+    Ok(CommandResponse::Embed(case.embed().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::commands::harness::{
+        self, assert_err, integer, role, string, subcommand, subcommand_group, user_option, Stub, StubServer,
+    };
+
+    #[test]
+    fn test_check_hierarchy() {
+        let ranked = |id, position| {
+            let mut ranked = role(id, "Role", None);
+            ranked["position"] = json!(position);
+            ranked
+        };
+        // The tester, 4, owns the guild.
+        let guild = harness::guild(1, vec![ranked(2, 1), ranked(3, 5)]);
+        let user = |x| UserId::new(x);
+        let (tara, owner) = (user(9), user(4));
+        let (low, high) = ([RoleId::new(2)], [RoleId::new(3)]);
+
+        assert!(check_hierarchy(&guild, tara, (user(5), &high), (user(6), Some(&low))).is_ok());
+        assert_err!(
+            check_hierarchy(&guild, tara, (user(5), &high), (user(6), Some(&high))),
+            Error::CommandMisuse(_)
+        );
+        assert_err!(
+            check_hierarchy(&guild, tara, (user(5), &low), (user(6), Some(&high))),
+            Error::CommandMisuse(_)
+        );
+        assert_err!(
+            check_hierarchy(&guild, tara, (user(5), &[]), (user(6), Some(&[]))),
+            Error::CommandMisuse(_)
+        );
+        // The owner outranks everyone, and users that aren't members have no roles to compare.
+        assert!(check_hierarchy(&guild, tara, (owner, &[]), (user(6), Some(&high))).is_ok());
+        assert!(check_hierarchy(&guild, tara, (user(5), &[]), (user(6), None)).is_ok());
+        // Nobody moderates the owner or Tara.
+        assert_err!(
+            check_hierarchy(&guild, tara, (user(5), &high), (owner, None)),
+            Error::CommandMisuse(_)
+        );
+        assert_err!(
+            check_hierarchy(&guild, tara, (owner, &[]), (tara, Some(&low))),
+            Error::CommandMisuse(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_moderation() {
+        let database = harness::database().await;
+        let server = StubServer::start().await;
+        let guild_id = u64::from(rand::random::<u32>()) + 1;
+        let member = guild_id + 1;
+        let args =
+            harness::guild_arguments(&server, harness::guild(guild_id, vec![]), database.clone()).await;
+        let warn = json!([subcommand(
+            "warn",
+            json!([user_option("member", member), string("reason", "Spam")])
+        )]);
+        let moderator = Permissions::MODERATE_MEMBERS;
+
+        // Every action needs its permission, and moderators can't act on themselves.
+        assert_err!(
+            harness::run_as("mod", warn.clone(), Permissions::empty(), args.clone()).await,
+            Error::MissingPermissions(_)
+        );
+        let ban = |member, duration| {
+            json!([subcommand(
+                "ban",
+                json!([
+                    user_option("member", member),
+                    string("reason", "Raiding"),
+                    string("duration", duration)
+                ])
+            )])
+        };
+        assert_err!(
+            harness::run_as("mod", ban(member, "1d"), moderator, args.clone()).await,
+            Error::MissingPermissions(_)
+        );
+        assert_err!(
+            harness::run_as("mod", ban(4, "1d"), Permissions::BAN_MEMBERS, args.clone()).await,
+            Error::CommandMisuse(_)
+        );
+
+        let response = harness::run_as("mod", warn, moderator, args.clone())
+            .await
+            .unwrap();
+        assert_eq!(harness::message(response)["embeds"][0]["title"], "Case 1: warn");
+
+        for duration in ["soon", "30s"] {
+            assert_err!(
+                harness::run_as(
+                    "mod",
+                    ban(member, duration),
+                    Permissions::BAN_MEMBERS,
+                    args.clone()
+                )
+                .await,
+                Error::CommandMisuse(_)
+            );
+        }
+        server
+            .route(
+                &format!("/api/v10/guilds/{guild_id}/bans/{member}"),
+                Stub::bytes("application/json", Vec::new()).status(204),
+            )
+            .await;
+        harness::run_as("mod", ban(member, "1d"), Permissions::BAN_MEMBERS, args.clone())
+            .await
+            .unwrap();
+        assert!(server
+            .requests()
+            .await
+            .contains(&format!("PUT /api/v10/guilds/{guild_id}/bans/{member}")));
+
+        // Unbanning someone that isn't banned is a mistake.
+        let unban = json!([subcommand(
+            "unban",
+            json!([user_option("user", member + 1), string("reason", "Appealed")])
+        )]);
+        assert_err!(
+            harness::run_as("mod", unban, Permissions::BAN_MEMBERS, args.clone()).await,
+            Error::CommandMisuse(_)
+        );
+
+        let guild_id = GuildId(NonZeroU64::new(guild_id).unwrap());
+        let cases = database.cases(guild_id).await.unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[1].action, Action::Ban);
+        assert_eq!(cases[1].duration, Some(86400));
+        assert!(cases[1].expiry.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cases() {
+        let database = harness::database().await;
+        let server = StubServer::start().await;
+        let guild_id = u64::from(rand::random::<u32>()) + 1;
+        let args =
+            harness::guild_arguments(&server, harness::guild(guild_id, vec![]), database.clone()).await;
+        let guild_id = GuildId(NonZeroU64::new(guild_id).unwrap());
+        database
+            .add_case(&Case::new(guild_id, UserId::new(5), Action::Warn, "Spam"))
+            .await
+            .unwrap();
+        let case = |name, options| json!([subcommand_group("case", json!([subcommand(name, options)]))]);
+        let moderator = Permissions::MODERATE_MEMBERS;
+
+        harness::run_as(
+            "mod",
+            case(
+                "edit",
+                json!([integer("number", 1), string("reason", "Advertising")]),
+            ),
+            moderator,
+            args.clone(),
+        )
+        .await
+        .unwrap();
+        let response = harness::run_as(
+            "mod",
+            case(
+                "note",
+                json!([integer("number", 1), string("note", "Second time")]),
+            ),
+            moderator,
+            args.clone(),
+        )
+        .await
+        .unwrap();
+        let embed = &harness::message(response)["embeds"][0];
+        assert!(embed.to_string().contains("Advertising"));
+        assert!(embed.to_string().contains("Second time"));
+
+        assert_err!(
+            harness::run_as(
+                "mod",
+                case("view", json!([integer("number", 2)])),
+                moderator,
+                args.clone()
+            )
+            .await,
+            Error::CommandMisuse(_)
+        );
+
+        // `/mod cases` loads its pages as they're turned to.
+        let pages = CasePages {
+            database,
+            guild_id,
+            member: Some(UserId::new(6)),
+        };
+        assert!(pages.cases().await.unwrap().is_empty());
+        let pages = CasePages {
+            member: None,
+            ..pages
+        };
+        let first = serde_json::to_value(pages.page(0).await.unwrap()).unwrap();
+        assert!(first["description"].to_string().contains("Advertising"));
+        assert!(pages.page(1).await.is_err());
+    }
+
+    #[test]
+    fn test_pages() {
+        let cases = (1..=25)
+            .map(|x| {
+                Case {
+                    number: x,
+                    ..Case::new(GuildId::new(1), UserId::new(2), Action::Warn, "Spam")
+                }
+            })
+            .collect::<Vec<_>>();
+        let first = serde_json::to_value(page(&cases, 0)).unwrap();
+        let last = serde_json::to_value(page(&cases, 2)).unwrap();
+
+        assert!(first["description"]
+            .as_str()
+            .unwrap()
+            .starts_with("`#1` **warn** <@2>"));
+        assert_eq!(last["footer"]["text"], "Page 3 of 3");
+        assert_eq!(last["description"].as_str().unwrap().lines().count(), 5);
+        assert!(page(&cases, 3).is_none());
+    }
+}
@@ -1,17 +1,30 @@
 use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
+use component_macro::component;
 use rand::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serenity::{
-    all::{CommandInteraction, CommandOptionType},
-    builder::{CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter},
+    all::{CommandInteraction, CommandOptionType, ComponentInteraction},
+    builder::{
+        CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponseMessage,
+    },
 };
 
-use super::{common::CommandResponse, CommandArguments, DiscordCommand};
-use crate::{Error, Result};
+use super::{
+    common::{replace_with_embed, string_select, CommandResponse},
+    CommandArguments, DiscordCommand,
+};
+use crate::{
+    componet::{Authorization, Component, DynComponent, RouteParams, Selection},
+    config::Configuration,
+    Error, Result,
+};
 pub const COMMAND: Movie = Movie;
 
+/// The menu to pick a movie from when the title wasn't found.
+pub(super) const COMPONENTS: &[DynComponent] = &[&movie_pick];
+
 pub(super) const OMDB_API_KEYS: &[&str] = &[
     "4b447405", "eb0c0475", "7776cbde", "ff28f90b", "6c3a2d45", "b07b58c8", "ad04b643", "a95b5205",
     "777d9323", "2c2c3314", "b5cff164", "89a9f57d", "73a9858a", "efbd8357",
@@ -62,17 +75,19 @@ impl DiscordCommand for Movie {
             (title, year, full_plot)
         };
 
-        let api_key = {
-            let choose_default_key = || *OMDB_API_KEYS.choose(&mut thread_rng()).unwrap();
+        let api_key = api_key(&args.config);
+        let base_url = &args.config.endpoints.omdb;
 
-            args.config
-                .secrets
-                .omdb_api_key
-                .as_ref()
-                .map_or_else(choose_default_key, String::as_str)
+        let movie = match OmdbMovie::movie(base_url, api_key, title, year.clone(), full_plot).await {
+            // Offer the closest matches instead
+            Err(Error::NoSearchResults(e)) => {
+                let route = format!("movie-pick/{full_plot}");
+                return disambiguate(base_url, api_key, title, year, "movie", &route)
+                    .await
+                    .ok_or(Error::NoSearchResults(e));
+            }
+            movie => movie?,
         };
-
-        let movie = OmdbMovie::movie(api_key, title, year, full_plot).await?;
         let embed: CreateEmbed = movie.into();
 
         Ok(CommandResponse::Embed(Box::new(embed)))
@@ -81,6 +96,101 @@ impl DiscordCommand for Movie {
     fn name(&self) -> &'static str { "movie" }
 }
 
+/// The configured `OMDb` API key, or one of the default ones.
+pub(super) fn api_key(config: &Configuration) -> &str {
+    let choose_default_key = || *OMDB_API_KEYS.choose(&mut thread_rng()).unwrap();
+
+    config
+        .secrets
+        .omdb_api_key
+        .as_ref()
+        .map_or_else(choose_default_key, String::as_str)
+}
+
+/// A match from searching `OMDb`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct OmdbSearchResult {
+    #[serde(rename = "Title")]
+    pub title:   String,
+    #[serde(rename = "Year")]
+    pub year:    String,
+    #[serde(rename = "imdbID")]
+    pub imdb_id: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct OmdbSearchResponse {
+    #[serde(rename = "Search")]
+    search: Vec<OmdbSearchResult>,
+}
+
+/// Search `OMDb` for titles of `kind` (`movie` or `series`) like `title`, and respond
+/// with a menu to pick one of them routed to `route`. Returns `None` if nothing was
+/// found.
+pub(super) async fn disambiguate(
+    base_url: &str,
+    omdb_api_key: &str,
+    title: &str,
+    year: Option<String>,
+    kind: &str,
+    route: &str,
+) -> Option<CommandResponse> {
+    let year = year.map_or_else(String::new, |year| format!("&y={year}"));
+    let url = format!(
+        "{base_url}/?s={}{year}&apikey={omdb_api_key}&type={kind}",
+        urlencoding::encode(title)
+    );
+    let results = fetch::<OmdbSearchResponse>(&url).await.ok()?.search;
+    if results.is_empty() {
+        return None;
+    }
+
+    let options = results
+        .into_iter()
+        .map(|x| (format!("{} ({})", x.title, x.year), x.imdb_id));
+    let message = CreateInteractionResponseMessage::new()
+        .content(format!("Couldn't find \"{title}\", did you mean one of these?"))
+        .components(vec![string_select(route, "Pick a title", options)]);
+    Some(CommandResponse::Message(message))
+}
+
+/// Get `url` from `OMDb`, turning its error responses into [`Error::NoSearchResults`].
+pub(super) async fn fetch<T: DeserializeOwned>(url: &str) -> Result<T> {
+    let response = reqwest::get(url).await?.text().await?;
+
+    match serde_json::from_str::<T>(&response) {
+        Ok(x) => Ok(x),
+        Err(e) => {
+            let err = serde_json::from_str::<OmdbErrorResponse>(&response)
+                .map_err(|_| Error::JsonParse(e.to_string()))?;
+            Err(Error::NoSearchResults(err.error))
+        }
+    }
+}
+
+#[component(route = "movie-pick/{full}", authorize = Authorization::Invoker)]
+async fn movie_pick(
+    interaction: ComponentInteraction,
+    args: CommandArguments,
+    params: RouteParams,
+    selection: Selection,
+) -> anyhow::Result<()> {
+    let full_plot: bool = params.get("full")?;
+    let imdb_id: String = selection.one()?;
+
+    let movie = OmdbMovie::by_id(
+        &args.config.endpoints.omdb,
+        api_key(&args.config),
+        &imdb_id,
+        full_plot,
+    )
+    .await?;
+    interaction
+        .create_response(&args.http, replace_with_embed(movie.into()))
+        .await?;
+    Ok(())
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct OmdbRating {
@@ -154,8 +264,9 @@ pub(super) struct OmdbErrorResponse {
 }
 
 impl OmdbMovie {
-    /// Perform a title request from `OMDb`
+    /// Perform a title request from the `OMDb` API at `base_url`
     pub async fn movie(
+        base_url: &str,
         omdb_api_key: &str,
         title: &str,
         year: Option<String>,
@@ -164,20 +275,26 @@ impl OmdbMovie {
         let year = year.map_or_else(String::new, |year| format!("&y={year}"));
         let plot = if full_plot { "&plot=full" } else { "" };
         let url = format!(
-            "http://www.omdbapi.com/?t={}{year}{plot}&apikey={omdb_api_key}&type=movie",
+            "{base_url}/?t={}{year}{plot}&apikey={omdb_api_key}&type=movie",
             urlencoding::encode(title)
         );
 
-        let response = reqwest::get(&url).await?.text().await?;
+        Self::get(&url, full_plot).await
+    }
 
-        let mut movie = match serde_json::from_str::<Self>(&response) {
-            Ok(x) => Ok(x),
-            Err(e) => {
-                let err = serde_json::from_str::<OmdbErrorResponse>(&response)
-                    .map_err(|_| Error::JsonParse(e.to_string()))?;
-                Err(Error::NoSearchResults(err.error))
-            }
-        }?;
+    /// Get the movie with the IMDb ID `imdb_id` from the `OMDb` API at `base_url`
+    pub async fn by_id(base_url: &str, omdb_api_key: &str, imdb_id: &str, full_plot: bool) -> Result<Self> {
+        let plot = if full_plot { "&plot=full" } else { "" };
+        let url = format!(
+            "{base_url}/?i={}{plot}&apikey={omdb_api_key}",
+            urlencoding::encode(imdb_id)
+        );
+
+        Self::get(&url, full_plot).await
+    }
+
+    async fn get(url: &str, full_plot: bool) -> Result<Self> {
+        let mut movie: Self = fetch(url).await?;
 
         if full_plot {
             movie.plot = format!("||{}||", movie.plot);
@@ -205,7 +322,7 @@ impl From<OmdbMovie> for CreateEmbed {
                         .next()
                         .unwrap_or("0")
                         .parse::<u64>()
-                        .unwrap(),
+                        .unwrap_or_default(),
                 ))
                 .to_string()
             }
@@ -232,3 +349,111 @@ impl From<OmdbMovie> for CreateEmbed {
             .footer(CreateEmbedFooter::new(format!("IMDb ID: {}", value.imdb_id)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        commands::harness::{self, assert_err, boolean, fixture, integer, string, Stub, StubServer},
+        Error,
+    };
+
+    #[tokio::test]
+    async fn test_movie() {
+        let server = StubServer::start().await;
+        server
+            .route("/?t=", Stub::json(fixture!("omdb/movie.json")))
+            .await;
+
+        let response = harness::run(
+            "movie",
+            json!([string("title", "The Matrix"), integer("year", 1999)]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        let embed = &harness::message(response)["embeds"][0];
+
+        assert_eq!(embed["title"], "The Matrix (1999)");
+        assert_eq!(embed["footer"]["text"], "IMDb ID: tt0133093");
+        assert!(embed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|x| x["value"] == "Metascore: 73\nIMDb:8.7\nRotten Tomatoes: 83%"));
+        assert_eq!(
+            server.requests().await,
+            ["GET /?t=The%20Matrix&y=1999&apikey=omdb-key&type=movie"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_movie_full_plot() {
+        let server = StubServer::start().await;
+        server
+            .route("/?t=", Stub::json(fixture!("omdb/movie.json")))
+            .await;
+
+        let response = harness::run(
+            "movie",
+            json!([string("title", "The Matrix"), boolean("full", true)]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        let description = harness::message(response)["embeds"][0]["description"].clone();
+
+        // The plot is a spoiler
+        let description = description.as_str().unwrap();
+        assert!(description.starts_with("||") && description.ends_with("||"));
+    }
+
+    #[tokio::test]
+    async fn test_movie_not_found() {
+        let server = StubServer::start().await;
+        server
+            .route("/?t=", Stub::json(fixture!("omdb/not_found.json")))
+            .await;
+
+        assert_err!(
+            harness::run(
+                "movie",
+                json!([string("title", "Not a movie")]),
+                harness::arguments_with(&server)
+            )
+            .await,
+            Error::NoSearchResults(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_movie_disambiguation() {
+        let server = StubServer::start().await;
+        server
+            .route("/?t=", Stub::json(fixture!("omdb/not_found.json")))
+            .await
+            .route("/?s=", Stub::json(fixture!("omdb/search.json")))
+            .await;
+
+        let response = harness::run(
+            "movie",
+            json!([string("title", "Matrix"), boolean("full", true)]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        let menu = &harness::message(response)["components"][0]["components"][0];
+
+        assert_eq!(menu["custom_id"], "movie-pick/true");
+        assert_eq!(menu["options"][0]["label"], "The Matrix (1999)");
+        assert_eq!(menu["options"][0]["value"], "tt0133093");
+        assert_eq!(
+            server.requests().await,
+            [
+                "GET /?t=Matrix&plot=full&apikey=omdb-key&type=movie",
+                "GET /?s=Matrix&apikey=omdb-key&type=movie"
+            ]
+        );
+    }
+}
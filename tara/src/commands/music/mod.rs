@@ -1,40 +1,75 @@
 // I apologize to anyone reading this; this is a mess.
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
+use component_macro::component;
 use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
 use serenity::{
-    all::{ChannelId, CommandInteraction, CommandOptionType, Guild, GuildId, MessageId},
-    builder::{Builder, CreateCommand, CreateCommandOption, CreateEmbed, EditMessage},
+    all::{
+        ChannelId, CommandDataOption, CommandDataOptionValue, CommandInteraction, CommandOptionType,
+        ComponentInteraction, Guild, GuildId, Message, MessageId, Permissions, RoleId, UserId,
+    },
+    builder::{
+        Builder, CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, EditMessage,
+    },
     http::Http,
-    prelude::Context,
+    prelude::TypeMapKey,
 };
 use songbird::{
-    events::EventHandler as VoiceEventHandler, input::YoutubeDl, tracks::TrackHandle, Event, EventContext,
-    Songbird, TrackEvent,
+    events::EventHandler as VoiceEventHandler, input::YoutubeDl, tracks::TrackHandle, Call, Event,
+    EventContext, Songbird, TrackEvent,
 };
 use tokio::sync::Mutex;
 use tracing::error;
-use uuid::Uuid;
 
 use self::youtube::TrackInfo;
-use super::{common::CommandResponse, CommandArguments, DiscordCommand};
-use crate::{commands::common, Error, HttpKey, Result};
+use super::{
+    common::{replace_with_embed, string_select, CommandResponse},
+    paginator::Paginator,
+    CommandArguments, DiscordCommand,
+};
+use crate::{
+    commands::common,
+    componet::{Authorization, Component, DynComponent, RouteParams, Selection},
+    Error, HttpKey, Result,
+};
 
 mod youtube;
 
+/// The menu to pick a track from the search results.
+pub(super) const COMPONENTS: &[DynComponent] = &[&music_pick];
+
+/// How many upcoming tracks a page of `/music queue` shows.
+const TRACKS_PER_PAGE: usize = 10;
+/// How many search results to offer.
+const SEARCH_RESULTS: usize = 10;
+/// How long a query has to be before it's searched for suggestions.
+const MIN_SUGGESTION_LENGTH: usize = 3;
+/// The subcommands that change what everyone in the call hears, which need the DJ role
+/// when the guild has one.
+const DJ_SUBCOMMANDS: &[&str] = &[
+    "skip", "remove", "move", "shuffle", "clear", "stop", "pause", "unpause", "leave",
+];
+
 static YOUTUBE_CLIENT_CONFIG: Lazy<Arc<youtubei_rs::types::client::ClientConfig>> =
     Lazy::new(|| Arc::new(youtubei_rs::utils::default_client_config()));
 
-#[allow(clippy::type_complexity)]
-static CURRENTLY_PLAYING: Lazy<Arc<Mutex<HashMap<Uuid, (TrackInfo, TrackHandle)>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
-
-static GUILD_TO_TRACK_MAP: Lazy<Arc<Mutex<HashMap<GuildId, Uuid>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+/// A track in a guild's queue, kept in the typemap of its handle.
+#[derive(Debug, Clone)]
+struct QueuedTrack {
+    info:       TrackInfo,
+    /// The text channel the session was started in, where the track is announced as it
+    /// starts.
+    channel_id: ChannelId,
+    /// The message showing the track's status, once it's been announced.
+    message:    Option<(ChannelId, MessageId)>,
+}
 
-static GUILD_CHANNEL_MAP: Lazy<Arc<Mutex<HashMap<Uuid, MessageId>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+impl TypeMapKey for QueuedTrack {
+    type Value = QueuedTrack;
+}
 
 pub const COMMAND: Music = Music;
 
@@ -44,20 +79,65 @@ pub struct Music;
 #[async_trait]
 impl DiscordCommand for Music {
     fn register(&self) -> serenity::builder::CreateCommand {
+        let position = |name, description| {
+            CreateCommandOption::new(CommandOptionType::Integer, name, description)
+                .min_int_value(1)
+                .required(true)
+        };
         let play = CreateCommandOption::new(
             CommandOptionType::SubCommand,
             "play",
-            "Join your voice channel and play a song",
+            "Join your voice channel and play a song, or queue it when one is playing",
         )
         .add_sub_option(
             CreateCommandOption::new(
                 CommandOptionType::String,
-                "url",
-                "The YouTube URL of the track to be played",
+                "query",
+                "A YouTube URL, or what to search YouTube for",
             )
-            .required(true),
+            .required(true)
+            .set_autocomplete(true),
+        )
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "next",
+            "Play it after the current track instead of at the end of the queue",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "choose",
+            "Pick from the top search results instead of playing the first one",
+        ));
+        let queue = CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "queue",
+            "List the tracks that are coming up",
+        );
+        let skip = CreateCommandOption::new(CommandOptionType::SubCommand, "skip", "Skip the current track");
+        let remove = CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "remove",
+            "Take a track out of the queue",
+        )
+        .add_sub_option(position("position", "The track's position in the queue"));
+        let move_ = CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "move",
+            "Move a track to another position in the queue",
+        )
+        .add_sub_option(position("from", "The track's position in the queue"))
+        .add_sub_option(position("to", "The position to move it to"));
+        let shuffle = CreateCommandOption::new(CommandOptionType::SubCommand, "shuffle", "Shuffle the queue");
+        let clear = CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "clear",
+            "Clear the queue, the current track keeps playing",
+        );
+        let stop = CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "stop",
+            "Stop playback and clear the queue",
         );
-        let stop = CreateCommandOption::new(CommandOptionType::SubCommand, "stop", "Stop playback");
         let pause = CreateCommandOption::new(
             CommandOptionType::SubCommand,
             "pause",
@@ -70,7 +150,9 @@ impl DiscordCommand for Music {
         );
         let leave =
             CreateCommandOption::new(CommandOptionType::SubCommand, "leave", "Leave your voice channel");
-        let options = vec![leave, play, pause, unpause, stop];
+        let options = vec![
+            leave, play, queue, skip, remove, move_, shuffle, clear, pause, unpause, stop,
+        ];
 
         CreateCommand::new(self.name())
             .dm_permission(false)
@@ -91,67 +173,219 @@ impl DiscordCommand for Music {
             ));
         }
 
-        let Some(guild) = args.guild else {
+        let Some(guild) = args.guild.clone() else {
             return Err(Error::InternalLogic);
         };
-        let option = &command.data.options[0];
-        let manager = songbird::get(&args.context).await.unwrap();
+        let option = common::first_option(&command.data.options)?;
+        let manager = voice_manager(&args).await?;
+        if DJ_SUBCOMMANDS.contains(&&*option.name) {
+            // There's nothing to control outside of a call, so that's checked first.
+            call(&manager, guild.id)?;
+            if let Some(dj_role) = args.guild_settings().await?.dj_role() {
+                let allowed = command
+                    .member
+                    .as_ref()
+                    .is_some_and(|x| is_dj(&x.roles, x.permissions, dj_role));
+                if !allowed {
+                    let name = guild.roles.get(&dj_role).map_or("DJ", |x| x.name.as_str());
+                    return Err(Error::CommandMisuse(format!(
+                        "You need the {name} role to control the music!"
+                    )));
+                }
+            }
+        }
         match &*option.name {
             "play" => {
-                // Get the url
-                let mut options = common::suboptions(option).iter();
-                let Some(url_option) = options.next() else {
+                let options = common::suboptions(option)?;
+                let Some(query) = find(options, "query").and_then(CommandDataOptionValue::as_str) else {
                     return Err(Error::InternalLogic);
                 };
-                let Some(url) = url_option.value.as_str() else {
-                    return Err(Error::InternalLogic);
+                let flag = |name| {
+                    find(options, name)
+                        .and_then(CommandDataOptionValue::as_bool)
+                        .unwrap_or(false)
+                };
+                let (next, choose) = (flag("next"), flag("choose"));
+
+                let url = match parse_query(query)? {
+                    Query::Url(url) => url.to_string(),
+                    Query::Search(terms) => {
+                        let mut results =
+                            youtube::search(YOUTUBE_CLIENT_CONFIG.clone(), terms, SEARCH_RESULTS).await?;
+                        if results.is_empty() {
+                            return Err(Error::NoSearchResults(terms.to_string()));
+                        }
+                        if choose {
+                            let options = results.into_iter().map(|x| (x.label(), x.video_id));
+                            let message = CreateInteractionResponseMessage::new()
+                                .content(format!("Which one of these should I play for \"{terms}\"?"))
+                                .components(vec![string_select(
+                                    format!("music-pick/{next}"),
+                                    "Pick a track",
+                                    options,
+                                )]);
+                            return Ok(CommandResponse::Message(message));
+                        }
+                        results.swap_remove(0).url()
+                    }
                 };
 
-                // Check the url is a youtube url
-                if !youtube::YOUTUBE_REGEX.is_match(url) {
-                    return Err(Error::CommandMisuse(
-                        "Must provide a valid YouTube video/audio URL!".to_string(),
-                    ));
-                }
-
-                play(url, args.context.clone(), &manager, &guild, command.clone()).await
+                play(&url, next, &args, &manager, &guild, Reply::Command(&command)).await
+            }
+            "queue" => queue(&manager, guild.id, &command, &args).await,
+            "skip" => skip(&manager, guild.id).await,
+            "remove" => remove(&manager, guild.id, position(option, "position")?).await,
+            "move" => {
+                let (from, to) = (position(option, "from")?, position(option, "to")?);
+                move_(&manager, guild.id, from, to).await
             }
-            "stop" => stop(guild.id).await,
+            "shuffle" => shuffle(&manager, guild.id).await,
+            "clear" => clear(&manager, guild.id).await,
+            "stop" => stop(&manager, guild.id).await,
             "leave" => leave(&manager, guild.id).await,
-            "pause" => pause(guild.id).await,
-            "unpause" => unpause(guild.id).await,
+            "pause" => pause(&manager, guild.id).await,
+            "unpause" => unpause(&manager, guild.id).await,
             _ => return Err(Error::InternalLogic),
         }
     }
 
     fn name(&self) -> &'static str { "music" }
+
+    async fn autocomplete(
+        &self,
+        command: Arc<CommandInteraction>,
+        args: CommandArguments,
+    ) -> Result<Vec<(String, String)>> {
+        // Nothing is suggested for a command that can't be used.
+        let enabled = args.config.music.clone().unwrap_or_default().enabled;
+        let Some(focused) = command.data.autocomplete().filter(|_| enabled) else {
+            return Ok(Vec::new());
+        };
+        // Picking a suggestion fills in its URL, which is played as is.
+        match parse_query(focused.value) {
+            Ok(Query::Search(terms)) if terms.chars().count() >= MIN_SUGGESTION_LENGTH => {
+                let results = youtube::search(YOUTUBE_CLIENT_CONFIG.clone(), terms, SEARCH_RESULTS).await?;
+                Ok(results.iter().map(|x| (x.label(), x.url())).collect())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
 }
 
+/// Whether a member with `roles` and `permissions` may use the [`DJ_SUBCOMMANDS`].
+/// Members that can manage the server don't need the role.
+fn is_dj(roles: &[RoleId], permissions: Option<Permissions>, dj_role: RoleId) -> bool {
+    roles.contains(&dj_role) || permissions.is_some_and(|x| x.manage_guild())
+}
+
+/// The voice client, kept in the client's data.
+async fn voice_manager(args: &CommandArguments) -> Result<Arc<Songbird>> {
+    args.data
+        .read()
+        .await
+        .get::<songbird::SongbirdKey>()
+        .cloned()
+        .ok_or(Error::InternalLogic)
+}
+
+/// What `/music play` was asked to play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Query<'a> {
+    /// A YouTube URL.
+    Url(&'a str),
+    /// What to search YouTube for.
+    Search(&'a str),
+}
+
+/// Tell URLs apart from searches in `query`. Only YouTube URLs can be played.
+fn parse_query(query: &str) -> Result<Query<'_>> {
+    let query = query.trim();
+    if youtube::YOUTUBE_REGEX.is_match(query) {
+        return Ok(Query::Url(query));
+    }
+    if query.starts_with("http://") || query.starts_with("https://") {
+        return Err(Error::CommandMisuse(
+            "Must provide a valid YouTube video/audio URL!".to_string(),
+        ));
+    }
+    if query.is_empty() {
+        return Err(Error::CommandMisuse("Tell me what to play!".to_string()));
+    }
+    Ok(Query::Search(query))
+}
+
+/// What `/music play` answers: the command, or the menu its search results were picked
+/// from.
+#[derive(Clone, Copy)]
+enum Reply<'a> {
+    Command(&'a CommandInteraction),
+    Pick(&'a ComponentInteraction),
+}
+
+impl Reply<'_> {
+    fn user_id(self) -> UserId {
+        match self {
+            Self::Command(x) => x.user.id,
+            Self::Pick(x) => x.user.id,
+        }
+    }
+
+    fn channel_id(self) -> ChannelId {
+        match self {
+            Self::Command(x) => x.channel_id,
+            Self::Pick(x) => x.channel_id,
+        }
+    }
+
+    /// Answer with `embed`, returning the message it's shown in.
+    async fn show(self, http: &Http, embed: CreateEmbed) -> Result<Message> {
+        match self {
+            Self::Command(x) => {
+                CommandResponse::Embed(Box::new(embed)).send(x, http).await;
+                Ok(x.get_response(http).await?)
+            }
+            Self::Pick(x) => {
+                x.create_response(http, replace_with_embed(embed)).await?;
+                Ok(x.get_response(http).await?)
+            }
+        }
+    }
+}
+
+fn find<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a CommandDataOptionValue> {
+    options.iter().find(|x| x.name == name).map(|x| &x.value)
+}
+
+/// The queue position given as the `name` option of the subcommand `option`.
+fn position(option: &CommandDataOption, name: &str) -> Result<usize> {
+    find(common::suboptions(option)?, name)
+        .and_then(CommandDataOptionValue::as_i64)
+        .and_then(|x| usize::try_from(x).ok())
+        .ok_or(Error::InternalLogic)
+}
 
 async fn play(
     url: &str,
-    context: Arc<Context>,
+    next: bool,
+    args: &CommandArguments,
     manager: &Songbird,
     guild: &Guild,
-    command: Arc<CommandInteraction>,
+    reply: Reply<'_>,
 ) -> Result<CommandResponse> {
     let track_info = youtube::TrackInfo::from_youtube_url(YOUTUBE_CLIENT_CONFIG.clone(), url).await?;
-    let mut embed = CreateEmbed::from(track_info.clone());
+    let embed = CreateEmbed::from(track_info.clone());
 
     let (handler_lock, mut message) = match manager.get(guild.id) {
         Some(x) => {
             // Create inital response message
-            CommandResponse::Embed(Box::new(embed.clone()))
-                .send(&command, &context.http)
-                .await;
-            let response = command.get_response(&context.http).await?;
+            let response = reply.show(&args.http, embed.clone()).await?;
             (x, response)
         }
         None => {
             // Join
             let Some(voice_channel_id) = guild
                 .voice_states
-                .get(&command.user.id)
+                .get(&reply.user_id())
                 .and_then(|voice_state| voice_state.channel_id)
             else {
                 return Err(Error::CommandMisuse("You're not in a voice channel!".to_string()));
@@ -159,135 +393,339 @@ async fn play(
 
             // We send a progress message then edit it later because discord only gives us 3 seconds
             // to reply to a slash command.
-            CommandResponse::Embed(Box::new(embed.clone().description("Joining voice channel...")))
-                .send(&command, &context.http)
-                .await;
+            let joining = embed.clone().description("Joining voice channel...");
+            let response = reply.show(&args.http, joining).await?;
 
-            join(&context, manager, guild.id, command.channel_id, voice_channel_id).await;
+            join(manager, guild.id, voice_channel_id).await;
 
-            let response = command.get_response(&context.http).await?;
-            (manager.get(guild.id).unwrap(), response)
+            let handler = manager
+                .get(guild.id)
+                .ok_or_else(|| Error::CommandMisuse("I couldn't join your voice channel!".to_string()))?;
+            (handler, response)
         }
     };
 
     let http_client = {
-        let data = context.data.read().await;
-        data.get::<HttpKey>().cloned().expect("to exist in the typemap")
+        let data = args.data.read().await;
+        data.get::<HttpKey>().cloned().ok_or(Error::InternalLogic)?
     };
-    let mut handler = handler_lock.lock().await;
     let source = YoutubeDl::new(http_client, url.to_string());
-    let handle = handler.play_only_input(source.into());
-    let uuid = handle.uuid();
-
-    embed = embed.description("").field("Status", "Playing", false);
-
-    // Insert the currently playing track into `CURRENTLY_PLAYING`
-    let mut currently_playing = CURRENTLY_PLAYING.lock().await;
-    currently_playing.insert(uuid, (track_info, handle.clone()));
-    GUILD_TO_TRACK_MAP.lock().await.insert(guild.id, uuid);
-    GUILD_CHANNEL_MAP.lock().await.insert(uuid, message.id);
+    let (handle, position, playing) = {
+        let mut handler = handler_lock.lock().await;
+        let playing = handler.queue().current();
+        let handle = handler.enqueue_input(source.into()).await;
+        let mut position = handler.queue().len() - 1;
+        if next && position > 1 {
+            handler
+                .queue()
+                .modify_queue(|queue| move_track(queue, position, 1))?;
+            position = 1;
+        }
+        (handle, position, playing)
+    };
 
-    message
-        .edit(&context.http, EditMessage::new().embed(embed))
-        .await?;
+    // The session goes on in the channel of the track that's playing. A track that starts
+    // right away has this response as its message, the others are announced when they
+    // start.
+    let channel_id = match &playing {
+        Some(playing) => queued(playing).await.map(|x| x.channel_id),
+        None => None,
+    };
+    let track = QueuedTrack {
+        info:       track_info,
+        channel_id: channel_id.unwrap_or_else(|| reply.channel_id()),
+        message:    playing.is_none().then_some((message.channel_id, message.id)),
+    };
+    // The track's events look it up, so it's added before they're listened for.
+    handle.typemap().write().await.insert::<QueuedTrack>(track);
+
+    // These only fail once the track is gone, and then there's nothing left to update.
+    let http = args.http.clone();
+    let _ = handle.add_event(TrackEvent::End.into(), TrackEndNotifier(http.clone()));
+    let _ = handle.add_event(
+        TrackEvent::Pause.into(),
+        TrackPauseNotifier(TrackNotifier(http.clone())),
+    );
+    let _ = handle.add_event(TrackEvent::Play.into(), TrackPlayNotifier(TrackNotifier(http)));
+
+    let status = match position {
+        0 => String::from("Playing"),
+        x => format!("Queued at position {x}"),
+    };
+    let embed = embed.description("").field("Status", status, false);
+    message.edit(&args.http, EditMessage::new().embed(embed)).await?;
 
     Ok(CommandResponse::None)
 }
 
 /// Join the voice channel specified in `voice_channel_id` and add global event handlers.
-async fn join(
-    context: &Context,
-    manager: &Songbird,
-    guild_id: GuildId,
-    channel_id: ChannelId,
-    voice_channel_id: ChannelId,
-) {
+async fn join(manager: &Songbird, guild_id: GuildId, voice_channel_id: ChannelId) {
     if let Ok(lock) = manager.join(guild_id, voice_channel_id).await {
         let mut handler = lock.lock().await;
         handler.add_global_event(TrackEvent::Error.into(), TrackErrorNotifier);
-        handler.add_global_event(
-            TrackEvent::End.into(),
-            TrackEndNotifier {
-                channel_id,
-                http: context.http.clone(),
-            },
-        );
-        handler.add_global_event(
-            TrackEvent::Pause.into(),
-            TrackPauseNotifier {
-                channel_id,
-                http: context.http.clone(),
-            },
-        );
-        handler.add_global_event(
-            TrackEvent::Play.into(),
-            TrackPlayNotifier {
-                channel_id,
-                http: context.http.clone(),
-            },
-        )
     }
 }
 
-async fn leave(manager: &Songbird, guild_id: GuildId) -> Result<CommandResponse> {
+/// The call Tara is in in `guild_id`.
+fn call(manager: &Songbird, guild_id: GuildId) -> Result<Arc<Mutex<Call>>> {
     manager
         .get(guild_id)
-        .ok_or_else(|| Error::CommandMisuse("I'm not in a voice channel!".to_string()))?;
+        .ok_or_else(|| Error::CommandMisuse("I'm not in a voice channel!".to_string()))
+}
+
+/// The call in `guild_id` and the track that's playing in it.
+async fn current(manager: &Songbird, guild_id: GuildId) -> Result<(Arc<Mutex<Call>>, TrackHandle, String)> {
+    let call = call(manager, guild_id)?;
+    let handle = call
+        .lock()
+        .await
+        .queue()
+        .current()
+        .ok_or_else(|| Error::CommandMisuse("Nothing's playing!".to_string()))?;
+    let title = title(&handle).await;
+    Ok((call, handle, title))
+}
+
+/// What's kept about the track `handle` plays.
+async fn queued(handle: &TrackHandle) -> Option<QueuedTrack> {
+    handle.typemap().read().await.get::<QueuedTrack>().cloned()
+}
+
+/// The title of the track `handle` plays.
+async fn title(handle: &TrackHandle) -> String {
+    queued(handle)
+        .await
+        .map_or_else(|| String::from("The track"), |x| x.info.title)
+}
 
+async fn leave(manager: &Songbird, guild_id: GuildId) -> Result<CommandResponse> {
+    call(manager, guild_id)?.lock().await.queue().stop();
     manager.remove(guild_id).await?;
-    let mut guild_track_map = GUILD_TO_TRACK_MAP.lock().await;
-    if let Some(uuid) = guild_track_map.remove(&guild_id) {
-        CURRENTLY_PLAYING.lock().await.remove(&uuid);
-        GUILD_CHANNEL_MAP.lock().await.remove(&uuid);
-    }
 
     Ok(CommandResponse::EphemeralString(
         "I left your voice channel!".to_string(),
     ))
 }
 
-async fn stop(guild_id: GuildId) -> Result<CommandResponse> {
-    let guild_track_map = GUILD_TO_TRACK_MAP.lock().await;
-    let uuid = guild_track_map
-        .get(&guild_id)
-        .ok_or_else(|| Error::InternalLogic)?;
-    let currently_playing = CURRENTLY_PLAYING.lock().await;
-    let (track, track_handle) = currently_playing.get(uuid).ok_or_else(|| Error::InternalLogic)?;
-    let _ = track_handle.stop();
+async fn queue(
+    manager: &Songbird,
+    guild_id: GuildId,
+    command: &CommandInteraction,
+    args: &CommandArguments,
+) -> Result<CommandResponse> {
+    let handles = call(manager, guild_id)?.lock().await.queue().current_queue();
+    let mut tracks = Vec::with_capacity(handles.len());
+    for handle in &handles {
+        if let Some(track) = queued(handle).await {
+            tracks.push(track.info);
+        }
+    }
+    if tracks.is_empty() {
+        return Ok(CommandResponse::EphemeralString("Nothing's playing.".to_string()));
+    }
+
+    Paginator::new(pages(&tracks)).send(command, args).await?;
+    Ok(CommandResponse::None)
+}
+
+/// The pages of `/music queue`, `tracks` starts with the one that's playing.
+fn pages(tracks: &[TrackInfo]) -> Vec<CreateEmbed> {
+    let Some((current, upcoming)) = tracks.split_first() else {
+        return Vec::new();
+    };
+    let total = tracks.iter().map(|x| x.duration).sum::<Duration>();
+    let summary = format!(
+        "{} tracks, {} in total",
+        tracks.len(),
+        humantime::format_duration(total)
+    );
+    let playing = format!("**Now playing:** {}", line(current));
+    let page = |description, footer| {
+        CreateEmbed::new()
+            .title("Queue")
+            .description(description)
+            .footer(CreateEmbedFooter::new(footer))
+    };
+    if upcoming.is_empty() {
+        return vec![page(format!("{playing}\n\nNothing's queued after it."), summary)];
+    }
+
+    let count = upcoming.len().div_ceil(TRACKS_PER_PAGE);
+    upcoming
+        .chunks(TRACKS_PER_PAGE)
+        .enumerate()
+        .map(|(i, tracks)| {
+            let lines = tracks
+                .iter()
+                .enumerate()
+                .map(|(j, x)| format!("`{}.` {}", i * TRACKS_PER_PAGE + j + 1, line(x)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            page(
+                format!("{playing}\n\n{lines}"),
+                format!("Page {} of {count} · {summary}", i + 1),
+            )
+        })
+        .collect()
+}
+
+/// `track` as a line of the queue.
+fn line(track: &TrackInfo) -> String {
+    format!(
+        "[{}]({}) ({}) by {}",
+        track.title,
+        track.url,
+        humantime::format_duration(track.duration),
+        track.author
+    )
+}
+
+async fn skip(manager: &Songbird, guild_id: GuildId) -> Result<CommandResponse> {
+    let (call, _, title) = current(manager, guild_id).await?;
+    let _ = call.lock().await.queue().skip();
+    Ok(CommandResponse::EphemeralString(format!(
+        "*{title}* was skipped."
+    )))
+}
+
+async fn remove(manager: &Songbird, guild_id: GuildId, position: usize) -> Result<CommandResponse> {
+    let removed = call(manager, guild_id)?
+        .lock()
+        .await
+        .queue()
+        .modify_queue(|queue| take_track(queue, position))?;
+    let title = title(&removed).await;
+    let _ = removed.stop();
+    Ok(CommandResponse::EphemeralString(format!(
+        "*{title}* was removed from the queue."
+    )))
+}
+
+async fn move_(manager: &Songbird, guild_id: GuildId, from: usize, to: usize) -> Result<CommandResponse> {
+    let call = call(manager, guild_id)?;
+    let handler = call.lock().await;
+    handler
+        .queue()
+        .modify_queue(|queue| move_track(queue, from, to))?;
+    let moved = handler
+        .queue()
+        .current_queue()
+        .get(to)
+        .cloned()
+        .ok_or(Error::InternalLogic)?;
+    let title = title(&moved).await;
+    Ok(CommandResponse::EphemeralString(format!(
+        "*{title}* was moved to position {to}."
+    )))
+}
+
+async fn shuffle(manager: &Songbird, guild_id: GuildId) -> Result<CommandResponse> {
+    call(manager, guild_id)?
+        .lock()
+        .await
+        .queue()
+        .modify_queue(|queue| shuffle_upcoming(queue));
+    Ok(CommandResponse::EphemeralString(
+        "The queue was shuffled.".to_string(),
+    ))
+}
+
+async fn clear(manager: &Songbird, guild_id: GuildId) -> Result<CommandResponse> {
+    let removed = call(manager, guild_id)?
+        .lock()
+        .await
+        .queue()
+        .modify_queue(|queue| queue.split_off(queue.len().min(1)));
+    for track in &removed {
+        let _ = track.stop();
+    }
+    Ok(CommandResponse::EphemeralString(format!(
+        "{} tracks were removed from the queue.",
+        removed.len()
+    )))
+}
+
+/// Take the track at `position` out of `queue`. The one that's playing can't be taken.
+fn take_track<T>(queue: &mut VecDeque<T>, position: usize) -> Result<T> {
+    if position == 0 {
+        return Err(no_track(position));
+    }
+    queue.remove(position).ok_or_else(|| no_track(position))
+}
+
+/// Move the track at `from` in `queue` to `to`. The one that's playing can't be moved.
+fn move_track<T>(queue: &mut VecDeque<T>, from: usize, to: usize) -> Result<()> {
+    if to == 0 || to >= queue.len() {
+        return Err(no_track(to));
+    }
+    let track = take_track(queue, from)?;
+    queue.insert(to, track);
+    Ok(())
+}
+
+/// Shuffle every track in `queue` but the one that's playing.
+fn shuffle_upcoming<T>(queue: &mut VecDeque<T>) {
+    if let Some((_, upcoming)) = queue.make_contiguous().split_first_mut() {
+        upcoming.shuffle(&mut rand::thread_rng());
+    }
+}
+
+fn no_track(position: usize) -> Error {
+    Error::CommandMisuse(format!("There's no track at position {position} of the queue!"))
+}
+
+async fn stop(manager: &Songbird, guild_id: GuildId) -> Result<CommandResponse> {
+    let (call, _, title) = current(manager, guild_id).await?;
+    call.lock().await.queue().stop();
     Ok(CommandResponse::EphemeralString(format!(
-        "*{}* is now stopped.",
-        track.title
+        "*{title}* is now stopped and the queue is cleared."
     )))
 }
 
-async fn pause(guild_id: GuildId) -> Result<CommandResponse> {
-    let guild_track_map = GUILD_TO_TRACK_MAP.lock().await;
-    let uuid = guild_track_map
-        .get(&guild_id)
-        .ok_or_else(|| Error::InternalLogic)?;
-    let currently_playing = CURRENTLY_PLAYING.lock().await;
-    let (track, track_handle) = currently_playing.get(uuid).ok_or_else(|| Error::InternalLogic)?;
+async fn pause(manager: &Songbird, guild_id: GuildId) -> Result<CommandResponse> {
+    let (_, track_handle, title) = current(manager, guild_id).await?;
     let _ = track_handle.pause();
     Ok(CommandResponse::EphemeralString(format!(
-        "*{}* is now paused.",
-        track.title
+        "*{title}* is now paused."
     )))
 }
 
-async fn unpause(guild_id: GuildId) -> Result<CommandResponse> {
-    let guild_track_map = GUILD_TO_TRACK_MAP.lock().await;
-    let uuid = guild_track_map
-        .get(&guild_id)
-        .ok_or_else(|| Error::InternalLogic)?;
-    let currently_playing = CURRENTLY_PLAYING.lock().await;
-    let (track, track_handle) = currently_playing.get(uuid).ok_or_else(|| Error::InternalLogic)?;
+async fn unpause(manager: &Songbird, guild_id: GuildId) -> Result<CommandResponse> {
+    let (_, track_handle, title) = current(manager, guild_id).await?;
     let _ = track_handle.play();
     Ok(CommandResponse::EphemeralString(format!(
-        "*{}* is now unpaused.",
-        track.title
+        "*{title}* is now unpaused."
     )))
 }
 
+#[component(route = "music-pick/{next}", authorize = Authorization::Invoker)]
+async fn music_pick(
+    interaction: ComponentInteraction,
+    args: CommandArguments,
+    params: RouteParams,
+    selection: Selection,
+) -> anyhow::Result<()> {
+    let next: bool = params.get("next")?;
+    let video_id: String = selection.one()?;
+    let guild = args.guild.clone().ok_or(Error::InternalLogic)?;
+    let manager = voice_manager(&args).await?;
+
+    let url = youtube::video_url(&video_id);
+    let played = play(&url, next, &args, &manager, &guild, Reply::Pick(&interaction)).await;
+    if let Err(e) = &played {
+        // This only gets through when the menu wasn't replaced yet, like when they aren't
+        // in a voice channel.
+        let message = CreateInteractionResponseMessage::new()
+            .content(e.to_string())
+            .ephemeral(true);
+        let _ = interaction
+            .create_response(&args.http, CreateInteractionResponse::Message(message))
+            .await;
+    }
+    played?;
+    Ok(())
+}
+
 struct TrackErrorNotifier;
 
 #[async_trait]
@@ -307,25 +745,57 @@ impl VoiceEventHandler for TrackErrorNotifier {
     }
 }
 
-/// Deletes the message related to the track that just ended
-struct TrackEndNotifier {
-    channel_id: ChannelId,
-    http:       Arc<Http>,
+/// Keeps the messages about tracks up to date.
+struct TrackNotifier(Arc<Http>);
+
+impl TrackNotifier {
+    /// Show `status` in the message about the track `handle` plays. Tracks that don't
+    /// have one yet are announced in the channel the session was started in.
+    async fn update(&self, handle: &TrackHandle, status: &str) {
+        let Some(track) = queued(handle).await else {
+            return;
+        };
+        let embed = CreateEmbed::from(track.info).field("Status", status, false);
+        match track.message {
+            Some((channel_id, message_id)) => {
+                let _ = EditMessage::new()
+                    .add_embed(embed)
+                    .execute(&self.0, (channel_id, message_id))
+                    .await
+                    .map_err(|e| error!("Error sending message to channel {channel_id}: {e}"));
+            }
+            None => {
+                let channel_id = track.channel_id;
+                let message = channel_id
+                    .send_message(&self.0, CreateMessage::new().add_embed(embed))
+                    .await;
+                match message {
+                    Ok(message) => {
+                        if let Some(track) = handle.typemap().write().await.get_mut::<QueuedTrack>() {
+                            track.message = Some((channel_id, message.id));
+                        }
+                    }
+                    Err(e) => error!("Error sending message to channel {channel_id}: {e}"),
+                }
+            }
+        }
+    }
 }
 
+/// Deletes the message about the track that just ended
+struct TrackEndNotifier(Arc<Http>);
+
 #[async_trait]
 impl VoiceEventHandler for TrackEndNotifier {
     async fn act(&self, context: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(track_list) = context {
             for (_state, handle) in *track_list {
-                let uuid = handle.uuid();
-                if let Some(message_id) = GUILD_CHANNEL_MAP.lock().await.remove(&uuid) {
-                    if let Ok(message) = self.channel_id.message(&self.http, message_id).await {
-                        let _ = message
-                            .delete(&self.http)
-                            .await
-                            .map_err(|e| error!("Error deleting message: {e}"));
-                    }
+                let track = queued(handle).await;
+                if let Some((channel_id, message_id)) = track.and_then(|x| x.message) {
+                    let _ = channel_id
+                        .delete_message(&self.0, message_id)
+                        .await
+                        .map_err(|e| error!("Error deleting message: {e}"));
                 }
             }
         }
@@ -334,28 +804,14 @@ impl VoiceEventHandler for TrackEndNotifier {
     }
 }
 
-struct TrackPauseNotifier {
-    channel_id: ChannelId,
-    http:       Arc<Http>,
-}
+struct TrackPauseNotifier(TrackNotifier);
 
 #[async_trait]
 impl VoiceEventHandler for TrackPauseNotifier {
     async fn act(&self, context: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(track_list) = context {
             for (_state, handle) in *track_list {
-                let uuid = handle.uuid();
-                let currently_playing = CURRENTLY_PLAYING.lock().await;
-                if let Some((track, _)) = currently_playing.get(&uuid).cloned() {
-                    if let Some(message_id) = GUILD_CHANNEL_MAP.lock().await.get(&uuid).cloned() {
-                        let embed = CreateEmbed::from(track).field("Status", "Paused", false);
-                        let _ = EditMessage::new()
-                            .add_embed(embed)
-                            .execute(&self.http, (self.channel_id, message_id))
-                            .await
-                            .map_err(|e| error!("Error sending message to channel {}: {e}", self.channel_id));
-                    }
-                }
+                self.0.update(handle, "Paused").await;
             }
         }
 
@@ -363,31 +819,201 @@ impl VoiceEventHandler for TrackPauseNotifier {
     }
 }
 
-struct TrackPlayNotifier {
-    channel_id: ChannelId,
-    http:       Arc<Http>,
-}
+struct TrackPlayNotifier(TrackNotifier);
 
 #[async_trait]
 impl VoiceEventHandler for TrackPlayNotifier {
     async fn act(&self, context: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(track_list) = context {
             for (_state, handle) in *track_list {
-                let uuid = handle.uuid();
-                let currently_playing = CURRENTLY_PLAYING.lock().await;
-                if let Some((track, _)) = currently_playing.get(&uuid).cloned() {
-                    if let Some(message_id) = GUILD_CHANNEL_MAP.lock().await.get(&uuid).cloned() {
-                        let embed = CreateEmbed::from(track).field("Status", "Playing", false);
-                        let _ = EditMessage::new()
-                            .add_embed(embed)
-                            .execute(&self.http, (self.channel_id, message_id))
-                            .await
-                            .map_err(|e| error!("Error sending message to channel {}: {e}", self.channel_id));
-                    }
-                }
+                self.0.update(handle, "Playing").await;
             }
         }
 
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+    use serde_json::json;
+    use serenity::all::{Permissions, RoleId};
+
+    use super::{
+        is_dj, move_track, pages, parse_query, shuffle_upcoming, take_track, youtube::TrackInfo, Query,
+        TRACKS_PER_PAGE,
+    };
+    use crate::{
+        commands::{
+            harness::{self, assert_err, string, subcommand},
+            CommandArguments, DiscordCommand,
+        },
+        config::{music::Music, Configuration},
+        Error,
+    };
+
+    async fn arguments() -> CommandArguments {
+        let mut args = harness::arguments();
+        args.guild = Some(harness::guild(1, vec![]));
+        args.data
+            .write()
+            .await
+            .insert::<songbird::SongbirdKey>(songbird::Songbird::serenity());
+        args
+    }
+
+    #[tokio::test]
+    async fn test_music_disabled() {
+        let mut args = arguments().await;
+        let mut config = Configuration::default();
+        config.music = Some(Music { enabled: false });
+        args.config = config.into();
+
+        assert_err!(
+            harness::run("music", json!([subcommand("leave", json!([]))]), args).await,
+            Error::FeatureDisabled(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_music_disabled_suggestions() {
+        let mut args = arguments().await;
+        let mut config = Configuration::default();
+        config.music = Some(Music { enabled: false });
+        args.config = config.into();
+        let mut query = string("query", "never gonna");
+        query["focused"] = json!(true);
+        let command = harness::interaction("music", json!([subcommand("play", json!([query]))]));
+
+        let suggestions = super::COMMAND
+            .autocomplete(Arc::new(command), args)
+            .await
+            .unwrap();
+        assert!(suggestions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_music_play_not_youtube() {
+        assert_err!(
+            harness::run(
+                "music",
+                json!([subcommand(
+                    "play",
+                    json!([string("query", "https://example.com/song.mp3")])
+                )]),
+                arguments().await
+            )
+            .await,
+            Error::CommandMisuse(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_music_leave_without_joining() {
+        assert_err!(
+            harness::run(
+                "music",
+                json!([subcommand("leave", json!([]))]),
+                arguments().await
+            )
+            .await,
+            Error::CommandMisuse(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_music_skip_without_joining() {
+        assert_err!(
+            harness::run("music", json!([subcommand("skip", json!([]))]), arguments().await).await,
+            Error::CommandMisuse(_)
+        );
+    }
+
+    #[test]
+    fn test_is_dj() {
+        let dj_role = RoleId::new(2);
+
+        assert!(is_dj(&[RoleId::new(1), dj_role], None, dj_role));
+        assert!(!is_dj(&[RoleId::new(1)], Some(Permissions::empty()), dj_role));
+        assert!(is_dj(&[], Some(Permissions::MANAGE_GUILD), dj_role));
+        assert!(!is_dj(&[], None, dj_role));
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            parse_query(" https://youtu.be/BbIaaxi9uAY ").unwrap(),
+            Query::Url("https://youtu.be/BbIaaxi9uAY")
+        );
+        assert_eq!(
+            parse_query("never gonna give you up").unwrap(),
+            Query::Search("never gonna give you up")
+        );
+        assert_err!(
+            parse_query("https://example.com/song.mp3"),
+            Error::CommandMisuse(_)
+        );
+        assert_err!(parse_query("  "), Error::CommandMisuse(_));
+    }
+
+    #[test]
+    fn test_queue_changes() {
+        let mut queue = VecDeque::from([0, 1, 2, 3]);
+
+        move_track(&mut queue, 3, 1).unwrap();
+        assert_eq!(queue, [0, 3, 1, 2]);
+        move_track(&mut queue, 1, 3).unwrap();
+        assert_eq!(queue, [0, 1, 2, 3]);
+        // The track that's playing stays where it is.
+        assert_err!(move_track(&mut queue, 0, 2), Error::CommandMisuse(_));
+        assert_err!(move_track(&mut queue, 2, 0), Error::CommandMisuse(_));
+        assert_err!(move_track(&mut queue, 1, 4), Error::CommandMisuse(_));
+        assert_eq!(queue, [0, 1, 2, 3]);
+
+        assert_eq!(take_track(&mut queue, 2).unwrap(), 2);
+        assert_err!(take_track(&mut queue, 0), Error::CommandMisuse(_));
+        assert_err!(take_track(&mut queue, 3), Error::CommandMisuse(_));
+        assert_eq!(queue, [0, 1, 3]);
+
+        let mut queue = (0..100).collect::<VecDeque<_>>();
+        shuffle_upcoming(&mut queue);
+        assert_eq!(queue[0], 0);
+        let mut sorted = queue.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+        shuffle_upcoming(&mut VecDeque::<u8>::new());
+    }
+
+    #[test]
+    fn test_pages() {
+        let track = |i| {
+            TrackInfo {
+                title:         format!("Track {i}"),
+                url:           format!("https://youtu.be/{i:011}"),
+                duration:      Duration::from_secs(60),
+                thumbnail_url: None,
+                author:        String::from("Someone"),
+            }
+        };
+        let json = |x| serde_json::to_value(x).unwrap();
+
+        assert!(pages(&[]).is_empty());
+        let only = pages(&[track(0)]);
+        assert_eq!(only.len(), 1);
+        assert!(json(&only[0])["description"]
+            .as_str()
+            .unwrap()
+            .ends_with("Nothing's queued after it."));
+
+        let tracks = (0..=TRACKS_PER_PAGE + 1).map(track).collect::<Vec<_>>();
+        let pages = pages(&tracks);
+        assert_eq!(pages.len(), 2);
+        let second = json(&pages[1]);
+        let description = second["description"].as_str().unwrap();
+        assert!(description.starts_with("**Now playing:** [Track 0]"));
+        assert!(description.contains("`11.` [Track 11]"));
+        assert_eq!(second["footer"]["text"], "Page 2 of 2 · 12 tracks, 12m in total");
+    }
+}
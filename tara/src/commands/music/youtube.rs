@@ -4,8 +4,8 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use serenity::builder::{CreateEmbed, CreateEmbedAuthor};
 use youtubei_rs::{
-    query::player,
-    types::{client::ClientConfig, query_results::PlayerResult},
+    query::{player, search as search_videos},
+    types::{client::ClientConfig, enums::ItemSectionRendererContents, query_results::PlayerResult},
 };
 
 /// A YouTube video regex that matches on youtube.com/watch and youtu.be links.
@@ -51,9 +51,7 @@ impl TrackInfo {
         let video_id = extract_id_from_url(url)
             .ok_or_else(|| Error::CommandMisuse(format!("\"{url}\": Isn't a YouTube video/audio URL")))?;
 
-        let player: PlayerResult = player(String::from(video_id), String::from(""), &client_config)
-            .await
-            .unwrap();
+        let player: PlayerResult = player(String::from(video_id), String::from(""), &client_config).await?;
         let video = player.video_details;
 
         // Get the thumbnail list and sort it.
@@ -74,7 +72,7 @@ impl TrackInfo {
         let track_info = Self {
             title: video.title,
             url: url.to_string(),
-            duration: Duration::from_secs(video.length_seconds.parse().unwrap()),
+            duration: Duration::from_secs(video.length_seconds.parse().unwrap_or_default()),
             thumbnail_url,
             author: video.author,
         };
@@ -84,6 +82,58 @@ impl TrackInfo {
 }
 
 
+/// A video found by [`search`].
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct SearchResult {
+    pub(super) video_id: String,
+    pub(super) title:    String,
+    /// How long it is as YouTube writes it, like `3:32`. Empty for livestreams.
+    pub(super) length:   String,
+}
+
+impl SearchResult {
+    /// The title and length, to pick the video by.
+    pub(super) fn label(&self) -> String {
+        if self.length.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} ({})", self.title, self.length)
+        }
+    }
+
+    pub(super) fn url(&self) -> String { video_url(&self.video_id) }
+}
+
+/// Search YouTube for up to `limit` videos matching `query`, best match first.
+pub(super) async fn search(
+    client_config: Arc<ClientConfig>,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchResult>> {
+    let results = search_videos(query.to_string(), String::new(), &client_config).await?;
+    let videos = results
+        .contents
+        .into_iter()
+        .filter_map(|x| {
+            match x {
+                ItemSectionRendererContents::VideoRenderer(video) => {
+                    Some(SearchResult {
+                        video_id: video.video_id,
+                        title:    video.title,
+                        length:   video.length_text,
+                    })
+                }
+                _ => None,
+            }
+        })
+        .take(limit)
+        .collect();
+    Ok(videos)
+}
+
+/// The URL of the video with the ID `video_id`, which [`YOUTUBE_REGEX`] matches.
+pub(super) fn video_url(video_id: &str) -> String { format!("https://www.youtube.com/watch?v={video_id}") }
+
 #[inline]
 pub(super) fn extract_id_from_url(url: &str) -> Option<&str> {
     Some(YOUTUBE_REGEX.captures(url)?.get(1)?.as_str())
@@ -135,5 +185,21 @@ mod tests {
             extract_id_from_url("https://youtu.be/7YT0rQ2eKkY").unwrap(),
             "7YT0rQ2eKkY"
         );
+        assert_eq!(
+            extract_id_from_url(&video_url("7YT0rQ2eKkY")).unwrap(),
+            "7YT0rQ2eKkY"
+        );
+    }
+
+    #[test]
+    fn test_search_result_label() {
+        let mut result = SearchResult {
+            video_id: String::from("7YT0rQ2eKkY"),
+            title:    String::from("A song"),
+            length:   String::from("3:32"),
+        };
+        assert_eq!(result.label(), "A song (3:32)");
+        result.length.clear();
+        assert_eq!(result.label(), "A song");
     }
 }
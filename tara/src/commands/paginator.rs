@@ -0,0 +1,455 @@
+//! Responses that page through a list of embeds with buttons. Only the user that ran the
+//! command can turn the pages, and the buttons are disabled once the paginator times out.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use component_macro::component;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, ComponentInteraction, Embed, InputTextStyle, ModalInteraction,
+        ReactionType,
+    },
+    builder::{
+        Builder, CreateActionRow, CreateButton, CreateEmbed, CreateInputText, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateModal, EditInteractionResponse,
+    },
+    client::Cache,
+    http::Http,
+};
+use tokio::sync::Mutex;
+
+use super::CommandArguments;
+use crate::{
+    componet::{
+        Authorization, Component, ComponentMap, DynComponent, DynModal, ModalFields, Registration,
+        RouteParams,
+    },
+    IdUtil, Result,
+};
+
+/// The paginator's buttons.
+pub(super) const COMPONENTS: &[DynComponent] = &[&paginator_button];
+/// The jump-to-page form.
+pub(super) const MODALS: &[DynModal] = &[&paginator_jump];
+
+/// Loads pages as they're turned to, for when building every page up front is too
+/// expensive.
+#[async_trait]
+pub trait PageLoader {
+    async fn page(&self, index: usize) -> anyhow::Result<CreateEmbed>;
+}
+
+#[derive(Clone)]
+enum Pages {
+    Embeds(Vec<CreateEmbed>),
+    Lazy {
+        count:  usize,
+        loader: Arc<dyn PageLoader + Send + Sync>,
+    },
+    /// Lazily loaded pages restored after a restart. Their loader didn't survive it, so
+    /// all that's left is to expire them.
+    Expired {
+        count: usize,
+    },
+}
+
+impl Pages {
+    fn len(&self) -> usize {
+        match self {
+            Pages::Embeds(embeds) => embeds.len(),
+            Pages::Lazy { count, .. } | Pages::Expired { count } => *count,
+        }
+    }
+
+    async fn get(&self, index: usize) -> anyhow::Result<CreateEmbed> {
+        match self {
+            Pages::Embeds(embeds) => {
+                embeds
+                    .get(index)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("No page {index}"))
+            }
+            Pages::Lazy { loader, .. } => loader.page(index).await,
+            Pages::Expired { .. } => anyhow::bail!("The pages expired with the restart"),
+        }
+    }
+}
+
+struct Session {
+    pages: Pages,
+    index: usize,
+    /// The token of the interaction the paginator responded to, used to disable the
+    /// buttons.
+    token: String,
+}
+
+/// What's persisted of a [`Session`], saved again every time a page is turned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedSession {
+    /// Every page serialized as JSON, or `None` for lazily loaded pages which can't be.
+    pages: Option<Vec<String>>,
+    count: usize,
+    index: usize,
+    token: String,
+}
+
+/// A response paging through embeds. Only the user that ran the command can turn the
+/// pages.
+#[derive(Clone)]
+pub struct Paginator {
+    pages: Pages,
+}
+
+impl Paginator {
+    pub fn new(pages: Vec<CreateEmbed>) -> Self {
+        Self {
+            pages: Pages::Embeds(pages),
+        }
+    }
+
+    /// A paginator with `count` pages loaded by `loader` when they're turned to. Those
+    /// can't be persisted, so after a restart the buttons only work until they expire.
+    pub fn lazy(count: usize, loader: impl PageLoader + Send + Sync + 'static) -> Self {
+        Self {
+            pages: Pages::Lazy {
+                count,
+                loader: Arc::new(loader),
+            },
+        }
+    }
+
+    /// Respond to `command` with the first page and listen for the buttons.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::NoSearchResults`] when there are no pages.
+    pub async fn send(self, command: &CommandInteraction, args: &CommandArguments) -> Result<()> {
+        let count = self.pages.len();
+        if count == 0 {
+            return Err(crate::Error::NoSearchResults(String::from("Nothing to show!")));
+        }
+        let first = self.pages.get(0).await.map_err(|_| crate::Error::InternalLogic)?;
+
+        let id = command.id.touint();
+        let mut message = CreateInteractionResponseMessage::new().embed(first);
+        if count > 1 {
+            message = message.components(components(id, 0, count, false));
+        }
+        command
+            .create_response(&args.http, CreateInteractionResponse::Message(message))
+            .await?;
+        if count == 1 {
+            return Ok(());
+        }
+
+        let session = Session {
+            pages: self.pages,
+            index: 0,
+            token: command.token.clone(),
+        };
+        let saved = session.save().map_err(|_| crate::Error::InternalLogic)?;
+        let registration = Registration::new(command.guild_id, command.channel_id)
+            .with_state(&saved)
+            .map_err(|_| crate::Error::InternalLogic)?;
+
+        // Every button is under this ID, the session is dropped once they time out.
+        args.component_map
+            .insert(
+                format!("paginator/{id}"),
+                paginator_button.with_state(Mutex::new(session)),
+                None,
+                registration,
+            )
+            .await;
+        Ok(())
+    }
+}
+
+impl Session {
+    fn save(&self) -> serde_json::Result<SavedSession> {
+        let pages = match &self.pages {
+            Pages::Embeds(embeds) => {
+                Some(
+                    embeds
+                        .iter()
+                        .map(serde_json::to_string)
+                        .collect::<serde_json::Result<_>>()?,
+                )
+            }
+            Pages::Lazy { .. } | Pages::Expired { .. } => None,
+        };
+        Ok(SavedSession {
+            pages,
+            count: self.pages.len(),
+            index: self.index,
+            token: self.token.clone(),
+        })
+    }
+
+    fn load(saved: SavedSession) -> anyhow::Result<Self> {
+        let pages = match saved.pages {
+            Some(pages) => {
+                Pages::Embeds(
+                    pages
+                        .iter()
+                        .map(|x| Ok(CreateEmbed::from(serde_json::from_str::<Embed>(x)?)))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+            None => Pages::Expired { count: saved.count },
+        };
+        Ok(Self {
+            pages,
+            index: saved.index,
+            token: saved.token,
+        })
+    }
+
+    /// Turn to `index`, saving it so the paginator is still there after a restart, and
+    /// build the message showing it.
+    async fn turn(
+        &mut self,
+        index: usize,
+        id: u64,
+        component_map: &ComponentMap,
+    ) -> anyhow::Result<CreateInteractionResponseMessage> {
+        let page = self.pages.get(index).await?;
+        self.index = index;
+        component_map
+            .save_state(&format!("paginator/{id}"), &self.save()?)
+            .await;
+        Ok(CreateInteractionResponseMessage::new()
+            .embed(page)
+            .components(components(id, index, self.pages.len(), false)))
+    }
+}
+
+/// The page `action` turns to from `index`, or `None` for actions that don't turn it.
+fn target(action: &str, index: usize, count: usize) -> Option<usize> {
+    let last = count.saturating_sub(1);
+    match action {
+        "first" => Some(0),
+        "prev" => Some(if index == 0 { last } else { index - 1 }),
+        "next" => Some(if index >= last { 0 } else { index + 1 }),
+        "last" => Some(last),
+        _ => None,
+    }
+}
+
+fn components(id: u64, index: usize, count: usize, disabled: bool) -> Vec<CreateActionRow> {
+    let button = |action: &str, emoji: &str| {
+        CreateButton::new(format!("paginator/{id}/{action}"))
+            .style(ButtonStyle::Secondary)
+            .emoji(ReactionType::Unicode(String::from(emoji)))
+            .disabled(disabled)
+    };
+
+    vec![CreateActionRow::Buttons(vec![
+        button("first", "⏮️"),
+        button("prev", "⬅️"),
+        CreateButton::new(format!("paginator/{id}/jump"))
+            .style(ButtonStyle::Primary)
+            .label(format!("{}/{count}", index + 1))
+            .disabled(disabled),
+        button("next", "➡️"),
+        button("last", "⏭️"),
+    ])]
+}
+
+/// Respond to `interaction` with an ephemeral `content`.
+macro_rules! respond_ephemeral {
+    ($interaction:expr, $http:expr, $content:expr) => {
+        $interaction
+            .create_response(
+                $http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content($content)
+                        .ephemeral(true),
+                ),
+            )
+            .await
+    };
+}
+
+#[component(
+    state = Mutex<Session>,
+    route = "paginator/{id}/{action}",
+    authorize = Authorization::Invoker,
+    cleanup = paginator_cleanup,
+    restore = paginator_restore
+)]
+async fn paginator_button(
+    session: &Mutex<Session>,
+    interaction: ComponentInteraction,
+    args: CommandArguments,
+    params: RouteParams,
+) -> anyhow::Result<()> {
+    let id: u64 = params.get("id")?;
+    let action: String = params.get("action")?;
+
+    let mut session = session.lock().await;
+    // Lazy pages can't be turned after a restart, so they're expired right away, which
+    // disables the buttons.
+    if let Pages::Expired { .. } = session.pages {
+        respond_ephemeral!(interaction, &args.http, "These pages have expired.")?;
+        return args.component_map.timeout(format!("paginator/{id}")).await;
+    }
+    let response = match target(&action, session.index, session.pages.len()) {
+        Some(index) => {
+            let message = session.turn(index, id, &args.component_map).await?;
+            CreateInteractionResponse::UpdateMessage(message)
+        }
+        None => {
+            let input = CreateInputText::new(InputTextStyle::Short, "Page", "page")
+                .placeholder(format!("1-{}", session.pages.len()))
+                .required(true);
+            CreateInteractionResponse::Modal(
+                CreateModal::new(format!("paginator-jump/{id}"), "Jump to page")
+                    .components(vec![CreateActionRow::InputText(input)]),
+            )
+        }
+    };
+    interaction.create_response(&args.http, response).await?;
+    Ok(())
+}
+
+#[component(modal, route = "paginator-jump/{id}")]
+async fn paginator_jump(
+    interaction: ModalInteraction,
+    args: CommandArguments,
+    fields: ModalFields,
+    params: RouteParams,
+) -> anyhow::Result<()> {
+    let id: u64 = params.get("id")?;
+
+    let session = args
+        .component_map
+        .state::<Mutex<Session>>(&format!("paginator/{id}"))
+        .await;
+    let Some(session) = session else {
+        respond_ephemeral!(interaction, &args.http, "These pages have expired.")?;
+        return Ok(());
+    };
+    let mut session = session.lock().await;
+    let count = session.pages.len();
+    let page = fields
+        .get::<usize>("page")
+        .ok()
+        .filter(|x| (1..=count).contains(x));
+    let Some(page) = page else {
+        let content = format!("Pick a page from 1 to {count}.");
+        respond_ephemeral!(interaction, &args.http, content)?;
+        return Ok(());
+    };
+
+    let message = session.turn(page - 1, id, &args.component_map).await?;
+    interaction
+        .create_response(&args.http, CreateInteractionResponse::UpdateMessage(message))
+        .await?;
+    Ok(())
+}
+
+fn session_id(id: &str) -> anyhow::Result<u64> {
+    sscanf::sscanf!(id, "paginator/{u64}").map_err(|e| anyhow::anyhow!("Malformed paginator id '{id}': {e}"))
+}
+
+async fn paginator_restore(_id: String, state: Vec<u8>) -> anyhow::Result<Mutex<Session>> {
+    let saved: SavedSession = bincode::deserialize(&state)?;
+    Ok(Mutex::new(Session::load(saved)?))
+}
+
+async fn paginator_cleanup(
+    session: &Mutex<Session>,
+    id: String,
+    http: Arc<Http>,
+    _cache: Arc<Cache>,
+) -> anyhow::Result<()> {
+    let id = session_id(&id)?;
+    let session = session.lock().await;
+
+    EditInteractionResponse::new()
+        .components(components(id, session.index, session.pages.len(), true))
+        .execute(&http, &session.token)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target() {
+        assert_eq!(target("first", 3, 5), Some(0));
+        assert_eq!(target("last", 0, 5), Some(4));
+        assert_eq!(target("next", 1, 5), Some(2));
+        assert_eq!(target("prev", 1, 5), Some(0));
+        // Wraps around
+        assert_eq!(target("next", 4, 5), Some(0));
+        assert_eq!(target("prev", 0, 5), Some(4));
+        assert_eq!(target("jump", 2, 5), None);
+    }
+
+    #[test]
+    fn test_components() {
+        let rows = serde_json::to_value(components(7, 1, 3, true)).unwrap();
+        let buttons = rows[0]["components"].as_array().unwrap();
+
+        assert_eq!(buttons.len(), 5);
+        assert_eq!(buttons[2]["label"], "2/3");
+        assert_eq!(buttons[2]["custom_id"], "paginator/7/jump");
+        assert!(buttons.iter().all(|x| x["disabled"] == true));
+    }
+
+    #[tokio::test]
+    async fn test_saved_session() {
+        let session = Session {
+            pages: Pages::Embeds(vec![
+                CreateEmbed::new().title("One").description("First"),
+                CreateEmbed::new().title("Two"),
+            ]),
+            index: 1,
+            token: String::from("token"),
+        };
+        let saved = session.save().unwrap();
+        let state = bincode::serialize(&saved).unwrap();
+        let loaded = Session::load(bincode::deserialize(&state).unwrap()).unwrap();
+
+        assert_eq!(loaded.index, 1);
+        let first = serde_json::to_value(loaded.pages.get(0).await.unwrap()).unwrap();
+        assert_eq!(first["title"], "One");
+        assert_eq!(first["description"], "First");
+    }
+
+    struct Numbered;
+
+    #[async_trait]
+    impl PageLoader for Numbered {
+        async fn page(&self, index: usize) -> anyhow::Result<CreateEmbed> {
+            Ok(CreateEmbed::new().title(format!("Page {}", index + 1)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lazy_session() {
+        let session = Session {
+            pages: Paginator::lazy(3, Numbered).pages,
+            index: 2,
+            token: String::from("token"),
+        };
+        let page = serde_json::to_value(session.pages.get(2).await.unwrap()).unwrap();
+        assert_eq!(page["title"], "Page 3");
+
+        // The loader doesn't survive a restart, only what's needed to expire the pages.
+        let saved = session.save().unwrap();
+        assert!(saved.pages.is_none());
+        let state = bincode::serialize(&saved).unwrap();
+        let loaded = Session::load(bincode::deserialize(&state).unwrap()).unwrap();
+
+        assert!(matches!(loaded.pages, Pages::Expired { count: 3 }));
+        assert_eq!(loaded.index, 2);
+        assert!(loaded.pages.get(0).await.is_err());
+    }
+}
@@ -12,14 +12,15 @@ struct Emoji {
     unicode: Vec<String>,
 }
 
-pub(super) async fn random_emoji() -> Result<char> {
-    let emoji = reqwest::get("https://emojihub.yurace.pro/api/random")
+/// Get a random emoji from the EmojiHub API at `base_url`.
+pub(super) async fn random_emoji(base_url: &str) -> Result<char> {
+    let emoji = reqwest::get(format!("{base_url}/api/random"))
         .await?
         .json::<Emoji>()
         .await
         .map_err(|e| Error::JsonParse(e.to_string()))?;
 
-    let emoji_unicode_str = emoji.unicode.get(0).ok_or(Error::InternalLogic)?;
+    let emoji_unicode_str = emoji.unicode.first().ok_or(Error::InternalLogic)?;
     let Ok(emoji_unicode) = sscanf::sscanf!(emoji_unicode_str, "U+{u32:x}") else {
         return Err(Error::Unexpected(
             "Emoji API returned a different format for their unicode characters than expected!",
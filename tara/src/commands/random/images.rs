@@ -40,10 +40,11 @@ impl Default for CatImage {
 }
 
 impl CatImage {
-    pub async fn random() -> Result<Self> {
+    /// Get a random cat image from the Cat API at `base_url`.
+    pub async fn random(base_url: &str) -> Result<Self> {
         // Request URL
-        const RANDOM_CAT_IMAGE_URL: &str = "https://api.thecatapi.com/v1/images/search";
-        let response = reqwest::get(RANDOM_CAT_IMAGE_URL).await?;
+        let url = format!("{base_url}/v1/images/search");
+        let response = reqwest::get(url).await?;
 
         // Parse the response
         let image = response
@@ -52,7 +53,7 @@ impl CatImage {
             .map_err(|e| Error::JsonParse(e.to_string()))?;
 
         image
-            .get(0)
+            .first()
             .cloned()
             .ok_or_else(|| Error::Unexpected("Server returned an empty list of results!"))
     }
@@ -66,12 +67,13 @@ pub(super) struct DogImage {
 }
 
 impl DogImage {
-    pub async fn random() -> Result<Self> {
+    /// Get a random dog image from the Dog CEO API at `base_url`.
+    pub async fn random(base_url: &str) -> Result<Self> {
         // Request URL
-        const URL: &str = "https://dog.ceo/api/breeds/image/random";
+        let url = format!("{base_url}/api/breeds/image/random");
 
         // Get the response
-        let response = match reqwest::get(URL).await {
+        let response = match reqwest::get(url).await {
             Ok(x) => x,
             Err(e) => return Err(Error::HttpRequest(e)),
         };
@@ -68,21 +68,22 @@ impl DiscordCommand for Random {
     }
 
     async fn run(&self, command: Arc<CommandInteraction>, args: CommandArguments) -> Result<CommandResponse> {
-        let option = &command.data.options[0];
+        let option = super::common::first_option(&command.data.options)?;
+        let endpoints = &args.config.endpoints;
         match &*option.name {
             "coin" => Ok(coin_flip()),
-            "quote" => quote::random().await,
+            "quote" => quote::random(&endpoints.quotable).await,
             "cat" | "dog" => {
                 // Get the image url
                 let url = match &*option.name {
-                    "cat" => Image::from(images::CatImage::random().await?).link,
-                    "dog" => Image::from(images::DogImage::random().await?).link,
+                    "cat" => Image::from(images::CatImage::random(&endpoints.thecatapi).await?).link,
+                    "dog" => Image::from(images::DogImage::random(&endpoints.dog_ceo).await?).link,
                     _ => unreachable!(),
                 };
 
                 // Create attachment from image and respond to command. We're downloading the image just
                 // to upload it again to discord because discord began to have issues embeding the links.
-                let attachment = CreateAttachment::url(&args.context.http, &url)
+                let attachment = CreateAttachment::url(&args.http, &url)
                     .await
                     .map_err(|e| Error::SerenityHttpRequest(Box::new(e)))?;
 
@@ -95,7 +96,7 @@ impl DiscordCommand for Random {
                 let mut high = 1_000_000.0;
                 let mut integer = false;
 
-                for option in super::common::suboptions(option) {
+                for option in super::common::suboptions(option)? {
                     match &*option.name {
                         "low" => {
                             low = option.value.as_f64().unwrap_or(low);
@@ -103,11 +104,11 @@ impl DiscordCommand for Random {
                         "high" => {
                             high = option.value.as_f64().unwrap_or(high);
                         }
-                        "integer" => integer = true,
+                        "integer" => integer = option.value.as_bool().unwrap_or(true),
                         _ => return Err(Error::InternalLogic),
                     }
                 }
-                Ok(random_number(low, high, integer))
+                random_number(low, high, integer)
             }
             "image" => {
                 let Some(api_key) = args.config.secrets.unsplash_key.as_ref() else {
@@ -115,13 +116,17 @@ impl DiscordCommand for Random {
                         "Unsplash images have been disabled".to_string(),
                     ));
                 };
-                let image = &unsplash::UnsplashImage::random(api_key).await?;
+                let image = &unsplash::UnsplashImage::random(&endpoints.unsplash, api_key).await?;
                 let embed: CreateEmbed = image.into();
 
                 Ok(CommandResponse::Embed(Box::new(embed)))
             }
-            "emoji" => Ok(CommandResponse::String(emoji::random_emoji().await?.to_string())),
-            "fact" => random_fact().await,
+            "emoji" => {
+                Ok(CommandResponse::String(
+                    emoji::random_emoji(&endpoints.emojihub).await?.to_string(),
+                ))
+            }
+            "fact" => random_fact(&endpoints.uselessfacts).await,
             _ => Err(Error::InternalLogic),
         }
     }
@@ -157,8 +162,17 @@ fn coin_flip() -> CommandResponse {
 ///
 /// dbg!(random_number(low, high, false));
 /// ```
+///
+/// # Errors
+///
+/// Errors if `low` is greater than `high` or either isn't finite.
 #[allow(clippy::cast_possible_truncation)]
-fn random_number(low: f64, high: f64, integer: bool) -> CommandResponse {
+fn random_number(low: f64, high: f64, integer: bool) -> Result<CommandResponse> {
+    if !(low.is_finite() && high.is_finite()) || low > high {
+        return Err(Error::CommandMisuse(format!(
+            "Can't pick a number between {low} and {high}"
+        )));
+    }
     let mut rng = rand::thread_rng();
 
     let x = if integer {
@@ -167,7 +181,7 @@ fn random_number(low: f64, high: f64, integer: bool) -> CommandResponse {
         rng.gen_range(low..=high).to_string()
     };
 
-    CommandResponse::String(x)
+    Ok(CommandResponse::String(x))
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -181,13 +195,156 @@ struct Fact {
 }
 
 impl Fact {
-    async fn random() -> Result<Self> {
-        const URL: &str = "https://uselessfacts.jsph.pl/api/v2/facts/random";
-        Ok(reqwest::get(URL).await?.json::<Self>().await?)
+    async fn random(base_url: &str) -> Result<Self> {
+        let url = format!("{base_url}/api/v2/facts/random");
+        Ok(reqwest::get(url).await?.json::<Self>().await?)
     }
 }
 
-async fn random_fact() -> Result<CommandResponse> {
-    let fact = Fact::random().await?;
+async fn random_fact(base_url: &str) -> Result<CommandResponse> {
+    let fact = Fact::random(base_url).await?;
     Ok(fact.text.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        commands::{
+            harness::{self, fixture, number, subcommand, Stub, StubServer},
+            CommandResponse,
+        },
+        Result,
+    };
+
+    /// The smallest valid PNG there is, a single transparent pixel.
+    const PIXEL: &[u8] = &[
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1F, 0x15, 0xC4, 0x89, 0x00,
+        0x00, 0x00, 0x0D, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9C, 0x63, 0x00, 0x01, 0x00, 0x00, 0x05, 0x00, 0x01,
+        0x0D, 0x0A, 0x2D, 0xB4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82,
+    ];
+
+    async fn random(server: &StubServer, subcommand_name: &str) -> Result<CommandResponse> {
+        harness::run(
+            "random",
+            json!([subcommand(subcommand_name, json!([]))]),
+            harness::arguments_with(server),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_random_quote() {
+        let server = StubServer::start().await;
+        server
+            .route("/random", Stub::json(fixture!("quotable/random.json")))
+            .await;
+
+        let content = harness::message(random(&server, "quote").await.unwrap())["content"].clone();
+        assert_eq!(
+            content,
+            "*Those who cannot remember the past are condemned to repeat it.*\n\t—George Santayana"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_random_fact() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/api/v2/facts/random",
+                Stub::json(fixture!("uselessfacts/random.json")),
+            )
+            .await;
+
+        let content = harness::message(random(&server, "fact").await.unwrap())["content"].clone();
+        assert_eq!(content, "Honey is the only natural food that never spoils.");
+    }
+
+    #[tokio::test]
+    async fn test_random_emoji() {
+        let server = StubServer::start().await;
+        server
+            .route("/api/random", Stub::json(fixture!("emojihub/random.json")))
+            .await;
+
+        let content = harness::message(random(&server, "emoji").await.unwrap())["content"].clone();
+        assert_eq!(content, "😀");
+    }
+
+    #[tokio::test]
+    async fn test_random_image() {
+        let server = StubServer::start().await;
+        server
+            .route("/photos/random", Stub::json(fixture!("unsplash/random.json")))
+            .await;
+
+        let embed = harness::message(random(&server, "image").await.unwrap())["embeds"][0].clone();
+        assert_eq!(embed["author"]["name"], "Joe Example");
+        assert_eq!(embed["footer"]["text"], "From Unsplash.");
+        assert_eq!(
+            server.requests().await,
+            ["GET /photos/random?client_id=unsplash-key"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_random_animals() {
+        let server = StubServer::start().await;
+        server
+            .route("/v1/images/search", Stub::json(fixture!("thecatapi/search.json")))
+            .await
+            .route("/images/", Stub::bytes("image/png", PIXEL.to_vec()))
+            .await
+            .route(
+                "/api/breeds/image/random",
+                Stub::json(fixture!("dog_ceo/random.json")),
+            )
+            .await
+            .route("/breeds/", Stub::bytes("image/png", PIXEL.to_vec()))
+            .await;
+
+        // The images are downloaded to be attached instead of linked
+        assert!(matches!(
+            random(&server, "cat").await,
+            Ok(CommandResponse::Message(_))
+        ));
+        assert!(matches!(
+            random(&server, "dog").await,
+            Ok(CommandResponse::Message(_))
+        ));
+        assert_eq!(
+            server.requests().await,
+            [
+                "GET /v1/images/search",
+                "GET /images/9ccXTANkb.png",
+                "GET /api/breeds/image/random",
+                "GET /breeds/hound-afghan/n02088094_1003.png",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_random_offline() {
+        let server = StubServer::start().await;
+        let coin = harness::message(random(&server, "coin").await.unwrap())["content"].clone();
+        assert!(coin == "Heads" || coin == "Tails");
+
+        let response = harness::run(
+            "random",
+            json!([subcommand(
+                "number",
+                json!([number("low", 5.0), number("high", 5.0)])
+            )]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        assert_eq!(harness::message(response)["content"], "5");
+
+        // Neither needs the network
+        assert!(server.requests().await.is_empty());
+    }
+}
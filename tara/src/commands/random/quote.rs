@@ -2,7 +2,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::{commands::CommandResponse, Error, Result};
 
-pub async fn random() -> Result<CommandResponse> { Ok(Quote::random().await?.to_string().into()) }
+pub async fn random(base_url: &str) -> Result<CommandResponse> {
+    Ok(Quote::random(base_url).await?.to_string().into())
+}
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Quote {
@@ -10,7 +12,7 @@ pub enum Quote {
 }
 
 impl Quote {
-    async fn random() -> Result<Self> { Ok(Self::Random(Random::fetch().await?)) }
+    async fn random(base_url: &str) -> Result<Self> { Ok(Self::Random(Random::fetch(base_url).await?)) }
 }
 
 impl std::fmt::Display for Quote {
@@ -40,13 +42,14 @@ pub struct Random {
 }
 
 impl Random {
-    pub async fn fetch() -> Result<Self> {
+    /// Fetch a random quote from the Quotable API at `base_url`.
+    pub async fn fetch(base_url: &str) -> Result<Self> {
         // Construct request URL
-        const URL: &str = "https://api.quotable.io/random";
+        let url = format!("{base_url}/random");
 
         // Get the response
         let resp = match {
-            match reqwest::get(URL).await {
+            match reqwest::get(url).await {
                 Ok(x) => x,
                 Err(e) => return Err(Error::HttpRequest(e)),
             }
@@ -71,6 +74,7 @@ impl std::fmt::Display for Random {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commands::harness::{fixture, Stub, StubServer};
 
     #[test]
     fn test_random_quote_display() {
@@ -100,7 +104,12 @@ mod tests {
 
     #[tokio::test]
     async fn fetch_random_quote() {
-        let quote = Quote::random().await.unwrap();
+        let server = StubServer::start().await;
+        server
+            .route("/random", Stub::json(fixture!("quotable/random.json")))
+            .await;
+
+        let quote = Quote::random(server.url()).await.unwrap();
         let Quote::Random(quote) = quote;
 
         assert_eq!(
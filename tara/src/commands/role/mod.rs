@@ -1,13 +1,22 @@
-use std::{fmt::Write, sync::Arc};
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serenity::{
-    all::{CommandDataOptionValue, CommandInteraction, CommandOptionType, RoleId},
+    all::{CommandDataOptionValue, CommandInteraction, CommandOptionType, Guild, RoleId},
     builder::{CreateCommand, CreateCommandOption, CreateEmbed},
 };
 
-use super::{common::ExistingRole, CommandArguments, CommandResponse, DiscordCommand};
-use crate::{Error, IdUtil, Result};
+use super::{
+    common::{first_option, suboptions},
+    CommandArguments, CommandResponse, DiscordCommand,
+};
+use crate::{componet::DynComponent, roles::SelfAssignable, Error, Result};
+
+pub(super) mod panel;
+
+/// The role panels' buttons and select menus.
+pub(super) const COMPONENTS: &[DynComponent] = panel::COMPONENTS;
 
 pub const COMMAND: Role = Role;
 
@@ -34,7 +43,7 @@ impl DiscordCommand for Role {
             CreateCommandOption::new(
                 CommandOptionType::SubCommand,
                 "list",
-                "List all self-assignable roles",
+                "List all self-assignable roles by group",
             ),
         ];
 
@@ -46,72 +55,93 @@ impl DiscordCommand for Role {
 
     /// Run the discord command
     async fn run(&self, command: Arc<CommandInteraction>, args: CommandArguments) -> Result<CommandResponse> {
-        let option = &command.data.options[0];
+        let option = first_option(&command.data.options)?;
         let guild = args.guild.ok_or_else(|| Error::InternalLogic)?;
 
-        let ids = sqlx::query_as!(
-            ExistingRole,
-            "SELECT id FROM roles WHERE guild_id = $1",
-            guild.id.toint(),
-        )
-        .fetch_all(&args.database)
-        .await?
-        .into_iter()
-        .map(ExistingRole::id)
-        .collect::<Vec<RoleId>>();
+        let roles = SelfAssignable::load(&args.database, guild.id).await?;
 
         match &*option.name {
             "list" => {
-                let mut description = String::new();
-                for (i, id) in ids.iter().copied().enumerate() {
-                    if let Some(role) = guild.roles.get(&id) {
-                        let emoji = role.unicode_emoji.clone().map_or_else(String::new, |e| e + " ");
-                        write!(&mut description, "{emoji}{}", role.name).unwrap();
-
-                        if i != ids.len() - 1 {
-                            write!(&mut description, ", ").unwrap();
-                        }
+                // Roles that were deleted are left out.
+                let names = |ids: &mut dyn Iterator<Item = RoleId>| {
+                    ids.filter_map(|id| guild.roles.get(&id))
+                        .map(|role| {
+                            let emoji = role.unicode_emoji.clone().map_or_else(String::new, |e| e + " ");
+                            format!("{emoji}{}", role.name)
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+
+                let mut embed = CreateEmbed::new()
+                    .title("Roles")
+                    .description(names(&mut roles.ungrouped()));
+                for group in roles.groups() {
+                    let mut value = String::new();
+                    if !group.description.is_empty() {
+                        value = format!("{}\n", group.description);
+                    }
+                    value.push_str(&format!("*{}*", group.rules()));
+                    let members = names(&mut group.roles.iter().copied());
+                    if !members.is_empty() {
+                        value.push_str(&format!("\n{members}"));
                     }
+                    embed = embed.field(&group.name, value, false);
                 }
-
-                let roles = CreateEmbed::new().title("Roles").description(description);
-                Ok(CommandResponse::Embed(roles.into()))
+                Ok(CommandResponse::Embed(embed.into()))
             }
 
             "add" | "remove" => {
                 let role = {
-                    let CommandDataOptionValue::Role(role_id) = super::common::suboptions(option)[0].value
+                    let CommandDataOptionValue::Role(role_id) = first_option(suboptions(option)?)?.value
                     else {
                         return Err(crate::Error::InternalLogic);
                     };
-                    guild.roles.get(&role_id).unwrap()
+                    guild
+                        .roles
+                        .get(&role_id)
+                        .ok_or_else(|| Error::RoleNotAssignable(format!("<@&{role_id}>")))?
                 };
 
-                if !ids.into_iter().any(|x| x == role.id) {
+                if !roles.contains(role.id) {
                     return Err(Error::RoleNotAssignable(role.name.clone()));
                 }
 
-                // We can unwrap because this command only runs in guilds.
-                let mut member = command.member.clone().unwrap();
+                // This command only runs in guilds, so there's always a member.
+                let mut member = command.member.clone().ok_or(Error::InternalLogic)?;
 
                 match &*option.name {
                     "add" => {
+                        let swapped = roles.take(role.id, &member.roles)?;
+                        if !swapped.is_empty() {
+                            member
+                                .remove_roles(&args.http, &swapped)
+                                .await
+                                .map_err(|e| Error::UserRole(Box::new(e)))?;
+                        }
                         member
-                            .add_role(&args.context.http, role.id)
+                            .add_role(&args.http, role.id)
                             .await
                             .map_err(|e| Error::UserRole(Box::new(e)))?;
+                        let expiry = roles
+                            .took(&args.database, guild.id, member.user.id, role.id)
+                            .await?;
 
-                        Ok(format!("Added {}", role.name).into())
+                        Ok(added(&role.name, &swapped, expiry, &guild).into())
                     }
                     "remove" => {
+                        roles.give_up(role.id, &member.roles)?;
                         member
-                            .remove_role(&args.context.http, role.id)
+                            .remove_role(&args.http, role.id)
                             .await
                             .map_err(|e| Error::UserRole(Box::new(e)))?;
+                        args.database
+                            .remove_temporary_role(member.user.id, role.id)
+                            .await?;
 
                         Ok(format!("Removed {}", role.name).into())
                     }
-                    _ => unreachable!(),
+                    _ => Err(Error::InternalLogic),
                 }
             }
 
@@ -122,3 +152,68 @@ impl DiscordCommand for Role {
     /// The name of the command
     fn name(&self) -> &'static str { "role" }
 }
+
+/// What to tell a member that took the role `name` instead of the roles `swapped`, which
+/// they keep until `expiry`.
+fn added(name: &str, swapped: &[RoleId], expiry: Option<DateTime<Utc>>, guild: &Guild) -> String {
+    let swapped = swapped
+        .iter()
+        .filter_map(|x| guild.roles.get(x))
+        .map(|x| x.name.as_str())
+        .collect::<Vec<_>>();
+    let mut message = format!("Added {name}");
+    if !swapped.is_empty() {
+        message.push_str(&format!(" instead of {}", swapped.join(", ")));
+    }
+    if let Some(expiry) = expiry {
+        message.push_str(&format!(", it's taken away again <t:{}:R>", expiry.timestamp()));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use serde_json::json;
+    use serenity::all::RoleId;
+
+    use crate::{
+        commands::harness::{self, assert_err, role, role_option, subcommand, StubServer},
+        Error,
+    };
+
+    #[tokio::test]
+    async fn test_roles() {
+        let database = harness::database().await;
+        let server = StubServer::start().await;
+        let guild_id = u64::from(rand::random::<u32>()) + 1;
+        let (assignable, other) = (guild_id + 1, guild_id + 2);
+        let guild = harness::guild(
+            guild_id,
+            vec![role(assignable, "Gamer", Some("🎮")), role(other, "Admin", None)],
+        );
+        let args = harness::guild_arguments(&server, guild.clone(), database.clone()).await;
+        database
+            .add_self_assignable_role(guild.id, RoleId(NonZeroU64::new(assignable).unwrap()))
+            .await
+            .unwrap();
+
+        let response = harness::run("role", json!([subcommand("list", json!([]))]), args.clone())
+            .await
+            .unwrap();
+        assert_eq!(harness::message(response)["embeds"][0]["description"], "🎮 Gamer");
+
+        for role_id in [other, guild_id + 3] {
+            assert_err!(
+                harness::run(
+                    "role",
+                    json!([subcommand("add", json!([role_option("role", role_id)]))]),
+                    args.clone()
+                )
+                .await,
+                Error::RoleNotAssignable(_)
+            );
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Role panels: messages with a button or select menu option for every role of a role
+//! group, which toggle the role for whoever clicks them. Panels are registered for good,
+//! so they survive restarts, and are rebuilt whenever their group changes.
+use component_macro::component;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{ButtonStyle, ChannelId, ComponentInteraction, Guild, GuildId, MessageId, ReactionType, RoleId},
+    builder::{
+        CreateActionRow, CreateButton, CreateEmbed, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption, EditMessage,
+    },
+    http::Http,
+};
+use truncrate::TruncateToBoundary;
+
+use crate::{
+    commands::CommandArguments,
+    componet::{Component, ComponentMap, DynComponent, Registration, RouteParams, Selection},
+    database::Database,
+    roles::{RoleGroup, SelfAssignable},
+    Error, Result,
+};
+
+/// The panel's buttons and select menu.
+pub(super) const COMPONENTS: &[DynComponent] = &[&role_panel];
+
+/// Discord allows at most 5 rows of 5 buttons, or 25 select menu options.
+const MAX_ROLES: usize = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Style {
+    Buttons,
+    Select,
+}
+
+/// What's persisted of a panel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Panel {
+    group:   String,
+    style:   Style,
+    message: MessageId,
+}
+
+/// Send a panel for `group` to `channel_id`.
+pub async fn post(
+    args: &CommandArguments,
+    guild: &Guild,
+    group: &RoleGroup,
+    style: Style,
+    channel_id: ChannelId,
+    id: u64,
+) -> Result<()> {
+    let (embed, components) = contents(guild, Some(group), style, id);
+    let message = channel_id
+        .send_message(
+            &args.http,
+            CreateMessage::new().embed(embed).components(components),
+        )
+        .await?;
+
+    let panel = Panel {
+        group: group.name.clone(),
+        style,
+        message: message.id,
+    };
+    // Panels work until their message is deleted.
+    let registration = Registration::new(Some(guild.id), channel_id)
+        .permanent()
+        .with_state(&panel)
+        .map_err(|_| Error::InternalLogic)?;
+    args.component_map
+        .insert(
+            format!("role-panel/{id}"),
+            role_panel.with_state(panel),
+            None,
+            registration,
+        )
+        .await;
+    Ok(())
+}
+
+/// Rebuild every panel in `guild`, after its self-assignable roles or role groups
+/// changed. Panels whose message was deleted are forgotten.
+pub async fn refresh(
+    http: &Http,
+    database: &Database,
+    component_map: &ComponentMap,
+    guild: &Guild,
+) -> Result<()> {
+    let roles = SelfAssignable::load(database, guild.id).await?;
+    for (id, channel_id, panel) in panels(database, guild.id).await? {
+        let Some(number) = id.strip_prefix("role-panel/").and_then(|x| x.parse().ok()) else {
+            continue;
+        };
+        let group = roles.groups().iter().find(|x| x.name == panel.group);
+        let (embed, components) = contents(guild, group, panel.style, number);
+
+        let edit = EditMessage::new().embed(embed).components(components);
+        match channel_id.edit_message(http, panel.message, edit).await {
+            Ok(_) => {}
+            Err(serenity::Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {
+                if let Err(e) = component_map.timeout(id).await {
+                    tracing::warn!("{e}");
+                }
+            }
+            Err(e) => tracing::warn!("Couldn't refresh role panel '{id}': {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// The registration ID, channel and state of every panel in `guild_id`.
+async fn panels(database: &Database, guild_id: GuildId) -> Result<Vec<(String, ChannelId, Panel)>> {
+    let kind = role_panel.kind();
+    Ok(database
+        .components()
+        .await?
+        .into_iter()
+        .filter(|x| x.kind == kind && x.guild_id == Some(guild_id))
+        .filter_map(|x| Some((x.id, x.channel_id, bincode::deserialize(&x.state).ok()?)))
+        .collect())
+}
+
+/// The embed and components of the panel registered as `role-panel/{id}`. A group that
+/// was removed gets a panel without components.
+fn contents(
+    guild: &Guild,
+    group: Option<&RoleGroup>,
+    style: Style,
+    id: u64,
+) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let Some(group) = group else {
+        let embed = CreateEmbed::new().description("*These roles can't be picked here anymore.*");
+        return (embed, Vec::new());
+    };
+
+    let mut description = String::new();
+    if !group.description.is_empty() {
+        description = format!("{}\n", group.description);
+    }
+    description.push_str(&format!("*{}*", group.rules()));
+    let embed = CreateEmbed::new().title(&group.name).description(description);
+
+    // Roles that were deleted are left out.
+    let roles = group
+        .roles
+        .iter()
+        .filter_map(|x| guild.roles.get(x))
+        .take(MAX_ROLES)
+        .collect::<Vec<_>>();
+    if roles.is_empty() {
+        return (embed, Vec::new());
+    }
+
+    let emoji = |x: &serenity::all::Role| x.unicode_emoji.clone().map(ReactionType::Unicode);
+    let components = match style {
+        Style::Buttons => {
+            roles
+                .chunks(5)
+                .map(|row| {
+                    let buttons = row
+                        .iter()
+                        .map(|role| {
+                            let mut button = CreateButton::new(format!("role-panel/{id}/{}", role.id))
+                                .style(ButtonStyle::Secondary)
+                                .label(role.name.truncate_to_boundary(80));
+                            if let Some(emoji) = emoji(role) {
+                                button = button.emoji(emoji);
+                            }
+                            button
+                        })
+                        .collect();
+                    CreateActionRow::Buttons(buttons)
+                })
+                .collect()
+        }
+        Style::Select => {
+            let options = roles
+                .iter()
+                .map(|role| {
+                    let mut option =
+                        CreateSelectMenuOption::new(role.name.truncate_to_boundary(100), role.id.to_string());
+                    if let Some(emoji) = emoji(role) {
+                        option = option.emoji(emoji);
+                    }
+                    option
+                })
+                .collect();
+            let menu = CreateSelectMenu::new(
+                format!("role-panel/{id}/select"),
+                CreateSelectMenuKind::String { options },
+            )
+            .placeholder("Pick a role to take it, or give it up");
+            vec![CreateActionRow::SelectMenu(menu)]
+        }
+    };
+    (embed, components)
+}
+
+#[component(
+    state = Panel,
+    route = "role-panel/{id}/{action}",
+    restore = role_panel_restore
+)]
+async fn role_panel(
+    panel: &Panel,
+    interaction: ComponentInteraction,
+    args: CommandArguments,
+    params: RouteParams,
+    selection: Selection,
+) -> anyhow::Result<()> {
+    let action: String = params.get("action")?;
+    let role_id = if action == "select" {
+        RoleId::new(selection.one()?)
+    } else {
+        RoleId::new(action.parse()?)
+    };
+
+    // Errors are shown like a command's, since clicking the panel stands in for `/role`.
+    let content = toggle(panel, &interaction, &args, role_id)
+        .await
+        .unwrap_or_else(|e| format!("*[{}] {e}.*", e.code()));
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    interaction
+        .create_response(&args.http, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+async fn role_panel_restore(_id: String, state: Vec<u8>) -> anyhow::Result<Panel> {
+    Ok(bincode::deserialize(&state)?)
+}
+
+/// Give the member that used the panel `role_id`, or take it away when they have it.
+async fn toggle(
+    panel: &Panel,
+    interaction: &ComponentInteraction,
+    args: &CommandArguments,
+    role_id: RoleId,
+) -> Result<String> {
+    let guild = args.guild.as_ref().ok_or(Error::InternalLogic)?;
+    let mut member = interaction.member.clone().ok_or(Error::InternalLogic)?;
+    let roles = SelfAssignable::load(&args.database, guild.id).await?;
+    // The panel is out of date when the role was moved to another group.
+    let in_group = roles.group_of(role_id).is_some_and(|x| x.name == panel.group);
+    let role = guild
+        .roles
+        .get(&role_id)
+        .filter(|_| in_group && roles.contains(role_id))
+        .ok_or_else(|| Error::RoleNotAssignable(format!("<@&{role_id}>")))?;
+
+    if member.roles.contains(&role_id) {
+        roles.give_up(role_id, &member.roles)?;
+        member
+            .remove_role(&args.http, role_id)
+            .await
+            .map_err(|e| Error::UserRole(Box::new(e)))?;
+        args.database
+            .remove_temporary_role(member.user.id, role_id)
+            .await?;
+        return Ok(format!("Removed {}", role.name));
+    }
+
+    let swapped = roles.take(role_id, &member.roles)?;
+    if !swapped.is_empty() {
+        member
+            .remove_roles(&args.http, &swapped)
+            .await
+            .map_err(|e| Error::UserRole(Box::new(e)))?;
+    }
+    member
+        .add_role(&args.http, role_id)
+        .await
+        .map_err(|e| Error::UserRole(Box::new(e)))?;
+    let expiry = roles
+        .took(&args.database, guild.id, member.user.id, role_id)
+        .await?;
+
+    Ok(super::added(&role.name, &swapped, expiry, guild))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::commands::harness::{self, role};
+
+    #[test]
+    fn test_contents() {
+        let guild = harness::guild(1, vec![role(2, "Europe", Some("🇪🇺")), role(3, "Asia", None)]);
+        let group = RoleGroup {
+            roles: vec![RoleId::new(2), RoleId::new(3), RoleId::new(4)],
+            ..RoleGroup::new("region")
+        };
+
+        let (_, components) = contents(&guild, Some(&group), Style::Buttons, 5);
+        let components = serde_json::to_value(components).unwrap();
+        let buttons = &components[0]["components"];
+        assert_eq!(buttons.as_array().unwrap().len(), 2, "deleted roles are left out");
+        assert_eq!(buttons[0]["custom_id"], "role-panel/5/2");
+        assert_eq!(buttons[0]["emoji"], json!({"name": "🇪🇺"}));
+
+        let (_, components) = contents(&guild, Some(&group), Style::Select, 5);
+        let components = serde_json::to_value(components).unwrap();
+        assert_eq!(components[0]["components"][0]["custom_id"], "role-panel/5/select");
+        assert_eq!(components[0]["components"][0]["options"][1]["value"], "3");
+
+        let (_, components) = contents(&guild, None, Style::Select, 5);
+        assert!(components.is_empty());
+    }
+
+    #[test]
+    fn test_panel_state() {
+        let panel = Panel {
+            group:   String::from("region"),
+            style:   Style::Select,
+            message: MessageId::new(6),
+        };
+        let state = bincode::serialize(&panel).unwrap();
+
+        assert_eq!(bincode::deserialize::<Panel>(&state).unwrap(), panel);
+    }
+}
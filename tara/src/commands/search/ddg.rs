@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use rustrict::Type;
 use scraper::{Html, Selector};
 
-use crate::{Error, Result};
+use crate::{settings::SafeSearch, Error, Result};
 
 #[derive(Clone, Debug, Eq)]
 pub struct SearchResult {
@@ -33,19 +33,32 @@ impl std::fmt::Display for SearchResult {
     }
 }
 
-pub async fn scrape(search_term: &str, result_count: usize) -> Result<(Vec<SearchResult>, String)> {
+/// Scrape up to `result_count` results for `search_term` from the DuckDuckGo HTML
+/// frontend at `base_url`, returning them with the URL of the results page.
+///
+/// Unless `safe_search` is off, inappropriate search terms are refused and inappropriate
+/// results are left out. Strict safe search also turns on DuckDuckGo's own filter.
+pub async fn scrape(
+    base_url: &str,
+    search_term: &str,
+    result_count: usize,
+    safe_search: SafeSearch,
+) -> Result<(Vec<SearchResult>, String)> {
     use rustrict::Censor;
 
+    let filter = safe_search != SafeSearch::Off;
+    let inappropriate = |x: Type| filter && x.is(Type::SEXUAL | Type::PROFANE);
+
     // If the search term is sexual or profane, we stop here.
-    if Censor::from_str(search_term)
-        .analyze()
-        .is(Type::SEXUAL | Type::PROFANE)
-    {
+    if inappropriate(Censor::from_str(search_term).analyze()) {
         return Err(Error::InappropriateSearch(search_term.to_string()));
     }
 
     let search_term = urlencoding::encode(search_term);
-    let url = format!("https://duckduckgo.com/html?q={search_term}");
+    let url = match safe_search {
+        SafeSearch::Strict => format!("{base_url}/html?q={search_term}&kp=1"),
+        SafeSearch::Off | SafeSearch::Moderate => format!("{base_url}/html?q={search_term}"),
+    };
 
     let client = reqwest::Client::new();
     let resp = client.get(&url).send().await.map_err(Error::HttpRequest)?;
@@ -62,24 +75,24 @@ pub async fn scrape(search_term: &str, result_count: usize) -> Result<(Vec<Searc
         .select(&result_selector)
         .filter_map(|result| {
             // Get the title
-            let result_title = result.select(&result_title_selector).next().unwrap();
+            let result_title = result.select(&result_title_selector).next()?;
             let title = Censor::from_str(&result_title.text().collect::<String>())
                 .with_censor_replacement('#')
                 .censor_and_analyze();
 
             // If we've seen this title before, or the title is sexual or profane, we skip
             // this result.
-            if results_hash.contains(&title.0) || title.1.is(Type::SEXUAL | Type::PROFANE) {
+            if results_hash.contains(&title.0) || inappropriate(title.1) {
                 return None;
             }
 
-            let result_snippet = result.select(&result_snippet_selector).next().unwrap();
+            let result_snippet = result.select(&result_snippet_selector).next()?;
             let snippet = Censor::from_str(&result_snippet.text().collect::<String>())
                 .with_censor_replacement('#')
                 .censor_and_analyze();
 
             // If the snippet is sexual or profane, we skip this result
-            if snippet.1.is(Type::SEXUAL | Type::PROFANE) {
+            if inappropriate(snippet.1) {
                 return None;
             }
 
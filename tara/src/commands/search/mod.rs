@@ -3,18 +3,14 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use serenity::{
     all::{CommandInteraction, CommandOptionType},
-    builder::{
-        CreateCommand, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
-        EditInteractionResponse,
-    },
+    builder::{CreateCommand, CreateCommandOption, CreateEmbed},
 };
 use truncrate::TruncateToBoundary;
 
-use super::{common::unsplash, CommandArguments, CommandResponse, DiscordCommand};
+use super::{common::unsplash, paginator::Paginator, CommandArguments, CommandResponse, DiscordCommand};
 use crate::{defaults, Error, Result};
 
 mod ddg;
-mod image;
 
 pub const COMMAND: Search = Search;
 
@@ -89,7 +85,7 @@ impl DiscordCommand for Search {
 
     #[allow(clippy::cast_possible_truncation)]
     async fn run(&self, command: Arc<CommandInteraction>, args: CommandArguments) -> Result<CommandResponse> {
-        let option = &command.data.options[0];
+        let option = super::common::first_option(&command.data.options)?;
         match &*option.name {
             "image" => {
                 let (query, color, orientation) = {
@@ -97,12 +93,12 @@ impl DiscordCommand for Search {
                     let mut query = "";
                     let mut color = None;
                     let mut orientation = None;
-                    for option in super::common::suboptions(option) {
+                    for option in super::common::suboptions(option)? {
                         match &*option.name {
-                            "query" => query = option.value.as_str().unwrap(),
+                            "query" => query = option.value.as_str().ok_or(Error::InternalLogic)?,
                             "color" => color = option.value.as_str().map(ToString::to_string),
                             "orientation" => orientation = option.value.as_str().map(ToString::to_string),
-                            _ => unreachable!("How did {} get given to my bot?!", &option.name),
+                            _ => return Err(Error::InternalLogic),
                         }
                     }
 
@@ -114,56 +110,21 @@ impl DiscordCommand for Search {
                         "Unsplash images have been disabled".to_string(),
                     ));
                 };
-                let images = unsplash::UnsplashImage::search(api_key, query, color, orientation).await?;
-
-                let image = images
-                    .get(0)
-                    .ok_or(Error::NoSearchResults(format!("No search results for {query}!")))?;
-
-                // Initially create the response because we need the MessageId for a unique identifier.
-                command
-                    .create_response(
-                        &args.context.http,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().embed(image.into()),
-                        ),
-                    )
-                    .await?;
-
-                let message = command.get_response(&args.context.http).await?;
-                let umid = (command.channel_id, message.id);
-                let id = format!("{}-{}", command.channel_id, message.id);
-                let components = image::button_components(&id, 0, images.len(), false);
-
-                // Finally send the buttons
-                command
-                    .edit_response(
-                        &args.context.http,
-                        EditInteractionResponse::new().components(components.clone()),
-                    )
-                    .await?;
-
-                image::IMAGE_RESULTS
-                    .lock()
-                    .await
-                    .insert((command.channel_id, message.id), (images, 0, command.clone()));
-
-                args.component_map
-                    .insert(format!("{id}-next"), &image::forward_button_handler, None)
-                    .await;
-                args.component_map
-                    .insert(format!("{id}-prev"), &image::backward_button_handler, None)
-                    .await;
-
-                let mut users_lock = image::USERS.lock().await;
-                if let Some((previous_channel_id, previous_message_id)) =
-                    users_lock.insert(command.user.id, umid)
-                {
-                    let id = format!("{previous_channel_id}-{previous_message_id}");
-                    let _ = args.component_map.timeout(format!("{id}-next")).await;
-                    let _ = args.component_map.timeout(format!("{id}-prev")).await;
-                }
+                let images = unsplash::UnsplashImage::search(
+                    &args.config.endpoints.unsplash,
+                    api_key,
+                    query,
+                    color,
+                    orientation,
+                    args.guild_settings().await?.safe_search(),
+                )
+                .await?;
 
+                if images.is_empty() {
+                    return Err(Error::NoSearchResults(format!("No search results for {query}!")));
+                }
+                let pages = images.iter().map(CreateEmbed::from).collect();
+                Paginator::new(pages).send(&command, &args).await?;
 
                 Ok(CommandResponse::None)
             }
@@ -172,10 +133,12 @@ impl DiscordCommand for Search {
                 let mut search_term = None;
                 let mut result_count = 2;
 
-                for option in super::common::suboptions(option) {
+                for option in super::common::suboptions(option)? {
                     match &*option.name {
-                        "search_term" => search_term = Some(option.value.as_str().unwrap().to_string()),
-                        "result_count" => result_count = option.value.as_i64().unwrap().max(0) as usize,
+                        "search_term" => search_term = option.value.as_str().map(ToString::to_string),
+                        "result_count" => {
+                            result_count = option.value.as_i64().ok_or(Error::InternalLogic)?.max(0) as usize;
+                        }
                         _ => (),
                     }
                 }
@@ -183,7 +146,13 @@ impl DiscordCommand for Search {
                 let Some(search_term) = search_term else {
                     return Err(Error::InternalLogic);
                 };
-                let (results, url) = ddg::scrape(&search_term, result_count).await?;
+                let (results, url) = ddg::scrape(
+                    &args.config.endpoints.duckduckgo,
+                    &search_term,
+                    result_count,
+                    args.guild_settings().await?.safe_search(),
+                )
+                .await?;
 
                 // Get `result_count` number of results, create a string from it, then append a
                 // newline to the end.
@@ -206,7 +175,7 @@ impl DiscordCommand for Search {
                 }
                 return Ok(content.into());
             }
-            _ => unreachable!(),
+            _ => Err(Error::InternalLogic),
         }
     }
 
@@ -229,3 +198,124 @@ Valid arguments for Color filtering:
         Some(String::from(s))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        commands::harness::{self, assert_err, fixture, integer, string, subcommand, Stub, StubServer},
+        Error,
+    };
+
+    #[tokio::test]
+    async fn test_duckduckgo() {
+        let server = StubServer::start().await;
+        server
+            .route("/html?q=", Stub::html(fixture!("duckduckgo/search.html")))
+            .await;
+
+        let response = harness::run(
+            "search",
+            json!([subcommand(
+                "duckduckgo",
+                json!([string("search_term", "rust programming language")])
+            )]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        let content = harness::message(response)["content"].clone();
+
+        // Two results by default
+        assert_eq!(
+            content,
+            "***Rust Programming Language***\n\tA language empowering everyone to build reliable and \
+             efficient software.\n\n***Rust (programming language) - Wikipedia***\n\tRust is a \
+             multi-paradigm, general-purpose programming language that emphasizes performance, type safety, \
+             and concurrency.\n\n"
+        );
+        assert_eq!(
+            server.requests().await,
+            ["GET /html?q=rust%20programming%20language"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duckduckgo_no_results() {
+        let server = StubServer::start().await;
+        server
+            .route("/html?q=", Stub::html("<html><body></body></html>"))
+            .await;
+
+        assert_err!(
+            harness::run(
+                "search",
+                json!([subcommand(
+                    "duckduckgo",
+                    json!([string("search_term", "nothing"), integer("result_count", 5)])
+                )]),
+                harness::arguments_with(&server)
+            )
+            .await,
+            Error::NoSearchResults(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duckduckgo_inappropriate() {
+        let server = StubServer::start().await;
+
+        assert_err!(
+            harness::run(
+                "search",
+                json!([subcommand("duckduckgo", json!([string("search_term", "fuck")]))]),
+                harness::arguments_with(&server)
+            )
+            .await,
+            Error::InappropriateSearch(_)
+        );
+        assert!(server.requests().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_image_no_results() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/search/photos",
+                Stub::json(fixture!("unsplash/search_empty.json")),
+            )
+            .await;
+
+        assert_err!(
+            harness::run(
+                "search",
+                json!([subcommand(
+                    "image",
+                    json!([string("query", "nothing"), string("color", "green")])
+                )]),
+                harness::arguments_with(&server)
+            )
+            .await,
+            Error::NoSearchResults(_)
+        );
+        assert_eq!(
+            server.requests().await,
+            ["GET /search/photos?client_id=unsplash-key&query=nothing&color=green"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_image_disabled() {
+        assert_err!(
+            harness::run(
+                "search",
+                json!([subcommand("image", json!([string("query", "cats")]))]),
+                harness::arguments()
+            )
+            .await,
+            Error::FeatureDisabled(_)
+        );
+    }
+}
@@ -1,21 +1,27 @@
 use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
-use rand::{seq::SliceRandom, thread_rng};
+use component_macro::component;
 use serde::{Deserialize, Serialize};
 use serenity::{
-    all::{CommandInteraction, CommandOptionType},
+    all::{CommandInteraction, CommandOptionType, ComponentInteraction},
     builder::{CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter},
 };
 
 use super::{
-    common::CommandResponse,
-    movie::{OmdbErrorResponse, OmdbRating},
+    common::{replace_with_embed, CommandResponse},
+    movie::{self, OmdbRating},
     CommandArguments, DiscordCommand,
 };
-use crate::{Error, Result};
+use crate::{
+    componet::{Authorization, Component, DynComponent, RouteParams, Selection},
+    Error, Result,
+};
 pub const COMMAND: Series = Series;
 
+/// The menu to pick a series from when the title wasn't found.
+pub(super) const COMPONENTS: &[DynComponent] = &[&series_pick];
+
 pub struct Series;
 
 #[async_trait]
@@ -69,17 +75,20 @@ impl DiscordCommand for Series {
             (title, year, full_plot, episode)
         };
 
-        let api_key = {
-            let choose_default_key = || *super::movie::OMDB_API_KEYS.choose(&mut thread_rng()).unwrap();
-
-            args.config
-                .secrets
-                .omdb_api_key
-                .as_ref()
-                .map_or_else(choose_default_key, String::as_str)
-        };
+        let api_key = movie::api_key(&args.config);
+        let base_url = &args.config.endpoints.omdb;
 
-        let series = OmdbSeries::series(api_key, title, year, full_plot, episode).await?;
+        let series =
+            match OmdbSeries::series(base_url, api_key, title, year.clone(), full_plot, episode).await {
+                // Offer the closest matches instead, episodes are too ambiguous to search for.
+                Err(Error::NoSearchResults(e)) if !episode => {
+                    let route = format!("series-pick/{full_plot}");
+                    return movie::disambiguate(base_url, api_key, title, year, "series", &route)
+                        .await
+                        .ok_or(Error::NoSearchResults(e));
+                }
+                series => series?,
+            };
         let embed: CreateEmbed = series.into();
 
         Ok(CommandResponse::Embed(Box::new(embed)))
@@ -88,6 +97,24 @@ impl DiscordCommand for Series {
     fn name(&self) -> &'static str { "series" }
 }
 
+#[component(route = "series-pick/{full}", authorize = Authorization::Invoker)]
+async fn series_pick(
+    interaction: ComponentInteraction,
+    args: CommandArguments,
+    params: RouteParams,
+    selection: Selection,
+) -> anyhow::Result<()> {
+    let full_plot: bool = params.get("full")?;
+    let imdb_id: String = selection.one()?;
+
+    let api_key = movie::api_key(&args.config);
+    let series = OmdbSeries::by_id(&args.config.endpoints.omdb, api_key, &imdb_id, full_plot).await?;
+    interaction
+        .create_response(&args.http, replace_with_embed(series.into()))
+        .await?;
+    Ok(())
+}
+
 /// Movie metadata from `OMDb`
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -153,7 +180,7 @@ impl From<OmdbSeries> for CreateEmbed {
                 .next()
                 .unwrap_or("0")
                 .parse::<u64>()
-                .unwrap(),
+                .unwrap_or_default(),
         ))
         .to_string();
 
@@ -176,6 +203,7 @@ impl From<OmdbSeries> for CreateEmbed {
 
 impl OmdbSeries {
     pub async fn series(
+        base_url: &str,
         omdb_api_key: &str,
         title: &str,
         year: Option<String>,
@@ -186,25 +214,91 @@ impl OmdbSeries {
         let plot = if full_plot { "&plot=full" } else { "" };
         let kind = if episode { "episode" } else { "series" };
         let url = format!(
-            "http://www.omdbapi.com/?t={}{year}{plot}&apikey={omdb_api_key}&type={kind}",
+            "{base_url}/?t={}{year}{plot}&apikey={omdb_api_key}&type={kind}",
             urlencoding::encode(title)
         );
 
-        let response = reqwest::get(&url).await?.text().await?;
+        Self::get(&url, full_plot).await
+    }
 
-        let mut movie = match serde_json::from_str::<Self>(&response) {
-            Ok(x) => Ok(x),
-            Err(e) => {
-                let err = serde_json::from_str::<OmdbErrorResponse>(&response)
-                    .map_err(|_| Error::JsonParse(e.to_string()))?;
-                Err(Error::NoSearchResults(err.error))
-            }
-        }?;
+    /// Get the series with the IMDb ID `imdb_id` from the `OMDb` API at `base_url`
+    pub async fn by_id(base_url: &str, omdb_api_key: &str, imdb_id: &str, full_plot: bool) -> Result<Self> {
+        let plot = if full_plot { "&plot=full" } else { "" };
+        let url = format!(
+            "{base_url}/?i={}{plot}&apikey={omdb_api_key}",
+            urlencoding::encode(imdb_id)
+        );
+
+        Self::get(&url, full_plot).await
+    }
+
+    async fn get(url: &str, full_plot: bool) -> Result<Self> {
+        let mut series: Self = movie::fetch(url).await?;
 
         if full_plot {
-            movie.plot = format!("||{}||", movie.plot);
+            series.plot = format!("||{}||", series.plot);
         }
 
-        Ok(movie)
+        Ok(series)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        commands::harness::{self, assert_err, boolean, fixture, string, Stub, StubServer},
+        Error,
+    };
+
+    #[tokio::test]
+    async fn test_series() {
+        let server = StubServer::start().await;
+        server
+            .route("/?t=", Stub::json(fixture!("omdb/series.json")))
+            .await;
+
+        let response = harness::run(
+            "series",
+            json!([string("title", "Breaking Bad")]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        let embed = &harness::message(response)["embeds"][0];
+
+        assert_eq!(embed["title"], "Breaking Bad (2008–2013)");
+        assert!(embed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|x| x["name"] == "Seasons" && x["value"] == "5"));
+        assert_eq!(
+            server.requests().await,
+            ["GET /?t=Breaking%20Bad&apikey=omdb-key&type=series"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_series_episode() {
+        let server = StubServer::start().await;
+        server
+            .route("/?t=", Stub::json(fixture!("omdb/not_found.json")))
+            .await;
+
+        assert_err!(
+            harness::run(
+                "series",
+                json!([string("title", "Pilot"), boolean("episode", true)]),
+                harness::arguments_with(&server)
+            )
+            .await,
+            Error::NoSearchResults(_)
+        );
+        assert_eq!(
+            server.requests().await,
+            ["GET /?t=Pilot&apikey=omdb-key&type=episode"]
+        );
     }
 }
@@ -2,13 +2,27 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use serenity::{
-    all::{CommandDataOptionValue, CommandInteraction, CommandOptionType},
-    builder::{CreateCommand, CreateCommandOption},
+    all::{
+        ChannelType, CommandDataOption, CommandDataOptionValue, CommandInteraction, CommandOptionType, Guild,
+    },
+    builder::{
+        CreateAttachment, CreateCommand, CreateCommandOption, CreateEmbed, CreateInteractionResponseMessage,
+    },
     model::Permissions,
 };
+use tara_util::ipc::BundleFormat;
 
-use super::{CommandArguments, CommandResponse, DiscordCommand};
-use crate::{commands::common::ExistingRole, Error, IdUtil};
+use super::{role::panel, CommandArguments, CommandResponse, DiscordCommand};
+use crate::{
+    commands::common::{first_option, suboptions},
+    database::GuildBundle,
+    roles::{RoleGroup, RoleRules},
+    settings::{Kind, Setting, SETTINGS},
+    Error, IdUtil,
+};
+
+/// Exported servers are tiny, anything bigger than this isn't one.
+const MAX_BUNDLE_SIZE: usize = 1024 * 1024;
 
 pub const COMMAND: Settings = Settings;
 
@@ -18,38 +32,114 @@ pub struct Settings;
 #[async_trait]
 impl DiscordCommand for Settings {
     fn register(&self) -> CreateCommand {
-        let options = vec![
+        let mut set = CreateCommandOption::new(
+            CommandOptionType::SubCommandGroup,
+            "set",
+            "Set Tara's settings for this guild",
+        )
+        .add_sub_option(
             CreateCommandOption::new(
-                CommandOptionType::SubCommandGroup,
-                "set",
-                "Set Tara's settings for this guild",
+                CommandOptionType::SubCommand,
+                "add_self_assignable_role",
+                "Add a role to the list of roles that users can self-assign",
             )
             .add_sub_option(
-                CreateCommandOption::new(
-                    CommandOptionType::SubCommand,
-                    "add_self_assignable_role",
-                    "Add a role to the list of roles that users can self-assign",
-                )
-                .add_sub_option(
-                    CreateCommandOption::new(CommandOptionType::Role, "role", "The role to add")
-                        .required(true),
-                ),
+                CreateCommandOption::new(CommandOptionType::Role, "role", "The role to add").required(true),
+            ),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "remove_self_assignable_role",
+                "Remove a role from the list of roles that users can self-assign",
             )
             .add_sub_option(
-                CreateCommandOption::new(
-                    CommandOptionType::SubCommand,
-                    "remove_self_assignable_role",
-                    "Remove a role from the list of roles that users can self-assign",
-                )
-                .add_sub_option(
-                    CreateCommandOption::new(CommandOptionType::Role, "role", "The role to remove")
-                        .required(true),
-                ),
+                CreateCommandOption::new(CommandOptionType::Role, "role", "The role to remove")
+                    .required(true),
+            ),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "add_auto_role",
+                "Give a role to everyone that joins",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Role, "role", "The role to give").required(true),
             ),
+        )
+        .add_sub_option(
             CreateCommandOption::new(
-                CommandOptionType::SubCommandGroup,
+                CommandOptionType::SubCommand,
+                "remove_auto_role",
+                "Stop giving a role to everyone that joins",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Role, "role", "The role to stop giving")
+                    .required(true),
+            ),
+        );
+        for setting in SETTINGS {
+            let mut value =
+                CreateCommandOption::new(setting.option_type(), "value", setting.description).required(true);
+            match setting.kind {
+                Kind::Channel => value = value.channel_types(vec![ChannelType::Text, ChannelType::News]),
+                Kind::Choice(choices) => {
+                    for choice in choices {
+                        value = value.add_string_choice(*choice, *choice);
+                    }
+                }
+                Kind::Role
+                | Kind::Boolean
+                | Kind::Choices(_)
+                | Kind::Roles
+                | Kind::Channels
+                | Kind::Words => {}
+            }
+            set = set.add_sub_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, setting.name, setting.description)
+                    .add_sub_option(value),
+            );
+        }
+
+        let options = vec![
+            set,
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
                 "view",
-                "View a setting's value",
+                "View this guild's settings",
+            )
+            .add_sub_option(setting_option("The setting to view, all of them by default")),
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reset",
+                "Put a setting back to its default",
+            )
+            .add_sub_option(setting_option("The setting to reset").required(true)),
+            role_groups(),
+            role_rules(),
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "export",
+                "Export everything Tara has for this guild, to import it into another Tara",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "format", "JSON by default")
+                    .add_string_choice("JSON", "json")
+                    .add_string_choice("TOML", "toml"),
+            ),
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "import",
+                "Replace everything Tara has for this guild with an export",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Attachment,
+                    "export",
+                    "The file made by /settings export",
+                )
+                .required(true),
             ),
         ];
 
@@ -65,78 +155,944 @@ impl DiscordCommand for Settings {
         command: Arc<CommandInteraction>,
         args: CommandArguments,
     ) -> crate::Result<CommandResponse> {
-        let option = &command.data.options[0];
-        let guild = args.guild.unwrap();
+        let option = first_option(&command.data.options)?;
+        let guild = args
+            .guild
+            .clone()
+            .ok_or_else(|| Error::CommandMisuse("This command can only be used in a server".to_string()))?;
         match &*option.name {
             "set" => {
-                let option = &super::common::suboptions(option)[0];
-                let option_name = option.name.clone();
-                let option = &super::common::suboptions(option)[0];
-                let CommandDataOptionValue::Role(role_id) = option.value else {
-                    return Err(crate::Error::InternalLogic);
-                };
-
-                match &*option_name {
-                    "add_self_assignable_role" => {
-                        let role = { guild.roles.get(&role_id).unwrap() };
-                        let inserted = sqlx::query_as!(
-                            ExistingRole,
-                            "INSERT INTO roles (id, guild_id) VALUES ($1, $2)
-                            ON CONFLICT DO NOTHING
-                            returning id",
-                            role.id.toint(),
-                            guild.id.toint(),
-                        )
-                        .fetch_optional(&args.database)
-                        .await?
-                        .map(ExistingRole::id);
-
-                        // For the message
-                        if let Some(id) = inserted {
-                            Ok(format!("Added '{}' ({id}) to self-assignable roles!", role.name).into())
-                        } else {
-                            Ok(format!(
-                                "'{}' ({}) is already part of the guild's self-assingable roles.",
-                                role.name, role.id
-                            )
-                            .into())
-                        }
+                let option = first_option(suboptions(option)?)?;
+                match &*option.name {
+                    name @ ("add_self_assignable_role" | "remove_self_assignable_role") => {
+                        self_assignable_role(name, option, &guild, &args).await
+                    }
+                    name @ ("add_auto_role" | "remove_auto_role") => {
+                        auto_role(name, option, &guild, &args).await
                     }
+                    name => {
+                        let setting = Setting::find(name).ok_or(Error::InternalLogic)?;
+                        let value = first_option(suboptions(option)?)?;
+                        let value = setting.value_of(&value.value)?;
+                        setting.validate(&value, &guild)?;
 
-                    "remove_self_assignable_role" => {
-                        let role = { guild.roles.get(&role_id).unwrap() };
-                        let removed = sqlx::query_as!(
-                            ExistingRole,
-                            "DELETE FROM roles WHERE id = $1 RETURNING id",
-                            role.id.toint(),
-                        )
-                        .fetch_optional(&args.database)
-                        .await?
-                        .map(ExistingRole::id);
-
-                        if let Some(id) = removed {
-                            Ok(format!(
-                                "Removed '{}' ({id}) from the guild's self-assignable roles.",
-                                role.name
-                            )
-                            .into())
-                        } else {
-                            Err(Error::CommandMisuse(format!(
-                                "'{}' ({}) wasn't part of the self-assignable roles and couldn't be removed!",
-                                role.name, role.id
-                            )))
-                        }
+                        let message = format!("Set `{}` to {value}.", setting.name);
+                        args.settings.set(guild.id, setting, value).await?;
+                        Ok(message.into())
                     }
-                    _ => unreachable!(),
                 }
             }
             "view" => {
-                let _option = &super::common::suboptions(option)[0];
-                return Err(crate::Error::InternalLogic);
+                let values = args.settings.get(guild.id).await?;
+                let setting = match suboptions(option)?.first() {
+                    Some(x) => Some(setting_of(x)?),
+                    None => None,
+                };
+
+                let mut embed = CreateEmbed::new().title(format!("Settings for {}", guild.name));
+                for &setting in setting.as_ref().map_or(SETTINGS, std::slice::from_ref) {
+                    let value = values
+                        .get(setting)
+                        .map_or_else(|| String::from("*Not set*"), ToString::to_string);
+                    let default = if values.is_set(setting) {
+                        ""
+                    } else {
+                        " *(default)*"
+                    };
+                    embed = embed.field(
+                        setting.name,
+                        format!("{value}{default}\n*{}*", setting.description),
+                        false,
+                    );
+                }
+                Ok(CommandResponse::Embed(Box::new(embed)))
+            }
+            "reset" => {
+                let setting = setting_of(first_option(suboptions(option)?)?)?;
+                args.settings.reset(guild.id, setting).await?;
+
+                let default = setting
+                    .default
+                    .as_ref()
+                    .map_or_else(|| String::from("not set"), ToString::to_string);
+                Ok(format!("Reset `{}`, it's {default} again.", setting.name).into())
             }
-            _ => return Err(crate::Error::InternalLogic),
+            "export" => {
+                let format = match suboptions(option)?.first().and_then(|x| x.value.as_str()) {
+                    Some("toml") => BundleFormat::Toml,
+                    _ => BundleFormat::Json,
+                };
+                let bundle = GuildBundle::export(&args.database, guild.id).await?;
+                let file =
+                    CreateAttachment::bytes(bundle.write(format)?.into_bytes(), bundle.file_name(format));
+
+                Ok(CommandResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!(
+                            "Here's everything Tara has for {}. Use `/settings import` with it to bring it \
+                             to another Tara.",
+                            guild.name
+                        ))
+                        .add_file(file)
+                        .ephemeral(true),
+                ))
+            }
+            "import" => import(&command, option, &guild, &args).await,
+            "role_groups" => role_group(&command, first_option(suboptions(option)?)?, &guild, &args).await,
+            "role_rules" => role_rule(first_option(suboptions(option)?)?, &guild, &args).await,
+            _ => Err(crate::Error::InternalLogic),
         }
     }
 
     fn name(&self) -> &'static str { "settings" }
 }
+
+/// The `role_groups` subcommands, which sort the self-assignable roles into groups.
+fn role_groups() -> CreateCommandOption {
+    let name = |description| {
+        CreateCommandOption::new(CommandOptionType::String, "name", description)
+            .max_length(32)
+            .required(true)
+    };
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "role_groups",
+        "Sort the self-assignable roles into groups",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "save",
+            "Add a role group, or change one. What isn't given is left as it was",
+        )
+        .add_sub_option(name("The name of the group"))
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "description", "What the group is for")
+                .max_length(100),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "min",
+                "How many of the roles members have to keep once they picked some",
+            )
+            .min_int_value(0),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "max",
+                "How many of the roles members can have, 0 for any number",
+            )
+            .min_int_value(0),
+        )
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "exclusive",
+            "Whether picking a role swaps out the one the member had",
+        )),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "remove",
+            "Remove a role group, its roles stay self-assignable",
+        )
+        .add_sub_option(name("The group to remove")),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "assign",
+            "Put a self-assignable role into a group",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Role, "role", "The role to move").required(true),
+        )
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "group",
+            "The group to move it to, it's taken out of its group when this isn't given",
+        )),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "panel",
+            "Post a message members can pick the group's roles with",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "group", "The group to offer")
+                .max_length(32)
+                .required(true),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Channel, "channel", "Where to post it")
+                .channel_types(vec![ChannelType::Text, ChannelType::News])
+                .required(true),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "style", "Buttons by default")
+                .add_string_choice("Buttons", "buttons")
+                .add_string_choice("Select menu", "select"),
+        ),
+    )
+}
+
+/// Run one of the [`role_groups`] subcommands.
+async fn role_group(
+    command: &CommandInteraction,
+    option: &CommandDataOption,
+    guild: &Guild,
+    args: &CommandArguments,
+) -> crate::Result<CommandResponse> {
+    let options = suboptions(option)?;
+    let string = |name| {
+        options
+            .iter()
+            .find(|x| x.name == name)
+            .and_then(|x| x.value.as_str())
+    };
+    let integer = |name| {
+        options
+            .iter()
+            .find(|x| x.name == name)
+            .and_then(|x| x.value.as_i64())
+            .map(|x| u32::try_from(x).unwrap_or(u32::MAX))
+    };
+    let groups = args.database.role_groups(guild.id).await?;
+
+    match &*option.name {
+        "save" => {
+            let name = string("name").ok_or(Error::InternalLogic)?;
+            let existing = groups.into_iter().find(|x| x.name == name);
+            let created = existing.is_none();
+            let mut group = existing.unwrap_or_else(|| RoleGroup::new(name));
+            if let Some(description) = string("description") {
+                group.description = description.to_string();
+            }
+            if let Some(min) = integer("min") {
+                group.min_roles = min;
+            }
+            if let Some(max) = integer("max") {
+                group.max_roles = (max > 0).then_some(max);
+            }
+            if let Some(exclusive) = options.iter().find(|x| x.name == "exclusive") {
+                group.exclusive = exclusive.value.as_bool().ok_or(Error::InternalLogic)?;
+            }
+            group.validate()?;
+
+            args.database.save_role_group(guild.id, &group).await?;
+            refresh_panels(guild, args).await;
+            let verb = if created { "Added" } else { "Changed" };
+            Ok(format!("{verb} the role group `{}`: {}.", group.name, group.rules()).into())
+        }
+        "remove" => {
+            let name = string("name").ok_or(Error::InternalLogic)?;
+            if args.database.remove_role_group(guild.id, name).await? {
+                refresh_panels(guild, args).await;
+                Ok(format!("Removed the role group `{name}`.").into())
+            } else {
+                Err(Error::CommandMisuse(format!(
+                    "There's no role group called `{name}`"
+                )))
+            }
+        }
+        "assign" => {
+            let role_id = options
+                .iter()
+                .find_map(|x| {
+                    match x.value {
+                        CommandDataOptionValue::Role(x) => Some(x),
+                        _ => None,
+                    }
+                })
+                .ok_or(Error::InternalLogic)?;
+            let group = string("group");
+            if let Some(group) = group.filter(|group| !groups.iter().any(|x| x.name == *group)) {
+                return Err(Error::CommandMisuse(format!(
+                    "There's no role group called `{group}`"
+                )));
+            }
+            if !args.database.set_role_group(role_id, group).await? {
+                return Err(Error::CommandMisuse(format!(
+                    "<@&{role_id}> isn't self-assignable"
+                )));
+            }
+            refresh_panels(guild, args).await;
+
+            match group {
+                Some(group) => Ok(format!("Moved <@&{role_id}> to `{group}`.").into()),
+                None => Ok(format!("Took <@&{role_id}> out of its group.").into()),
+            }
+        }
+        "panel" => {
+            let name = string("group").ok_or(Error::InternalLogic)?;
+            let channel_id = options
+                .iter()
+                .find_map(|x| {
+                    match x.value {
+                        CommandDataOptionValue::Channel(x) => Some(x),
+                        _ => None,
+                    }
+                })
+                .ok_or(Error::InternalLogic)?;
+            let style = match string("style") {
+                Some("select") => panel::Style::Select,
+                _ => panel::Style::Buttons,
+            };
+            let group = groups
+                .iter()
+                .find(|x| x.name == name)
+                .ok_or_else(|| Error::CommandMisuse(format!("There's no role group called `{name}`")))?;
+            if group.roles.is_empty() {
+                return Err(Error::CommandMisuse(format!(
+                    "`{name}` has no roles yet, put some into it with `/settings role_groups assign`"
+                )));
+            }
+
+            panel::post(args, guild, group, style, channel_id, command.id.get()).await?;
+            Ok(format!("Posted the `{name}` roles in <#{channel_id}>.").into())
+        }
+        _ => Err(Error::InternalLogic),
+    }
+}
+
+/// The `role_rules` subcommands, which limit who can have a self-assignable role and for
+/// how long.
+fn role_rules() -> CreateCommandOption {
+    let role =
+        CreateCommandOption::new(CommandOptionType::Role, "role", "The self-assignable role").required(true);
+    CreateCommandOption::new(
+        CommandOptionType::SubCommandGroup,
+        "role_rules",
+        "Limit who can take a self-assignable role, and for how long",
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "require",
+            "Make members have another role before they can take a self-assignable role",
+        )
+        .add_sub_option(role.clone())
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Role,
+            "required",
+            "The role they need first, anyone can take it when this isn't given",
+        )),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "expire",
+            "Take a self-assignable role away again some time after it was taken",
+        )
+        .add_sub_option(role)
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "after",
+                "How long members keep it, like `1h 30m`. They keep it for good when this isn't given",
+            )
+            .max_length(32),
+        ),
+    )
+}
+
+/// Run one of the [`role_rules`] subcommands.
+async fn role_rule(
+    option: &CommandDataOption,
+    guild: &Guild,
+    args: &CommandArguments,
+) -> crate::Result<CommandResponse> {
+    let options = suboptions(option)?;
+    let role = |name| {
+        options.iter().find(|x| x.name == name).and_then(|x| {
+            match x.value {
+                CommandDataOptionValue::Role(x) => Some(x),
+                _ => None,
+            }
+        })
+    };
+    let role_id = role("role").ok_or(Error::InternalLogic)?;
+    let mut rules = args
+        .database
+        .role_rules(guild.id)
+        .await?
+        .into_iter()
+        .find(|x| x.role == role_id)
+        .unwrap_or_else(|| RoleRules::new(role_id));
+
+    let message = match &*option.name {
+        "require" => {
+            rules.requires = role("required");
+            match rules.requires {
+                Some(required) if required == role_id => {
+                    return Err(Error::CommandMisuse(String::from("A role can't require itself")));
+                }
+                Some(required) => format!("Members need <@&{required}> before they can take <@&{role_id}>."),
+                None => format!("Anyone can take <@&{role_id}>."),
+            }
+        }
+        "expire" => {
+            let after = options
+                .iter()
+                .find(|x| x.name == "after")
+                .and_then(|x| x.value.as_str());
+            rules.lifetime = after.map(lifetime).transpose()?;
+            match after {
+                Some(after) => format!("Members lose <@&{role_id}> {after} after they take it."),
+                None => format!("Members keep <@&{role_id}> for good."),
+            }
+        }
+        _ => return Err(Error::InternalLogic),
+    };
+
+    if !args.database.save_role_rules(&rules).await? {
+        return Err(Error::CommandMisuse(format!(
+            "<@&{role_id}> isn't self-assignable"
+        )));
+    }
+    Ok(message.into())
+}
+
+/// The seconds in a role lifetime like `1h 30m`.
+fn lifetime(text: &str) -> crate::Result<u64> {
+    let lifetime = humantime::parse_duration(text)
+        .map_err(|e| Error::CommandMisuse(format!("`{text}` isn't a duration like `1h 30m`: {e}")))?;
+    // The roles are checked once a minute.
+    if lifetime.as_secs() < 60 {
+        return Err(Error::CommandMisuse(String::from(
+            "Roles can be taken away after a minute at the earliest",
+        )));
+    }
+    Ok(lifetime.as_secs())
+}
+
+/// Rebuild the role panels after the roles they offer changed. The change sticks even
+/// when a panel can't be rebuilt.
+async fn refresh_panels(guild: &Guild, args: &CommandArguments) {
+    if let Err(e) = panel::refresh(&args.http, &args.database, &args.component_map, guild).await {
+        tracing::warn!("Couldn't refresh the role panels of guild {}: {e}", guild.id);
+    }
+}
+
+/// An option to pick one of the settings with.
+fn setting_option(description: &str) -> CreateCommandOption {
+    SETTINGS.iter().fold(
+        CreateCommandOption::new(CommandOptionType::String, "setting", description),
+        |option, setting| option.add_string_choice(setting.name, setting.name),
+    )
+}
+
+/// The setting picked with an option made by [`setting_option`].
+fn setting_of(option: &CommandDataOption) -> crate::Result<&'static Setting> {
+    let name = option.value.as_str().ok_or(Error::InternalLogic)?;
+    Setting::find(name).ok_or_else(|| Error::CommandMisuse(format!("There's no setting called `{name}`")))
+}
+
+/// Replace the guild's rows with the bundle attached to `/settings import`.
+async fn import(
+    command: &CommandInteraction,
+    option: &CommandDataOption,
+    guild: &Guild,
+    args: &CommandArguments,
+) -> crate::Result<CommandResponse> {
+    let CommandDataOptionValue::Attachment(id) = first_option(suboptions(option)?)?.value else {
+        return Err(Error::InternalLogic);
+    };
+    let attachment = command
+        .data
+        .resolved
+        .attachments
+        .get(&id)
+        .ok_or(Error::InternalLogic)?;
+    if attachment.size as usize > MAX_BUNDLE_SIZE {
+        return Err(Error::CommandMisuse(String::from(
+            "That file is too big to be an exported server",
+        )));
+    }
+
+    let bytes = attachment.download().await?;
+    let text = String::from_utf8(bytes)
+        .map_err(|_| Error::CommandMisuse(String::from("That file isn't an exported server")))?;
+    let bundle = GuildBundle::parse(&text)?;
+    bundle.validate(guild)?;
+    bundle.import(&args.database).await?;
+    args.settings.forget(guild.id).await;
+    if let Err(e) = args.component_map.reload(guild.id).await {
+        tracing::warn!("Couldn't reload the components of guild {}: {e}", guild.id);
+    }
+    refresh_panels(guild, args).await;
+
+    Ok(format!(
+        "Imported {} self-assignable roles, {} settings and {} components from the export made on {}.",
+        bundle.self_assignable_roles.len(),
+        bundle.settings.len(),
+        bundle.components.len(),
+        bundle.exported.format("%Y-%m-%d"),
+    )
+    .into())
+}
+
+/// Add a role to, or remove it from, the roles everyone that joins the guild gets.
+async fn auto_role(
+    name: &str,
+    option: &CommandDataOption,
+    guild: &Guild,
+    args: &CommandArguments,
+) -> crate::Result<CommandResponse> {
+    let option = first_option(suboptions(option)?)?;
+    let CommandDataOptionValue::Role(role_id) = option.value else {
+        return Err(crate::Error::InternalLogic);
+    };
+    let role = guild
+        .roles
+        .get(&role_id)
+        .ok_or_else(|| Error::CommandMisuse(format!("<@&{role_id}> isn't a role in this server")))?;
+
+    match name {
+        "add_auto_role" => {
+            // Everyone has @everyone, and managed roles can only be given by their integration.
+            if role.id.touint() == guild.id.touint() || role.managed {
+                return Err(Error::CommandMisuse(format!(
+                    "'{}' can't be given to anyone",
+                    role.name
+                )));
+            }
+            if args.database.add_auto_role(guild.id, role.id).await? {
+                Ok(format!("Everyone that joins gets '{}' now.", role.name).into())
+            } else {
+                Ok(format!("Everyone that joins already gets '{}'.", role.name).into())
+            }
+        }
+        "remove_auto_role" => {
+            if args.database.remove_auto_role(role.id).await? {
+                Ok(format!("Members that join don't get '{}' anymore.", role.name).into())
+            } else {
+                Err(Error::CommandMisuse(format!(
+                    "'{}' isn't given to members that join",
+                    role.name
+                )))
+            }
+        }
+        _ => Err(Error::InternalLogic),
+    }
+}
+
+/// Add a role to, or remove it from, the guild's self-assignable roles.
+async fn self_assignable_role(
+    name: &str,
+    option: &CommandDataOption,
+    guild: &Guild,
+    args: &CommandArguments,
+) -> crate::Result<CommandResponse> {
+    let option = first_option(suboptions(option)?)?;
+    let CommandDataOptionValue::Role(role_id) = option.value else {
+        return Err(crate::Error::InternalLogic);
+    };
+    let role = guild
+        .roles
+        .get(&role_id)
+        .ok_or_else(|| Error::CommandMisuse(format!("<@&{role_id}> isn't a role in this server")))?;
+
+    match name {
+        "add_self_assignable_role" => {
+            if args.database.add_self_assignable_role(guild.id, role.id).await? {
+                Ok(format!("Added '{}' ({}) to self-assignable roles!", role.name, role.id).into())
+            } else {
+                Ok(format!(
+                    "'{}' ({}) is already part of the guild's self-assingable roles.",
+                    role.name, role.id
+                )
+                .into())
+            }
+        }
+
+        "remove_self_assignable_role" => {
+            if args.database.remove_self_assignable_role(role.id).await? {
+                refresh_panels(guild, args).await;
+                Ok(format!(
+                    "Removed '{}' ({}) from the guild's self-assignable roles.",
+                    role.name, role.id
+                )
+                .into())
+            } else {
+                Err(Error::CommandMisuse(format!(
+                    "'{}' ({}) wasn't part of the self-assignable roles and couldn't be removed!",
+                    role.name, role.id
+                )))
+            }
+        }
+        _ => Err(Error::InternalLogic),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        commands::harness::{
+            self, assert_err, boolean, channel_option, integer, role, role_option, string, subcommand,
+            subcommand_group, StubServer,
+        },
+        database::GuildBundle,
+        settings::SafeSearch,
+        Error,
+    };
+
+    fn set(name: &str, role_id: u64) -> serde_json::Value {
+        json!([subcommand_group(
+            "set",
+            json!([subcommand(name, json!([role_option("role", role_id)]))])
+        )])
+    }
+
+    #[tokio::test]
+    async fn test_self_assignable_roles() {
+        let database = harness::database().await;
+        let server = StubServer::start().await;
+        let guild_id = u64::from(rand::random::<u32>()) + 1;
+        let guild = harness::guild(guild_id, vec![role(guild_id + 1, "Gamer", None)]);
+        let args = harness::guild_arguments(&server, guild, database).await;
+
+        let response = harness::run(
+            "settings",
+            set("add_self_assignable_role", guild_id + 1),
+            args.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(harness::message(response)["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("Added 'Gamer'"));
+
+        let response = harness::run(
+            "settings",
+            set("add_self_assignable_role", guild_id + 1),
+            args.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(harness::message(response)["content"]
+            .as_str()
+            .unwrap()
+            .contains("is already part of"));
+
+        let response = harness::run(
+            "settings",
+            set("remove_self_assignable_role", guild_id + 1),
+            args.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(harness::message(response)["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("Removed 'Gamer'"));
+
+        assert_err!(
+            harness::run("settings", set("remove_self_assignable_role", guild_id + 1), args).await,
+            Error::CommandMisuse(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_role_outside_guild() {
+        let server = StubServer::start().await;
+        let mut args = harness::arguments_with(&server);
+        args.guild = Some(harness::guild(1, vec![]));
+
+        // The role isn't checked against the database if it's not in the guild
+        assert_err!(
+            harness::run("settings", set("add_self_assignable_role", 2), args).await,
+            Error::CommandMisuse(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_settings() {
+        let database = harness::database().await;
+        let server = StubServer::start().await;
+        let guild_id = u64::from(rand::random::<u32>()) + 1;
+        let guild = harness::guild(guild_id, vec![]);
+        let args = harness::guild_arguments(&server, guild, database).await;
+        let view = || json!([subcommand("view", json!([string("setting", "safe_search")]))]);
+        let field = |response| harness::message(response)["embeds"][0]["fields"][0]["value"].clone();
+
+        let response = harness::run("settings", view(), args.clone()).await.unwrap();
+        assert!(field(response)
+            .as_str()
+            .unwrap()
+            .starts_with("moderate *(default)*"));
+
+        let set = json!([subcommand_group(
+            "set",
+            json!([subcommand("safe_search", json!([string("value", "strict")]))])
+        )]);
+        let response = harness::run("settings", set, args.clone()).await.unwrap();
+        assert_eq!(
+            harness::message(response)["content"],
+            "Set `safe_search` to strict."
+        );
+
+        let response = harness::run("settings", view(), args.clone()).await.unwrap();
+        assert!(field(response).as_str().unwrap().starts_with("strict\n"));
+
+        let reset = json!([subcommand("reset", json!([string("setting", "safe_search")]))]);
+        let response = harness::run("settings", reset, args.clone()).await.unwrap();
+        assert_eq!(
+            harness::message(response)["content"],
+            "Reset `safe_search`, it's moderate again."
+        );
+        assert_eq!(
+            args.guild_settings().await.unwrap().safe_search(),
+            SafeSearch::Moderate
+        );
+    }
+
+    #[tokio::test]
+    async fn test_everyone_dj_role() {
+        let server = StubServer::start().await;
+        let mut args = harness::arguments_with(&server);
+        args.guild = Some(harness::guild(1, vec![role(1, "@everyone", None)]));
+
+        // Validation happens before the database is touched
+        let set = json!([subcommand_group(
+            "set",
+            json!([subcommand("dj_role", json!([role_option("value", 1)]))])
+        )]);
+        assert_err!(harness::run("settings", set, args).await, Error::CommandMisuse(_));
+    }
+
+    #[tokio::test]
+    async fn test_import_validation() {
+        let database = harness::database().await;
+        let guild = harness::guild(1, vec![role(2, "Gamer", None)]);
+        let exported = |roles: &str, settings: &str| {
+            GuildBundle::parse(&format!(
+                r#"{{"version": 1, "guild_id": "1", "exported": "2023-08-06T00:00:00Z",
+                "self_assignable_roles": {roles}, "settings": {settings}}}"#
+            ))
+            .unwrap()
+        };
+
+        let bundle = exported(r#"["2"]"#, r#"{"safe_search": {"Choice": "strict"}}"#);
+        bundle.validate(&guild).unwrap();
+        bundle.import(&database).await.unwrap();
+        assert_eq!(
+            database.self_assignable_roles(guild.id).await.unwrap(),
+            bundle.self_assignable_roles
+        );
+
+        // Every problem is reported at once, and nothing is imported.
+        let bundle = exported(
+            r#"["3"]"#,
+            r#"{"safe_search": {"Choice": "none"}, "removed": {"Boolean": true}}"#,
+        );
+        let Err(Error::CommandMisuse(problems)) = bundle.validate(&guild) else {
+            panic!("the bundle doesn't fit the guild");
+        };
+        assert_eq!(problems.lines().count(), 4, "{problems}");
+
+        let other_guild = harness::guild(5, vec![role(2, "Gamer", None)]);
+        assert_err!(
+            exported("[]", "{}").validate(&other_guild),
+            Error::CommandMisuse(_)
+        );
+
+        // Temporary roles and components can't point at another server or at nothing.
+        let bundle = GuildBundle::parse(
+            r#"{"version": 4, "guild_id": "1", "exported": "2023-08-06T00:00:00Z",
+            "temporary_roles": [
+                {"guild_id": "1", "user_id": "7", "role_id": "2", "expiry": "2023-08-07T00:00:00Z"},
+                {"guild_id": "5", "user_id": "7", "role_id": "2", "expiry": "2023-08-07T00:00:00Z"},
+                {"guild_id": "1", "user_id": "7", "role_id": "3", "expiry": "2023-08-07T00:00:00Z"}
+            ],
+            "components": [{"id": "panel/9", "channel_id": "9", "kind": "removed", "state": [],
+                "authorization": "everyone", "expiry": null}]}"#,
+        )
+        .unwrap();
+        let Err(Error::CommandMisuse(problems)) = bundle.validate(&guild) else {
+            panic!("the bundle doesn't fit the guild");
+        };
+        assert_eq!(problems.lines().count(), 6, "{problems}");
+    }
+
+    #[tokio::test]
+    async fn test_role_groups() {
+        let database = harness::database().await;
+        let server = StubServer::start().await;
+        let guild_id = u64::from(rand::random::<u32>()) + 1;
+        let guild = harness::guild(guild_id, vec![role(guild_id + 1, "Europe", None)]);
+        let args = harness::guild_arguments(&server, guild, database.clone()).await;
+        let role_groups = |name: &str, options| {
+            json!([subcommand_group(
+                "role_groups",
+                json!([subcommand(name, options)])
+            )])
+        };
+        let content = |response| harness::message(response)["content"].clone();
+
+        let save = role_groups(
+            "save",
+            json!([string("name", "region"), boolean("exclusive", true)]),
+        );
+        let response = harness::run("settings", save, args.clone()).await.unwrap();
+        assert_eq!(
+            content(response),
+            "Added the role group `region`: Pick at most one."
+        );
+        let save = role_groups("save", json!([string("name", "region"), integer("min", 1)]));
+        let response = harness::run("settings", save, args.clone()).await.unwrap();
+        assert_eq!(content(response), "Changed the role group `region`: Pick one.");
+        let save = role_groups("save", json!([string("name", "region"), integer("max", 2)]));
+        assert_err!(
+            harness::run("settings", save, args.clone()).await,
+            Error::CommandMisuse(_)
+        );
+
+        // Only self-assignable roles can be put into groups.
+        let assign = || {
+            role_groups(
+                "assign",
+                json!([role_option("role", guild_id + 1), string("group", "region")]),
+            )
+        };
+        assert_err!(
+            harness::run("settings", assign(), args.clone()).await,
+            Error::CommandMisuse(_)
+        );
+        harness::run(
+            "settings",
+            set("add_self_assignable_role", guild_id + 1),
+            args.clone(),
+        )
+        .await
+        .unwrap();
+        harness::run("settings", assign(), args.clone()).await.unwrap();
+        let groups = database
+            .role_groups(args.guild.as_ref().unwrap().id)
+            .await
+            .unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].roles.len(), 1);
+
+        let response = harness::run("role", json!([subcommand("list", json!([]))]), args.clone())
+            .await
+            .unwrap();
+        let field = &harness::message(response)["embeds"][0]["fields"][0];
+        assert_eq!(field["name"], "region");
+        assert_eq!(field["value"], "*Pick one*\nEurope");
+
+        // Panels are only posted for groups with roles.
+        let panel = |group| {
+            role_groups(
+                "panel",
+                json!([string("group", group), channel_option("channel", guild_id + 2)]),
+            )
+        };
+        let save = role_groups("save", json!([string("name", "pronouns")]));
+        harness::run("settings", save, args.clone()).await.unwrap();
+        for group in ["pronouns", "games"] {
+            assert_err!(
+                harness::run("settings", panel(group), args.clone()).await,
+                Error::CommandMisuse(_)
+            );
+        }
+        assert!(server.requests().await.is_empty());
+
+        let remove = || role_groups("remove", json!([string("name", "region")]));
+        harness::run("settings", remove(), args.clone()).await.unwrap();
+        assert_err!(
+            harness::run("settings", remove(), args.clone()).await,
+            Error::CommandMisuse(_)
+        );
+        assert_eq!(
+            database
+                .self_assignable_roles(args.guild.as_ref().unwrap().id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_role_rules() {
+        let database = harness::database().await;
+        let server = StubServer::start().await;
+        let guild_id = u64::from(rand::random::<u32>()) + 1;
+        let (member, verified) = (guild_id + 1, guild_id + 2);
+        let guild = harness::guild(
+            guild_id,
+            vec![role(member, "Member", None), role(verified, "Verified", None)],
+        );
+        let args = harness::guild_arguments(&server, guild, database.clone()).await;
+        let role_rules =
+            |name: &str, options| json!([subcommand_group("role_rules", json!([subcommand(name, options)]))]);
+        let require = role_rules(
+            "require",
+            json!([role_option("role", member), role_option("required", verified)]),
+        );
+        let expire = |after| {
+            role_rules(
+                "expire",
+                json!([role_option("role", member), string("after", after)]),
+            )
+        };
+
+        // Only self-assignable roles have rules.
+        assert_err!(
+            harness::run("settings", require.clone(), args.clone()).await,
+            Error::CommandMisuse(_)
+        );
+        harness::run("settings", set("add_self_assignable_role", member), args.clone())
+            .await
+            .unwrap();
+        harness::run("settings", require, args.clone()).await.unwrap();
+        for after in ["soon", "30s"] {
+            assert_err!(
+                harness::run("settings", expire(after), args.clone()).await,
+                Error::CommandMisuse(_)
+            );
+        }
+        harness::run("settings", expire("1h 30m"), args.clone())
+            .await
+            .unwrap();
+
+        let rules = database
+            .role_rules(args.guild.as_ref().unwrap().id)
+            .await
+            .unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].requires.map(|x| x.get()), Some(verified));
+        assert_eq!(rules[0].lifetime, Some(5400));
+
+        // Auto-roles
+        harness::run("settings", set("add_auto_role", member), args.clone())
+            .await
+            .unwrap();
+        assert_err!(
+            harness::run("settings", set("add_auto_role", guild_id), args.clone()).await,
+            Error::CommandMisuse(_)
+        );
+        assert_eq!(
+            database
+                .auto_roles(args.guild.as_ref().unwrap().id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        harness::run("settings", set("remove_auto_role", member), args.clone())
+            .await
+            .unwrap();
+        assert_err!(
+            harness::run("settings", set("remove_auto_role", member), args).await,
+            Error::CommandMisuse(_)
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Feeds malformed options into every command to make sure they error instead of
+//! panicking.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use serenity::{all::CommandInteraction, builder::CreateCommand};
+
+use super::{
+    harness::{self, assert_err, integer, number, string, subcommand},
+    suggest_isolated, CommandArguments, CommandResponse, DiscordCommand,
+};
+use crate::{Error, Result};
+
+async fn run(name: &str, options: Value) -> Result<CommandResponse> {
+    harness::run(name, options, harness::arguments()).await
+}
+
+#[tokio::test]
+async fn test_commands_without_options() {
+    for name in [
+        "random",
+        "wikipedia",
+        "settings",
+        "conversions",
+        "search",
+        "role",
+        "help",
+        "mod",
+    ] {
+        assert_err!(run(name, json!([])).await, Error::ExpectedSuboption);
+    }
+}
+
+#[tokio::test]
+async fn test_unknown_subcommands() {
+    for name in ["random", "conversions", "search"] {
+        assert_err!(
+            run(name, json!([subcommand("nonexistent", json!([]))])).await,
+            Error::InternalLogic
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_random_malformed() {
+    // A subcommand that's really a string
+    assert_err!(
+        run("random", json!([string("number", "5")])).await,
+        Error::ExpectedSuboption
+    );
+    assert_err!(
+        run(
+            "random",
+            json!([subcommand(
+                "number",
+                json!([number("low", 10.0), number("high", 1.0)])
+            )])
+        )
+        .await,
+        Error::CommandMisuse(_)
+    );
+    assert_err!(
+        run(
+            "random",
+            json!([subcommand("number", json!([string("lower", "1")]))])
+        )
+        .await,
+        Error::InternalLogic
+    );
+}
+
+#[tokio::test]
+async fn test_conversions_malformed() {
+    assert_err!(
+        run("conversions", json!([subcommand("temperature", json!([]))])).await,
+        Error::InternalLogic
+    );
+    assert_err!(
+        run(
+            "conversions",
+            json!([subcommand(
+                "temperature",
+                json!([integer("value", 5), string("target", "c")])
+            )])
+        )
+        .await,
+        Error::InternalLogic
+    );
+    // No currency API key in the default configuration
+    assert_err!(
+        run("conversions", json!([subcommand("currency", json!([]))])).await,
+        Error::FeatureDisabled(_)
+    );
+}
+
+#[tokio::test]
+async fn test_search_malformed() {
+    assert_err!(
+        run(
+            "search",
+            json!([subcommand("image", json!([string("colour", "red")]))])
+        )
+        .await,
+        Error::InternalLogic
+    );
+    assert_err!(
+        run(
+            "search",
+            json!([subcommand("image", json!([integer("query", 1)]))])
+        )
+        .await,
+        Error::InternalLogic
+    );
+    assert_err!(
+        run(
+            "search",
+            json!([subcommand("duckduckgo", json!([integer("result_count", 3)]))])
+        )
+        .await,
+        Error::InternalLogic
+    );
+}
+
+#[tokio::test]
+async fn test_text_commands_malformed() {
+    for name in ["define", "movie", "series"] {
+        assert_err!(
+            run(name, json!([string("nonexistent", "x")])).await,
+            Error::InternalLogic
+        );
+    }
+    for name in ["movie", "series"] {
+        assert_err!(
+            run(name, json!([integer("title", 1)])).await,
+            Error::InternalLogic
+        );
+    }
+    assert_err!(
+        run("wikipedia", json!([integer("title", 1)])).await,
+        Error::InternalLogic
+    );
+    assert_err!(
+        run("help", json!([integer("command", 1)])).await,
+        Error::InternalLogic
+    );
+}
+
+#[tokio::test]
+async fn test_guild_commands_in_dms() {
+    assert_err!(
+        run("settings", json!([subcommand("view", json!([]))])).await,
+        Error::CommandMisuse(_)
+    );
+    assert_err!(
+        run("role", json!([subcommand("list", json!([]))])).await,
+        Error::InternalLogic
+    );
+    #[cfg(feature = "music")]
+    assert_err!(
+        run("music", json!([subcommand("play", json!([]))])).await,
+        Error::InternalLogic
+    );
+}
+
+/// A command whose suggestions panic.
+struct Panicking;
+
+#[async_trait]
+impl DiscordCommand for Panicking {
+    fn register(&self) -> CreateCommand { CreateCommand::new("panicking") }
+
+    async fn run(
+        &self,
+        _command: Arc<CommandInteraction>,
+        _args: CommandArguments,
+    ) -> Result<CommandResponse> {
+        Ok(CommandResponse::None)
+    }
+
+    fn name(&self) -> &'static str { "panicking" }
+
+    async fn autocomplete(
+        &self,
+        _command: Arc<CommandInteraction>,
+        _args: CommandArguments,
+    ) -> Result<Vec<(String, String)>> {
+        panic!("malformed")
+    }
+}
+
+#[tokio::test]
+async fn test_panicking_suggestions() {
+    let command = Arc::new(harness::interaction("panicking", json!([])));
+    let suggestions = suggest_isolated(&Panicking, command, harness::arguments()).await;
+    assert!(matches!(suggestions, Err(Error::Incident(_))), "{suggestions:?}");
+}
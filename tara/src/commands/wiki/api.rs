@@ -45,44 +45,51 @@ impl Page {
         }
     }
 
-    /// Search for a page on Wikipedia and return a `Page`
-    pub async fn search(search_term: &str) -> Result<Self> {
+    /// Search for a page on the Wikipedia at `base_url` and return a `Page`
+    pub async fn search(base_url: &str, search_term: &str) -> Result<Self> {
+        let mut pages = Self::search_many(base_url, search_term, 1).await?;
+        Ok(pages.remove(0))
+    }
+
+    /// Search for up to `limit` pages on the Wikipedia at `base_url`, best match first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WikipedaSearch`] if nothing was found.
+    pub async fn search_many(base_url: &str, search_term: &str, limit: usize) -> Result<Vec<Self>> {
         type SearchResult = (String, Vec<String>, Vec<String>, Vec<String>);
 
         // Replace spaces with %20 for the url
         let title = search_term.replace(' ', "%20");
 
         let request_url = format!(
-            "https://en.wikipedia.org/w/api.php?action=opensearch&search={}&limit=1&namespace=0&format=json",
+            "{base_url}/w/api.php?action=opensearch&search={}&limit={limit}&namespace=0&format=json",
             title.trim()
         );
 
-        // Make the API call, parse the json to a `Page`.
-        let page = match { reqwest::get(&request_url).await?.json::<SearchResult>().await } {
-            Ok(resp) => {
-                let t = match resp.1.get(0) {
-                    Some(x) => x.to_string(),
-                    None => return Err(Error::WikipedaSearch(search_term.to_string())),
-                };
-
-                let u = match resp.3.get(0) {
-                    Some(x) => x.to_string(),
-                    None => return Err(Error::WikipedaSearch(search_term.to_string())),
-                };
-
-                Self::new(t, u)
+        // Make the API call, parse the json to `Page`s.
+        let pages = match { reqwest::get(&request_url).await?.json::<SearchResult>().await } {
+            Ok((_, titles, _, urls)) => {
+                titles
+                    .into_iter()
+                    .zip(urls)
+                    .map(|(t, u)| Self::new(t, u))
+                    .collect::<Vec<_>>()
             }
             Err(e) => return Err(Error::JsonParse(e.to_string())),
         };
 
-
-        Ok(page)
+        if pages.is_empty() {
+            return Err(Error::WikipedaSearch(search_term.to_string()));
+        }
+        Ok(pages)
     }
 
-    pub async fn get_summary(self) -> Result<String> {
-        let request_url =
-        format!(
-            "https://en.wikipedia.org/w/api.php?action=query&format=json&prop=extracts&titles={}&formatversion=2&exchars=1000&explaintext=1&redirects=1",
+    /// Get a plain text summary of the page from the Wikipedia at `base_url`
+    pub async fn get_summary(self, base_url: &str) -> Result<String> {
+        let request_url = format!(
+            "{base_url}/w/api.php?action=query&format=json&prop=extracts&titles={}&formatversion=2&\
+             exchars=1000&explaintext=1&redirects=1",
             self.title
         );
 
@@ -92,7 +99,13 @@ impl Page {
             Err(e) => return Err(Error::JsonParse(e.to_string())),
         };
 
-        let summary_text = resp.query.pages.get(0).unwrap().extract.clone();
+        let summary_text = resp
+            .query
+            .pages
+            .first()
+            .ok_or_else(|| Error::WikipedaSearch(self.title.to_string()))?
+            .extract
+            .clone();
 
         Ok(summary_text)
     }
@@ -102,31 +115,60 @@ impl Page {
 
 pub mod tests {
     use super::Page;
+    use crate::{
+        commands::harness::{fixture, Stub, StubServer},
+        Error,
+    };
+
+    async fn server() -> StubServer {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/w/api.php?action=opensearch",
+                Stub::json(fixture!("wikipedia/opensearch.json")),
+            )
+            .await
+            .route(
+                "/w/api.php?action=query",
+                Stub::json(fixture!("wikipedia/summary.json")),
+            )
+            .await;
+        server
+    }
 
     #[tokio::test]
     async fn test_search_page() {
+        let server = server().await;
         let expected_page = Page::new(
             "Albert Einstein".to_string(),
             "https://en.wikipedia.org/wiki/Albert_Einstein".to_string(),
         );
-        let page = Page::search("Albert Einstein").await.unwrap();
+        let page = Page::search(server.url(), "Albert Einstein").await.unwrap();
         assert_eq!(page, expected_page);
+        assert_eq!(
+            server.requests().await,
+            ["GET /w/api.php?action=opensearch&search=Albert%20Einstein&limit=1&namespace=0&format=json"]
+        );
     }
 
     #[tokio::test]
-    async fn test_search_page_misspelled() {
-        let expected_page = Page::new(
-            "Programming language".to_string(),
-            "https://en.wikipedia.org/wiki/Programming_language".to_string(),
-        );
-        let page = Page::search("progrmming lang").await.unwrap();
-        assert_eq!(page, expected_page);
+    async fn test_search_page_not_found() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/w/api.php?action=opensearch",
+                Stub::json(fixture!("wikipedia/opensearch_empty.json")),
+            )
+            .await;
+        let page = Page::search(server.url(), "asdfghjklqwerty").await;
+        assert!(matches!(page, Err(Error::WikipedaSearch(_))));
     }
 
     #[tokio::test]
     async fn test_get_page_summary() {
-        let page = Page::search("Albert Einstein").await.unwrap();
-        let r = page.get_summary().await;
-        assert!(r.is_ok());
+        let server = server().await;
+        let page = Page::search(server.url(), "Albert Einstein").await.unwrap();
+        let summary = page.get_summary(server.url()).await.unwrap();
+        assert!(summary.starts_with("Albert Einstein ( EYEN-styne;"));
     }
 }
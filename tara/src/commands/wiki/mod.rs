@@ -1,19 +1,33 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use component_macro::component;
 use serenity::{
-    all::{CommandDataOptionValue, CommandInteraction, CommandOptionType},
-    builder::{CreateCommand, CreateCommandOption, CreateEmbed},
+    all::{CommandDataOptionValue, CommandInteraction, CommandOptionType, ComponentInteraction},
+    builder::{CreateCommand, CreateCommandOption, CreateEmbed, CreateInteractionResponseMessage},
 };
 use truncrate::TruncateToBoundary;
 
-use super::{CommandArguments, DiscordCommand};
-use crate::{commands::CommandResponse, defaults, Result};
+use super::{
+    common::{replace_with_embed, string_select},
+    CommandArguments, DiscordCommand,
+};
+use crate::{
+    commands::CommandResponse,
+    componet::{Authorization, Component, DynComponent, RouteParams, Selection},
+    defaults, Error, Result,
+};
 
 mod api;
 
 pub const COMMAND: Wiki = Wiki;
 
+/// The menu to pick a page from when the search is ambiguous.
+pub(super) const COMPONENTS: &[DynComponent] = &[&wiki_pick];
+
+/// How many pages to offer when the search is ambiguous.
+const MAX_CHOICES: usize = 10;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Wiki;
 
@@ -33,41 +47,141 @@ impl DiscordCommand for Wiki {
             .set_options(options)
     }
 
-    async fn run(
-        &self,
-        command: Arc<CommandInteraction>,
-        _args: CommandArguments,
-    ) -> Result<CommandResponse> {
+    async fn run(&self, command: Arc<CommandInteraction>, args: CommandArguments) -> Result<CommandResponse> {
         use api::Page;
 
         let title = {
             // Get the role argument
-            let mut title = None;
-            if let CommandDataOptionValue::String(input) = &command.data.options[0].value {
-                title = Some(input);
-            }
-            title.unwrap().trim().to_owned()
+            let option = super::common::first_option(&command.data.options)?;
+            let CommandDataOptionValue::String(input) = &option.value else {
+                return Err(Error::InternalLogic);
+            };
+            input.trim().to_owned()
         };
 
-        let page = Page::search(&title).await?;
-        let url = page.url.clone();
-        let title = page.title.clone();
-        let mut content = page.get_summary().await?;
+        let base_url = &args.config.endpoints.wikipedia;
+        let mut pages = Page::search_many(base_url, &title, MAX_CHOICES).await?;
 
-        let max = defaults::content_character_limit_default();
-        // Truncate wiki content.
-        if content.len() >= max {
-            content = format!("{}…", content.truncate_to_boundary(max));
+        // Let the user pick unless it's obvious which page they meant.
+        let exact = pages.iter().position(|x| x.title.eq_ignore_ascii_case(&title));
+        if pages.len() > 1 && exact.is_none() {
+            let options = pages
+                .into_iter()
+                .map(|x| (x.title.to_string(), x.title.to_string()));
+            let message = CreateInteractionResponseMessage::new()
+                .content(format!(
+                    "There's more than one page for \"{title}\", which one did you mean?"
+                ))
+                .components(vec![string_select("wiki-pick", "Pick a page", options)]);
+            return Ok(CommandResponse::Message(message));
         }
 
-        // Create an embed from everything
-        let embed = CreateEmbed::new()
-            .title(title.to_string())
-            .description(content)
-            .url(url.to_string());
-
-        Ok(CommandResponse::Embed(Box::new(embed)))
+        let page = pages.remove(exact.unwrap_or_default());
+        Ok(CommandResponse::Embed(Box::new(summary(base_url, page).await?)))
     }
 
     fn name(&self) -> &'static str { "wikipedia" }
 }
+
+/// An embed with the summary of `page`.
+async fn summary(base_url: &str, page: api::Page) -> Result<CreateEmbed> {
+    let url = page.url.clone();
+    let title = page.title.clone();
+    let mut content = page.get_summary(base_url).await?;
+
+    let max = defaults::content_character_limit_default();
+    // Truncate wiki content.
+    if content.len() >= max {
+        content = format!("{}…", content.truncate_to_boundary(max));
+    }
+
+    // Create an embed from everything
+    Ok(CreateEmbed::new()
+        .title(title.to_string())
+        .description(content)
+        .url(url.to_string()))
+}
+
+#[component(route = "wiki-pick", authorize = Authorization::Invoker)]
+async fn wiki_pick(
+    interaction: ComponentInteraction,
+    args: CommandArguments,
+    _params: RouteParams,
+    selection: Selection,
+) -> anyhow::Result<()> {
+    let title: String = selection.one()?;
+    let base_url = &args.config.endpoints.wikipedia;
+    let page = api::Page::search(base_url, &title).await?;
+
+    let embed = summary(base_url, page).await?;
+    interaction
+        .create_response(&args.http, replace_with_embed(embed))
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::commands::harness::{self, fixture, string, Stub, StubServer};
+
+    #[tokio::test]
+    async fn test_wikipedia() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/w/api.php?action=opensearch",
+                Stub::json(fixture!("wikipedia/opensearch.json")),
+            )
+            .await
+            .route(
+                "/w/api.php?action=query",
+                Stub::json(fixture!("wikipedia/summary.json")),
+            )
+            .await;
+
+        let response = harness::run(
+            "wikipedia",
+            json!([string("title", "albert einstein")]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        let embed = &harness::message(response)["embeds"][0];
+
+        assert_eq!(embed["title"], "Albert Einstein");
+        assert_eq!(embed["url"], "https://en.wikipedia.org/wiki/Albert_Einstein");
+        // The summary is longer than the content limit
+        assert!(embed["description"].as_str().unwrap().ends_with('…'));
+    }
+
+    #[tokio::test]
+    async fn test_wikipedia_ambiguous() {
+        let server = StubServer::start().await;
+        server
+            .route(
+                "/w/api.php?action=opensearch",
+                Stub::json(fixture!("wikipedia/opensearch_ambiguous.json")),
+            )
+            .await;
+
+        let response = harness::run(
+            "wikipedia",
+            json!([string("title", "mercury")]),
+            harness::arguments_with(&server),
+        )
+        .await
+        .unwrap();
+        let menu = &harness::message(response)["components"][0]["components"][0];
+
+        assert_eq!(menu["custom_id"], "wiki-pick");
+        assert_eq!(menu["options"][0]["value"], "Mercury (planet)");
+        assert_eq!(menu["options"].as_array().unwrap().len(), 3);
+        // The summary isn't fetched until a page is picked
+        assert_eq!(
+            server.requests().await,
+            ["GET /w/api.php?action=opensearch&search=mercury&limit=10&namespace=0&format=json"]
+        );
+    }
+}
@@ -1,20 +1,93 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{any::Any, collections::HashMap, str::FromStr, sync::Arc};
 
 use anyhow::Context;
 use async_trait::async_trait;
 use chrono::{DateTime, Duration, Utc};
-use serenity::{all::ComponentInteraction, client::Cache, http::Http};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{
+        ActionRow, ActionRowComponent, ChannelId, ComponentInteraction, ComponentInteractionDataKind,
+        GuildId, ModalInteraction, Permissions, RoleId, UserId,
+    },
+    builder::{CreateInteractionResponse, CreateInteractionResponseMessage},
+    client::Cache,
+    http::Http,
+};
 use tokio::sync::RwLock;
 
-use crate::commands::CommandArguments;
+use crate::{
+    commands::CommandArguments,
+    database::{Database, StoredComponent},
+    incident,
+};
 
-type DynComponent = &'static (dyn Component + Send + Sync);
+pub(crate) type DynComponent = &'static (dyn Component + Send + Sync);
+/// A registered component, owning whatever state it was registered with.
+pub(crate) type SharedComponent = Arc<dyn Component + Send + Sync>;
+pub(crate) type DynModal = &'static (dyn Modal + Send + Sync);
+
+/// Every component that can be registered by its [`Component::kind`], so handlers can be
+/// found again after a restart.
+static COMPONENTS: Lazy<HashMap<&'static str, DynComponent>> = Lazy::new(|| {
+    let mut components: Vec<DynComponent> = crate::commands::COMPONENTS.concat();
+    #[cfg(feature = "ai")]
+    components.extend_from_slice(crate::llm::COMPONENTS);
+    components.into_iter().map(|f| (f.kind(), f)).collect()
+});
+
+/// Whether this Tara has a component of `kind`, see [`Component::kind`].
+pub(crate) fn has_kind(kind: &str) -> bool { COMPONENTS.contains_key(kind) }
+
+/// The [`Route`] of every component that has one.
+static ROUTES: Lazy<Vec<(Route, DynComponent)>> = Lazy::new(|| {
+    COMPONENTS
+        .values()
+        .filter_map(|f| Some((Route::new(f.route()?), *f)))
+        .collect()
+});
+
+/// Every modal with its [`Route`].
+static MODALS: Lazy<Vec<(Route, DynModal)>> = Lazy::new(|| {
+    crate::commands::MODALS
+        .concat()
+        .into_iter()
+        .map(|f| (Route::new(f.route()), f))
+        .collect()
+});
 
 #[async_trait]
 pub trait Component {
+    /// A name for the handler that doesn't change between runs. It's stored with every
+    /// registration to find the handler again after a restart.
+    fn kind(&self) -> &'static str;
+    /// A pattern like `image-page/{channel}/{message}/{dir}` matching the IDs this
+    /// [`Component`] handles without them being inserted. By default there's none.
+    fn route(&self) -> Option<&'static str> { None }
     /// Runs whenever Discord sends a component interaction with the ID matching that
-    /// registered for this [`Component`] on insertion.
-    async fn run(&self, interaction: ComponentInteraction, args: CommandArguments) -> anyhow::Result<()>;
+    /// registered for this [`Component`] on insertion, or matching its
+    /// [`Component::route`]. `params` are the parameters of the route, it's empty for
+    /// inserted IDs. `selection` is what was picked in a select menu.
+    async fn run(
+        &self,
+        interaction: ComponentInteraction,
+        args: CommandArguments,
+        params: RouteParams,
+        selection: Selection,
+    ) -> anyhow::Result<()>;
+    /// Runs when a registration is loaded from the database after a restart, before it's
+    /// ran or cleaned up. `state` is the [`Registration`]'s state. Returns the component
+    /// to register again, `None` registers this one. By default it's a no-op.
+    #[allow(unused_variables)]
+    async fn restore(&self, id: String, state: Vec<u8>) -> anyhow::Result<Option<SharedComponent>> {
+        Ok(None)
+    }
+    /// The state this [`Component`] owns, see [`ComponentMap::state`]. By default there's
+    /// none.
+    fn state(&self) -> Option<Arc<dyn Any + Send + Sync>> { None }
+    /// Who may use this [`Component`] unless its [`Registration`] says otherwise. By
+    /// default it's anyone.
+    fn authorization(&self) -> Authorization { Authorization::Anyone }
     /// Runs whenever the the [`Component`] is removed from the map after its timeout or
     /// after evoking [`ComponentMap::timeout`] with the ID matching that registered for
     /// this [`Component`] on insertion. By default it's a no-op.
@@ -22,21 +95,448 @@ pub trait Component {
     async fn cleanup(&self, id: String, http: Arc<Http>, cache: Arc<Cache>) -> anyhow::Result<()> { Ok(()) }
 }
 
+/// Lets the `&'static` components without state be inserted like owned ones.
+#[async_trait]
+impl<T: Component + Send + Sync + ?Sized> Component for &'static T {
+    fn kind(&self) -> &'static str { (**self).kind() }
+
+    fn route(&self) -> Option<&'static str> { (**self).route() }
+
+    async fn run(
+        &self,
+        interaction: ComponentInteraction,
+        args: CommandArguments,
+        params: RouteParams,
+        selection: Selection,
+    ) -> anyhow::Result<()> {
+        (**self).run(interaction, args, params, selection).await
+    }
+
+    async fn restore(&self, id: String, state: Vec<u8>) -> anyhow::Result<Option<SharedComponent>> {
+        (**self).restore(id, state).await
+    }
+
+    fn state(&self) -> Option<Arc<dyn Any + Send + Sync>> { (**self).state() }
+
+    fn authorization(&self) -> Authorization { (**self).authorization() }
+
+    async fn cleanup(&self, id: String, http: Arc<Http>, cache: Arc<Cache>) -> anyhow::Result<()> {
+        (**self).cleanup(id, http, cache).await
+    }
+}
+
+/// Who may use a component. Anyone else is told it isn't theirs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Authorization {
+    #[default]
+    Anyone,
+    /// Only the user that ran the command the component's message responds to.
+    Invoker,
+    User(UserId),
+    /// Only members with the role.
+    Role(RoleId),
+    /// Only members with every one of the permissions.
+    Permissions(Permissions),
+}
+
+/// Who used a component, as far as an [`Authorization`] cares.
+struct Actor<'a> {
+    user:        UserId,
+    /// Who ran the command the component's message responds to.
+    invoker:     Option<UserId>,
+    roles:       &'a [RoleId],
+    permissions: Option<Permissions>,
+}
+
+impl<'a> From<&'a ComponentInteraction> for Actor<'a> {
+    fn from(interaction: &'a ComponentInteraction) -> Self {
+        let member = interaction.member.as_ref();
+        Self {
+            user:        interaction.user.id,
+            invoker:     interaction.message.interaction.as_ref().map(|x| x.user.id),
+            roles:       member.map_or(&[], |x| &x.roles),
+            permissions: member.and_then(|x| x.permissions),
+        }
+    }
+}
+
+impl Authorization {
+    fn allows(&self, actor: &Actor) -> bool {
+        match *self {
+            Self::Anyone => true,
+            Self::Invoker => actor.invoker == Some(actor.user),
+            Self::User(id) => actor.user == id,
+            Self::Role(id) => actor.roles.contains(&id),
+            Self::Permissions(required) => {
+                actor
+                    .permissions
+                    .is_some_and(|x| x.administrator() || x.contains(required))
+            }
+        }
+    }
+
+    /// What whoever isn't allowed is told.
+    fn denial(&self) -> String {
+        match self {
+            Self::Anyone | Self::Invoker | Self::User(_) => String::from("This isn't yours."),
+            Self::Role(id) => format!("This isn't yours, it's only for <@&{id}>."),
+            Self::Permissions(required) => format!("This isn't yours, it needs the {required} permissions."),
+        }
+    }
+}
+
+/// Tell whoever used a component that's no longer listened for that it has expired.
+pub async fn expired(interaction: &ComponentInteraction, http: &Http) -> anyhow::Result<()> {
+    let message = CreateInteractionResponseMessage::new()
+        .content("This has expired.")
+        .ephemeral(true);
+    interaction
+        .create_response(http, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+#[async_trait]
+pub trait Modal {
+    /// The pattern matching the custom ID of the modals this handles, see
+    /// [`Component::route`].
+    fn route(&self) -> &'static str;
+    /// Runs whenever a modal with an ID matching the [`Modal::route`] is submitted.
+    async fn run(
+        &self,
+        interaction: ModalInteraction,
+        args: CommandArguments,
+        fields: ModalFields,
+        params: RouteParams,
+    ) -> anyhow::Result<()>;
+}
+
+/// The values submitted in a modal's text inputs, by their custom ID.
+#[derive(Debug, Clone, Default)]
+pub struct ModalFields(HashMap<String, String>);
+
+impl ModalFields {
+    pub fn new(rows: &[ActionRow]) -> Self {
+        let fields = rows
+            .iter()
+            .flat_map(|x| &x.components)
+            .filter_map(|x| {
+                let ActionRowComponent::InputText(input) = x else {
+                    return None;
+                };
+                let value: Option<String> = input.value.clone().into();
+                Some((input.custom_id.clone(), value?))
+            })
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+        Self(fields)
+    }
+
+    /// Parse the field called `name`. It's an error if it was left empty.
+    pub fn get<T>(&self, name: &str) -> anyhow::Result<T>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.optional(name)?
+            .context(format!("The field '{name}' is required"))
+    }
+
+    /// Parse the field called `name` if it was filled in.
+    pub fn optional<T>(&self, name: &str) -> anyhow::Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.0
+            .get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid value for the field '{name}': {e}"))
+            })
+            .transpose()
+    }
+}
+
+/// The values picked in a select menu. The users, roles, mentionables and channels picked
+/// in the other kinds of select menus are picked as their IDs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Selection {
+    /// The component isn't a select menu.
+    #[default]
+    None,
+    Values(Vec<String>),
+}
+
+impl Selection {
+    pub fn new(kind: &ComponentInteractionDataKind) -> Self {
+        fn ids<T: std::fmt::Display>(ids: &[T]) -> Selection {
+            Selection::Values(ids.iter().map(ToString::to_string).collect())
+        }
+
+        match kind {
+            ComponentInteractionDataKind::StringSelect { values } => Self::Values(values.clone()),
+            ComponentInteractionDataKind::UserSelect { values } => ids(values),
+            ComponentInteractionDataKind::RoleSelect { values } => ids(values),
+            ComponentInteractionDataKind::MentionableSelect { values } => ids(values),
+            ComponentInteractionDataKind::ChannelSelect { values } => ids(values),
+            _ => Self::None,
+        }
+    }
+
+    /// Parse every value picked.
+    pub fn values<T>(&self) -> anyhow::Result<Vec<T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let Self::Values(values) = self else {
+            anyhow::bail!("Expected a selection, the component isn't a select menu");
+        };
+        values
+            .iter()
+            .map(|x| {
+                x.parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid selection '{x}': {e}"))
+            })
+            .collect()
+    }
+
+    /// Parse the only value picked.
+    pub fn one<T>(&self) -> anyhow::Result<T>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let mut values = self.values()?;
+        if values.len() != 1 {
+            anyhow::bail!("Expected one selected value, got {}", values.len());
+        }
+        Ok(values.remove(0))
+    }
+}
+
+/// A component ID pattern. Segments are separated by `/` and a segment in braces, like
+/// `{message}`, is a parameter matching any non-empty segment.
+#[derive(Debug, Clone)]
+struct Route {
+    pattern:  &'static str,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment {
+    Literal(&'static str),
+    Parameter(&'static str),
+}
+
+impl Route {
+    fn new(pattern: &'static str) -> Self {
+        let segments = pattern
+            .split('/')
+            .map(|x| {
+                match x.strip_prefix('{').and_then(|x| x.strip_suffix('}')) {
+                    Some(name) => Segment::Parameter(name),
+                    None => Segment::Literal(x),
+                }
+            })
+            .collect();
+        Self { pattern, segments }
+    }
+
+    /// The parameters in `id` if it matches the route.
+    fn matches(&self, id: &str) -> Option<RouteParams> {
+        let mut params = HashMap::new();
+        let mut parts = id.split('/');
+        for segment in &self.segments {
+            let part = parts.next()?;
+            match *segment {
+                Segment::Literal(x) if x == part => {}
+                Segment::Parameter(name) if !part.is_empty() => {
+                    params.insert(name, part.to_string());
+                }
+                _ => return None,
+            }
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(RouteParams {
+            route: Some(self.pattern),
+            params,
+        })
+    }
+}
+
+/// The parameters parsed from a component ID by its [`Component::route`].
+#[derive(Debug, Clone, Default)]
+pub struct RouteParams {
+    route:  Option<&'static str>,
+    params: HashMap<&'static str, String>,
+}
+
+impl RouteParams {
+    /// Parse the parameter called `name`.
+    pub fn get<T>(&self, name: &str) -> anyhow::Result<T>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let route = self.route.unwrap_or_default();
+        let value = self
+            .params
+            .get(name)
+            .context(format!("No parameter '{name}' in route '{route}'"))?;
+        value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid parameter '{name}' ('{value}') in route '{route}': {e}"))
+    }
+}
+
+/// Where a component was sent and what it needs to pick up where it left off after a
+/// restart.
+#[derive(Debug, Clone)]
+pub struct Registration {
+    guild_id:      Option<GuildId>,
+    channel_id:    ChannelId,
+    state:         Vec<u8>,
+    authorization: Option<Authorization>,
+    /// Whether the component keeps working until it's timed out by hand.
+    permanent:     bool,
+}
+
+impl Registration {
+    pub fn new(guild_id: Option<GuildId>, channel_id: ChannelId) -> Self {
+        Self {
+            guild_id,
+            channel_id,
+            state: Vec::new(),
+            authorization: None,
+            permanent: false,
+        }
+    }
+
+    /// Never expire the component, it's listened for until [`ComponentMap::timeout`] is
+    /// called for it.
+    pub fn permanent(mut self) -> Self {
+        self.permanent = true;
+        self
+    }
+
+    /// Only let who `authorization` allows use the component, instead of who its
+    /// [`Component::authorization`] does.
+    pub fn authorize(mut self, authorization: Authorization) -> Self {
+        self.authorization = Some(authorization);
+        self
+    }
+
+    /// Persist `state` with the registration, it's handed to [`Component::restore`] after
+    /// a restart.
+    pub fn with_state(mut self, state: &impl Serialize) -> bincode::Result<Self> {
+        self.state = bincode::serialize(state)?;
+        Ok(self)
+    }
+}
+
+/// A registered component and who may use it until when, if it expires.
+struct Listener {
+    f:             SharedComponent,
+    guild_id:      Option<GuildId>,
+    authorization: Authorization,
+    expiry:        Option<DateTime<Utc>>,
+}
+
 struct ComponentInner {
-    component_map: RwLock<HashMap<String, (DynComponent, DateTime<Utc>)>>,
+    component_map: RwLock<HashMap<String, Listener>>,
+    /// Where registrations are persisted, nothing is persisted without one.
+    database:      Option<Database>,
 }
 
 impl ComponentInner {
     #[inline]
-    fn new() -> Self {
+    fn new(database: Option<Database>) -> Self {
         Self {
             component_map: RwLock::new(HashMap::new()),
+            database,
+        }
+    }
+
+    async fn insert(
+        &self,
+        id: String,
+        f: SharedComponent,
+        timeout_duration: Option<Duration>,
+        registration: Registration,
+    ) {
+        let when =
+            (!registration.permanent).then(|| Utc::now() + timeout_duration.unwrap_or(Duration::minutes(5)));
+        let kind = f.kind();
+        let authorization = registration.authorization.unwrap_or_else(|| f.authorization());
+        let listener = Listener {
+            f,
+            guild_id: registration.guild_id,
+            authorization,
+            expiry: when,
+        };
+        let _ = self.component_map.write().await.insert(id.clone(), listener);
+
+        if let Err(e) = self.persist(&id, kind, when, authorization, registration).await {
+            tracing::error!("Couldn't persist component '{id}': {e}");
         }
     }
 
-    async fn insert(&self, id: String, f: DynComponent, timeout_duration: Option<Duration>) {
-        let when = Utc::now() + timeout_duration.unwrap_or(Duration::minutes(5));
-        let _ = self.component_map.write().await.insert(id.clone(), (f, when));
+    async fn persist(
+        &self,
+        id: &str,
+        kind: &str,
+        when: Option<DateTime<Utc>>,
+        authorization: Authorization,
+        registration: Registration,
+    ) -> crate::Result<()> {
+        let Some(database) = &self.database else {
+            return Ok(());
+        };
+        database
+            .save_component(StoredComponent {
+                id:            id.to_string(),
+                guild_id:      registration.guild_id,
+                channel_id:    registration.channel_id,
+                kind:          kind.to_string(),
+                state:         registration.state,
+                authorization: serde_json::to_string(&authorization).ok(),
+                expiry:        when,
+            })
+            .await
+    }
+
+    async fn save_state(&self, id: &str, state: &impl Serialize) -> crate::Result<()> {
+        let Some(database) = &self.database else {
+            return Ok(());
+        };
+        let state = bincode::serialize(state).map_err(|_| crate::Error::InternalLogic)?;
+        database.save_component_state(id, state).await
+    }
+
+    async fn forget(&self, id: &str) -> crate::Result<()> {
+        let Some(database) = &self.database else {
+            return Ok(());
+        };
+        database.forget_component(id).await
+    }
+
+    /// The component registered for `id`, or for the closest ID it's under, like
+    /// `paginator/1` for `paginator/1/next`, and who may use it.
+    async fn registered(&self, id: &str) -> Option<(SharedComponent, Authorization)> {
+        let component_map = self.component_map.read().await;
+        let mut id = id;
+        loop {
+            if let Some(listener) = component_map.get(id) {
+                return Some((listener.f.clone(), listener.authorization));
+            }
+            id = id.rsplit_once('/')?.0;
+        }
     }
 
     // Returns `None` if there's nothing ran
@@ -46,9 +546,55 @@ impl ComponentInner {
         interaction: ComponentInteraction,
         args: CommandArguments,
     ) -> Option<anyhow::Result<()>> {
-        let lock = self.component_map.read().await;
-        let (f, _) = lock.get(id)?;
-        Some(f.run(interaction, args).await)
+        // The handler is cloned out so the lock isn't held while it runs.
+        let (f, authorization, params): (SharedComponent, _, _) = match self.registered(id).await {
+            Some((f, authorization)) => {
+                let route = f.route().map(Route::new);
+                let params = route.and_then(|x| x.matches(id)).unwrap_or_default();
+                (f, authorization, params)
+            }
+            None => {
+                ROUTES.iter().find_map(|(route, f)| {
+                    Some((
+                        Arc::new(*f) as SharedComponent,
+                        f.authorization(),
+                        route.matches(id)?,
+                    ))
+                })?
+            }
+        };
+
+        if !authorization.allows(&Actor::from(&interaction)) {
+            let message = CreateInteractionResponseMessage::new()
+                .content(authorization.denial())
+                .ephemeral(true);
+            let response = interaction
+                .create_response(&args.http, CreateInteractionResponse::Message(message))
+                .await;
+            return Some(response.map_err(Into::into));
+        }
+
+        let selection = Selection::new(&interaction.data.kind);
+        let http = args.http.clone();
+        let result = incident::catch_panic(format!("Component '{id}'"), {
+            let interaction = interaction.clone();
+            async move { f.run(interaction, args, params, selection).await }
+        })
+        .await;
+
+        Some(match result {
+            Ok(result) => result,
+            Err(incident) => {
+                let message = CreateInteractionResponseMessage::new()
+                    .content(crate::Error::Incident(incident.id.clone()).to_string())
+                    .ephemeral(true);
+                // The handler may have already responded before panicking.
+                let _ = interaction
+                    .create_response(&http, CreateInteractionResponse::Message(message))
+                    .await;
+                Err(anyhow::anyhow!("Handler panicked (incident {})", incident.id))
+            }
+        })
     }
 }
 
@@ -58,21 +604,104 @@ pub struct ComponentMap {
 }
 
 impl ComponentMap {
+    /// Create a map that persists registrations to `database`, if there is one.
     #[inline]
-    pub(super) fn new() -> Self {
+    pub(super) fn new(database: Option<Database>) -> Self {
         Self {
-            inner: Arc::new(ComponentInner::new()),
+            inner: Arc::new(ComponentInner::new(database)),
         }
     }
 
+    /// Load the registrations persisted before a restart. Expired ones are picked up by
+    /// [`Self::timeout_watcher`] and cleaned up like any other.
+    pub(super) async fn restore(&self) -> anyhow::Result<()> {
+        let Some(database) = &self.inner.database else {
+            return Ok(());
+        };
+        let restored = self.load(database.components().await?).await?;
+
+        tracing::info!("Restored {restored} component listeners");
+        Ok(())
+    }
+
+    /// Listen for the registrations of `guild_id` again after they were replaced in the
+    /// database, like by an import. The guild's listeners that aren't registered anymore
+    /// are dropped.
+    pub async fn reload(&self, guild_id: GuildId) -> anyhow::Result<()> {
+        let Some(database) = &self.inner.database else {
+            return Ok(());
+        };
+        let rows = database
+            .components()
+            .await?
+            .into_iter()
+            .filter(|x| x.guild_id == Some(guild_id))
+            .collect();
+        self.inner
+            .component_map
+            .write()
+            .await
+            .retain(|_, x| x.guild_id != Some(guild_id));
+        let loaded = self.load(rows).await?;
+
+        tracing::info!("Reloaded {loaded} component listeners of guild {guild_id}");
+        Ok(())
+    }
+
+    /// Listen for the persisted `rows` that aren't listened for yet, returning how many
+    /// there were.
+    async fn load(&self, rows: Vec<StoredComponent>) -> anyhow::Result<usize> {
+        let mut loaded = 0usize;
+        for row in rows {
+            let id = row.id;
+            if self.inner.component_map.read().await.contains_key(&id) {
+                continue;
+            }
+
+            let Some(kind) = COMPONENTS.get(row.kind.as_str()).copied() else {
+                tracing::warn!("Forgetting component '{id}' of unknown kind '{}'", row.kind);
+                self.inner.forget(&id).await?;
+                continue;
+            };
+            let f: SharedComponent = match kind.restore(id.clone(), row.state).await {
+                Ok(f) => f.unwrap_or_else(|| Arc::new(kind) as SharedComponent),
+                Err(e) => {
+                    tracing::error!("Couldn't restore component '{id}': {e}");
+                    self.inner.forget(&id).await?;
+                    continue;
+                }
+            };
+
+            let authorization = match row.authorization.as_deref().map(serde_json::from_str) {
+                None => f.authorization(),
+                Some(Ok(authorization)) => authorization,
+                // Better to stop listening than to let anyone use it.
+                Some(Err(e)) => {
+                    tracing::error!("Forgetting component '{id}' with an invalid authorization: {e}");
+                    self.inner.forget(&id).await?;
+                    continue;
+                }
+            };
+            let listener = Listener {
+                f,
+                guild_id: row.guild_id,
+                authorization,
+                expiry: row.expiry,
+            };
+            self.inner.component_map.write().await.insert(id, listener);
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+
     #[inline]
     // Timeout an id before it's scheduled time. Returns wether it worked
     pub(super) async fn timeout(&self, id: String) -> anyhow::Result<()> {
         let mut component_map = self.inner.component_map.write().await;
-        let (_, timeout) = component_map
+        let listener = component_map
             .get_mut(&id)
             .context(format!("'{id}' wasn't found in the component map"))?;
-        *timeout = Utc::now();
+        listener.expiry = Some(Utc::now());
         Ok(())
     }
 
@@ -83,15 +712,28 @@ impl ComponentMap {
             let kill_list = {
                 let map = self.inner.component_map.read().await;
                 map.iter()
-                    .filter(|(_, (_, time))| *time <= now)
+                    .filter(|(_, listener)| listener.expiry.is_some_and(|x| x <= now))
                     .map(|(id, _)| id.clone()) // So the lock gets dropped when this is done collecting
                     .collect::<Vec<_>>()
             };
 
             for id in kill_list {
-                if let Some((f, _)) = self.inner.component_map.write().await.remove(&id) {
-                    tracing::debug!("Removed component listener: {id}");
-                    f.cleanup(id.clone(), http.clone(), cache.clone()).await?;
+                let Some(Listener { f, .. }) = self.inner.component_map.write().await.remove(&id) else {
+                    continue;
+                };
+                tracing::debug!("Removed component listener: {id}");
+
+                // A failing cleanup shouldn't stop every other component from timing out.
+                let cleanup = incident::catch_panic(format!("Cleanup of component '{id}'"), {
+                    let (id, http, cache) = (id.clone(), http.clone(), cache.clone());
+                    async move { f.cleanup(id, http, cache).await }
+                })
+                .await;
+                if let Ok(Err(e)) = cleanup {
+                    tracing::error!("Error cleaning up component '{id}': {e}");
+                }
+                if let Err(e) = self.inner.forget(&id).await {
+                    tracing::error!("Couldn't forget component '{id}': {e}");
                 }
             }
 
@@ -99,9 +741,34 @@ impl ComponentMap {
         }
     }
 
+    /// Listen for component interactions with `id`, or any ID under it like `id/next`,
+    /// until `timeout_duration` (5 minutes by default) passes, or until it's timed out
+    /// when the registration is [`Registration::permanent`]. The registration is
+    /// persisted so it survives a restart. Whatever state `f` owns is dropped once it's
+    /// cleaned up.
     #[inline]
-    pub async fn insert(&self, id: String, f: DynComponent, timeout_duration: Option<Duration>) {
-        self.inner.insert(id, f, timeout_duration).await;
+    pub async fn insert(
+        &self,
+        id: String,
+        f: SharedComponent,
+        timeout_duration: Option<Duration>,
+        registration: Registration,
+    ) {
+        self.inner.insert(id, f, timeout_duration, registration).await;
+    }
+
+    /// Replace the state persisted with the registration of `id`, for components whose
+    /// state changes after they're inserted.
+    pub async fn save_state(&self, id: &str, state: &impl Serialize) {
+        if let Err(e) = self.inner.save_state(id, state).await {
+            tracing::error!("Couldn't persist the state of component '{id}': {e}");
+        }
+    }
+
+    /// The state of the component registered for `id`, if it owns a `T`.
+    pub async fn state<T: Any + Send + Sync>(&self, id: &str) -> Option<Arc<T>> {
+        let (f, _) = self.inner.registered(id).await?;
+        f.state()?.downcast().ok()
     }
 
     #[inline]
@@ -114,4 +781,263 @@ impl ComponentMap {
     ) -> Option<anyhow::Result<()>> {
         self.inner.run(id, interaction, args).await
     }
+
+    // Returns `None` if there's no modal matching `id`
+    pub async fn run_modal(
+        &self,
+        id: &str,
+        interaction: ModalInteraction,
+        args: CommandArguments,
+    ) -> Option<anyhow::Result<()>> {
+        let (f, params) = MODALS
+            .iter()
+            .find_map(|(route, f)| Some((*f, route.matches(id)?)))?;
+        let fields = ModalFields::new(&interaction.data.components);
+
+        let http = args.http.clone();
+        let result = incident::catch_panic(format!("Modal '{id}'"), {
+            let interaction = interaction.clone();
+            async move { f.run(interaction, args, fields, params).await }
+        })
+        .await;
+
+        Some(match result {
+            Ok(result) => result,
+            Err(incident) => {
+                let message = CreateInteractionResponseMessage::new()
+                    .content(crate::Error::Incident(incident.id.clone()).to_string())
+                    .ephemeral(true);
+                let _ = interaction
+                    .create_response(&http, CreateInteractionResponse::Message(message))
+                    .await;
+                Err(anyhow::anyhow!("Handler panicked (incident {})", incident.id))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use component_macro::component;
+
+    use super::*;
+
+    #[test]
+    fn test_component_kinds_are_unique() {
+        let registered = crate::commands::COMPONENTS.concat().len();
+        #[cfg(feature = "ai")]
+        let registered = registered + crate::llm::COMPONENTS.len();
+
+        // Kinds are how handlers are found after a restart, they can't be shared.
+        assert_eq!(COMPONENTS.len(), registered);
+    }
+
+    #[test]
+    fn test_route_matches() {
+        let route = Route::new("image-page/{channel}/{message}/{dir}");
+
+        let params = route.matches("image-page/1/2/next").unwrap();
+        assert_eq!(params.get::<u64>("channel").unwrap(), 1);
+        assert_eq!(params.get::<u64>("message").unwrap(), 2);
+        assert_eq!(params.get::<String>("dir").unwrap(), "next");
+        assert!(params.get::<u64>("dir").is_err());
+        assert!(params.get::<u64>("user").is_err());
+
+        assert!(route.matches("image-page/1/2").is_none());
+        assert!(route.matches("image-page/1/2/next/3").is_none());
+        assert!(route.matches("image-page/1//next").is_none());
+        assert!(route.matches("role-panel/1/2/next").is_none());
+    }
+
+    #[test]
+    fn test_routes_are_unique() {
+        // Parameter names don't matter when matching.
+        let shape = |route: &Route| {
+            route
+                .segments
+                .iter()
+                .map(|x| {
+                    match x {
+                        Segment::Literal(x) => Some(*x),
+                        Segment::Parameter(_) => None,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (i, (route, _)) in ROUTES.iter().enumerate() {
+            for (other, _) in &ROUTES[i + 1..] {
+                assert_ne!(
+                    shape(route),
+                    shape(other),
+                    "'{}' overlaps '{}'",
+                    route.pattern,
+                    other.pattern
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_modal_fields() {
+        let rows: Vec<ActionRow> = serde_json::from_value(serde_json::json!([
+            {"type": 1, "components": [{"type": 4, "custom_id": "title", "value": "Hello"}]},
+            {"type": 1, "components": [{"type": 4, "custom_id": "count", "value": "3"}]},
+            {"type": 1, "components": [{"type": 4, "custom_id": "color", "value": ""}]},
+        ]))
+        .unwrap();
+        let fields = ModalFields::new(&rows);
+
+        assert_eq!(fields.get::<String>("title").unwrap(), "Hello");
+        assert_eq!(fields.get::<u8>("count").unwrap(), 3);
+        assert!(fields.get::<u8>("title").is_err());
+        // Empty inputs weren't filled in
+        assert_eq!(fields.optional::<String>("color").unwrap(), None);
+        assert!(fields.get::<String>("color").is_err());
+    }
+
+    #[test]
+    fn test_selection() {
+        let kind = ComponentInteractionDataKind::StringSelect {
+            values: vec![String::from("7")],
+        };
+        let selection = Selection::new(&kind);
+        assert_eq!(selection.one::<u64>().unwrap(), 7);
+
+        // Roles are picked as their IDs.
+        let kind = ComponentInteractionDataKind::RoleSelect {
+            values: vec![RoleId::new(1), RoleId::new(2)],
+        };
+        let selection = Selection::new(&kind);
+        assert_eq!(selection.values::<u64>().unwrap(), [1, 2]);
+        assert!(selection.one::<u64>().is_err());
+
+        assert_eq!(
+            Selection::new(&ComponentInteractionDataKind::Button),
+            Selection::None
+        );
+    }
+
+    #[component(state = String)]
+    async fn stateful(
+        _state: &String,
+        _interaction: ComponentInteraction,
+        _args: CommandArguments,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stateful_component() {
+        let map = ComponentMap::new(None);
+        let registration = Registration::new(None, ChannelId::new(1));
+        map.insert(
+            String::from("stateful/1"),
+            stateful.with_state(String::from("state")),
+            None,
+            registration,
+        )
+        .await;
+
+        assert_eq!(*map.state::<String>("stateful/1").await.unwrap(), "state");
+        // IDs under the registered one are handled by it
+        assert!(map.state::<String>("stateful/1/next").await.is_some());
+        assert!(map.state::<String>("stateful/2/next").await.is_none());
+        assert!(map.state::<u64>("stateful/1").await.is_none());
+        // Without state it's only the kind
+        assert!(stateful.state().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_permanent_component() {
+        let map = ComponentMap::new(None);
+        let registration = Registration::new(None, ChannelId::new(1));
+        map.insert(
+            String::from("stateful/1"),
+            Arc::new(stateful),
+            None,
+            registration.clone(),
+        )
+        .await;
+        map.insert(
+            String::from("stateful/2"),
+            Arc::new(stateful),
+            None,
+            registration.permanent(),
+        )
+        .await;
+
+        let component_map = map.inner.component_map.read().await;
+        assert!(component_map["stateful/1"].expiry.is_some());
+        assert!(component_map["stateful/2"].expiry.is_none());
+        drop(component_map);
+
+        map.timeout(String::from("stateful/2")).await.unwrap();
+        assert!(map.inner.component_map.read().await["stateful/2"]
+            .expiry
+            .is_some());
+    }
+
+    #[test]
+    fn test_authorization() {
+        let roles = [RoleId::new(3)];
+        let actor = Actor {
+            user:        UserId::new(1),
+            invoker:     Some(UserId::new(2)),
+            roles:       &roles,
+            permissions: Some(Permissions::SEND_MESSAGES | Permissions::MANAGE_MESSAGES),
+        };
+
+        assert!(Authorization::Anyone.allows(&actor));
+        assert!(!Authorization::Invoker.allows(&actor));
+        assert!(Authorization::User(UserId::new(1)).allows(&actor));
+        assert!(!Authorization::User(UserId::new(2)).allows(&actor));
+        assert!(Authorization::Role(RoleId::new(3)).allows(&actor));
+        assert!(!Authorization::Role(RoleId::new(4)).allows(&actor));
+        assert!(Authorization::Permissions(Permissions::MANAGE_MESSAGES).allows(&actor));
+        assert!(
+            !Authorization::Permissions(Permissions::MANAGE_MESSAGES | Permissions::BAN_MEMBERS)
+                .allows(&actor)
+        );
+
+        let invoker = Actor {
+            user: UserId::new(2),
+            ..actor
+        };
+        assert!(Authorization::Invoker.allows(&invoker));
+        // Outside of guilds there are no roles or permissions to check
+        let dm = Actor {
+            roles: &[],
+            permissions: None,
+            ..invoker
+        };
+        assert!(!Authorization::Role(RoleId::new(3)).allows(&dm));
+        assert!(!Authorization::Permissions(Permissions::SEND_MESSAGES).allows(&dm));
+    }
+
+    #[test]
+    fn test_authorization_round_trip() {
+        // It's persisted as JSON with the registration
+        for authorization in [
+            Authorization::Anyone,
+            Authorization::User(UserId::new(1)),
+            Authorization::Permissions(Permissions::BAN_MEMBERS),
+        ] {
+            let json = serde_json::to_string(&authorization).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Authorization>(&json).unwrap(),
+                authorization
+            );
+        }
+    }
+
+    #[test]
+    fn test_registration_state() {
+        let registration = Registration::new(None, ChannelId::new(1))
+            .with_state(&(String::from("state"), 2u64))
+            .unwrap();
+        let state: (String, u64) = bincode::deserialize(&registration.state).unwrap();
+
+        assert_eq!(state, (String::from("state"), 2));
+    }
 }
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+/// Base URLs of the web APIs Tara talks to. These only need to be changed to point Tara
+/// at a mirror or a proxy, every one of them defaults to the public API.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Endpoints {
+    /// OMDb, used for `/movie` and `/series`.
+    pub omdb:         String,
+    /// Wikipedia, used for `/wikipedia`.
+    pub wikipedia:    String,
+    /// Free Dictionary API, used for `/define`.
+    pub dictionary:   String,
+    /// Unsplash's API, used for image search and random images.
+    pub unsplash:     String,
+    /// `currencyapi.com`, used for currency conversions.
+    pub currency:     String,
+    /// DuckDuckGo's HTML frontend, used for `/search duckduckgo`.
+    pub duckduckgo:   String,
+    /// Quotable, used for random quotes.
+    pub quotable:     String,
+    /// EmojiHub, used for random emojis.
+    pub emojihub:     String,
+    /// The Cat API, used for random cat pictures.
+    pub thecatapi:    String,
+    /// Dog CEO, used for random dog pictures.
+    pub dog_ceo:      String,
+    /// Useless Facts, used for random facts.
+    pub uselessfacts: String,
+}
+
+impl Endpoints {
+    /// The name and URL of every endpoint.
+    pub fn urls(&self) -> [(&'static str, &str); 11] {
+        [
+            ("omdb", &self.omdb),
+            ("wikipedia", &self.wikipedia),
+            ("dictionary", &self.dictionary),
+            ("unsplash", &self.unsplash),
+            ("currency", &self.currency),
+            ("duckduckgo", &self.duckduckgo),
+            ("quotable", &self.quotable),
+            ("emojihub", &self.emojihub),
+            ("thecatapi", &self.thecatapi),
+            ("dog_ceo", &self.dog_ceo),
+            ("uselessfacts", &self.uselessfacts),
+        ]
+    }
+
+    /// Every endpoint pointed at the same `base` URL.
+    #[cfg(test)]
+    pub fn all(base: &str) -> Self {
+        let base = base.trim_end_matches('/').to_string();
+        Self {
+            omdb:         base.clone(),
+            wikipedia:    base.clone(),
+            dictionary:   base.clone(),
+            unsplash:     base.clone(),
+            currency:     base.clone(),
+            duckduckgo:   base.clone(),
+            quotable:     base.clone(),
+            emojihub:     base.clone(),
+            thecatapi:    base.clone(),
+            dog_ceo:      base.clone(),
+            uselessfacts: base,
+        }
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            omdb:         String::from("http://www.omdbapi.com"),
+            wikipedia:    String::from("https://en.wikipedia.org"),
+            dictionary:   String::from("https://api.dictionaryapi.dev"),
+            unsplash:     String::from("https://api.unsplash.com"),
+            currency:     String::from("https://api.currencyapi.com"),
+            duckduckgo:   String::from("https://duckduckgo.com"),
+            quotable:     String::from("https://api.quotable.io"),
+            emojihub:     String::from("https://emojihub.yurace.pro"),
+            thecatapi:    String::from("https://api.thecatapi.com"),
+            dog_ceo:      String::from("https://dog.ceo"),
+            uselessfacts: String::from("https://uselessfacts.jsph.pl"),
+        }
+    }
+}
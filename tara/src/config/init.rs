@@ -0,0 +1,481 @@
+//! `tara init`, which asks for everything Tara needs, tries it out, and writes a
+//! commented configuration file.
+use std::{
+    fmt::Write as _,
+    future::Future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use rustyline::DefaultEditor;
+use serde::Serialize;
+use serenity::http::Http;
+use tara_util::{paths, presence::Presence};
+use tokio::{fs, io::AsyncWriteExt};
+
+use super::{
+    ai::{Ai, Llm},
+    check,
+    endpoints::Endpoints,
+    music::Music,
+    Configuration, ConfigurationRandomErrorMessages, ConfigurationSecrets,
+};
+use crate::{
+    database::{self, Backend},
+    Error, Result,
+};
+
+/// Ask for a configuration and write it to `path`, or the default location.
+pub async fn run(path: Option<&Path>) -> anyhow::Result<()> {
+    let path = path
+        .map(Path::to_path_buf)
+        .or_else(|| paths::NEW_CONFIGURATION_FILE.clone())
+        .context("There's nowhere to write the configuration, use `--config` to pick a file")?;
+    let mut prompt = Prompt::new()?;
+
+    println!("This writes a configuration to \"{}\".", path.display());
+    println!("Leave optional answers empty to skip them.\n");
+    if path.exists() && !prompt.confirm("There's already a configuration there, replace it?", false)? {
+        return Ok(());
+    }
+
+    let endpoints = Endpoints::default();
+    let token = prompt.tested("Discord bot token", false, test_token).await?;
+    let database = prompt
+        .tested(
+            "Database URL (optional, postgres:// or sqlite://, SQLite in the data directory otherwise)",
+            true,
+            test_database,
+        )
+        .await?;
+    let currency_api_key = prompt
+        .tested("currencyapi.com API key (optional)", true, |x| {
+            test_key(format!("{}/v3/status?apikey={x}", endpoints.currency))
+        })
+        .await?;
+    let unsplash_key = prompt
+        .tested("Unsplash access key (optional)", true, |x| {
+            test_key(format!("{}/photos/random?client_id={x}", endpoints.unsplash))
+        })
+        .await?;
+    let omdb_api_key = prompt
+        .tested(
+            "OMDb API key (optional, built in ones are used otherwise)",
+            true,
+            |x| test_key(format!("{}/?i=tt0111161&apikey={x}", endpoints.omdb)),
+        )
+        .await?;
+
+    if !cfg!(feature = "music") {
+        println!("This build of Tara doesn't have music, the setting is only used by builds that do.");
+    }
+    let music = Music {
+        enabled: prompt.confirm("Enable music?", true)?,
+    };
+
+    let mut ai = None;
+    if prompt.confirm("Set up the LLM?", false)? {
+        if !cfg!(feature = "ai") {
+            println!(
+                "This build of Tara doesn't have the LLM, the settings are only used by builds that do."
+            );
+        }
+        let model = loop {
+            let model = PathBuf::from(prompt.line("Path of the GGML model")?);
+            if model.is_file() {
+                break model;
+            }
+            println!("  There's no file at \"{}\".", model.display());
+        };
+        let use_gpu = prompt.confirm("Use the GPU?", false)?;
+        let thread_count = loop {
+            match prompt.optional("Threads to use (optional, the physical cores otherwise)")? {
+                None => break None,
+                Some(x) => {
+                    match x.parse() {
+                        Ok(x) => break Some(x),
+                        Err(_) => println!("  That isn't a number."),
+                    }
+                }
+            }
+        };
+        ai = Some(Ai {
+            llm: Some(Llm {
+                model,
+                context_token_length: None,
+                architecture: None,
+                prefer_mmap: None,
+                use_gpu: Some(use_gpu),
+                gpu_layers: None,
+                thread_count,
+                batch_size: None,
+            }),
+        });
+    }
+
+    let config = Configuration {
+        secrets: ConfigurationSecrets {
+            token,
+            database,
+            currency_api_key,
+            omdb_api_key,
+            unsplash_key,
+            ..Default::default()
+        },
+        random_error_message: ConfigurationRandomErrorMessages::Boolean(false),
+        music: Some(music),
+        ai,
+        endpoints,
+        presence: Presence::default(),
+    };
+    write(&path, &config).await?;
+    println!(
+        "\nWrote \"{}\", run `tara check-config` after changing it.",
+        path.display()
+    );
+    Ok(())
+}
+
+/// Write `config` to `path` with comments, readable only by its owner since it has
+/// secrets in it.
+async fn write(path: &Path, config: &Configuration) -> Result<()> {
+    let contents = render(config).map_err(|e| {
+        Error::ConfigurationSave {
+            path:  path.to_path_buf(),
+            error: Box::new(e),
+        }
+    })?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    file.write_all(contents.as_bytes()).await?;
+
+    // The mode is only used when the file is created.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+    }
+    Ok(())
+}
+
+/// `config` as TOML, with a comment on every key. Keys without a value are written
+/// commented out.
+fn render(config: &Configuration) -> std::result::Result<String, toml::ser::Error> {
+    let mut out = String::from(
+        "# Tara's configuration, see the README for more on each key. Run `tara check-config` after \
+         changing it.\n\n",
+    );
+    entry(
+        &mut out,
+        "`true` picks error messages at random from `error_messages.json` in the default locations, a path \
+         picks them from that file.",
+        "random_error_message",
+        Some(&config.random_error_message),
+    )?;
+
+    let secrets = &config.secrets;
+    out.push_str(
+        "\n# Each secret can be read from a file instead, like `token_file = \
+         \"/run/secrets/token\"`.\n[secrets]\n",
+    );
+    entry(
+        &mut out,
+        "Discord bot token (`TARA_TOKEN`).",
+        "token",
+        secrets.token.as_ref(),
+    )?;
+    entry(
+        &mut out,
+        "Postgres or SQLite database URL (`TARA_DATABASE`), a SQLite database in the data directory is used \
+         without one.",
+        "database",
+        secrets.database.as_ref(),
+    )?;
+    entry(
+        &mut out,
+        "currencyapi.com API key for currency conversions (`TARA_CURRENCY_KEY`).",
+        "currency_api_key",
+        secrets.currency_api_key.as_ref(),
+    )?;
+    entry(
+        &mut out,
+        "Unsplash access key for image search and random images (`TARA_UNSPLASH_KEY`).",
+        "unsplash_key",
+        secrets.unsplash_key.as_ref(),
+    )?;
+    entry(
+        &mut out,
+        "OMDb API key for movies and series, built in ones are used without it (`TARA_OMDB_KEY`).",
+        "omdb_api_key",
+        secrets.omdb_api_key.as_ref(),
+    )?;
+
+    if let Some(music) = &config.music {
+        out.push_str("\n[music]\n");
+        entry(
+            &mut out,
+            "Whether music playback is enabled, when Tara is built with the `music` feature.",
+            "enabled",
+            Some(music.enabled),
+        )?;
+    }
+
+    match config.ai.as_ref().and_then(|x| x.llm.as_ref()) {
+        Some(llm) => {
+            out.push_str("\n[ai.llm]\n");
+            entry(&mut out, "Path of the GGML model.", "model", Some(&llm.model))?;
+            entry(
+                &mut out,
+                "How many tokens of context to evaluate prompts with.",
+                "context_token_length",
+                llm.context_token_length,
+            )?;
+            entry(
+                &mut out,
+                "The model's architecture, inferred from the model otherwise.",
+                "architecture",
+                llm.architecture.as_ref(),
+            )?;
+            entry(
+                &mut out,
+                "Whether to memory map the model.",
+                "prefer_mmap",
+                llm.prefer_mmap,
+            )?;
+            entry(&mut out, "Whether to use the GPU.", "use_gpu", llm.use_gpu)?;
+            entry(
+                &mut out,
+                "How many layers to offload to the GPU, all of them otherwise.",
+                "gpu_layers",
+                llm.gpu_layers,
+            )?;
+            entry(
+                &mut out,
+                "How many threads to use, the physical cores otherwise.",
+                "thread_count",
+                llm.thread_count,
+            )?;
+            entry(
+                &mut out,
+                "How many prompt tokens to ingest at once.",
+                "batch_size",
+                llm.batch_size,
+            )?;
+        }
+        None => {
+            out.push_str(
+                "\n# The LLM is only used when Tara is built with the `ai` feature and has a model.\n# \
+                 [ai.llm]\n# model = \"/path/to/model.bin\"\n",
+            );
+        }
+    }
+
+    if config.presence == Presence::default() {
+        out.push_str(
+            "\n# What Tara shows as its status, see the README for the activities and their variables.\n# \
+             [presence]\n# status = \"online\"\n# interval = 300\n# [[presence.activities]]\n# kind = \
+             \"watching\"\n# text = \"{guilds} servers\"\n",
+        );
+    } else {
+        /// Puts the presence in its own table.
+        #[derive(Serialize)]
+        struct Section<'a> {
+            presence: &'a Presence,
+        }
+        out.push('\n');
+        out.push_str(&toml::to_string(&Section {
+            presence: &config.presence,
+        })?);
+    }
+
+    if config.endpoints != Endpoints::default() {
+        out.push_str("\n[endpoints]\n");
+        for (name, url) in config.endpoints.urls() {
+            writeln!(out, "{name} = {}", toml::Value::try_from(url)?).unwrap();
+        }
+    }
+
+    Ok(out)
+}
+
+/// Write `key = value` to `out` with `comment` above it.
+fn entry(
+    out: &mut String,
+    comment: &str,
+    key: &str,
+    value: Option<impl Serialize>,
+) -> std::result::Result<(), toml::ser::Error> {
+    // Writing to a `String` can't fail.
+    writeln!(out, "# {comment}").unwrap();
+    match value {
+        Some(value) => writeln!(out, "{key} = {}", toml::Value::try_from(value)?).unwrap(),
+        None => writeln!(out, "# {key} =").unwrap(),
+    }
+    Ok(())
+}
+
+async fn test_token(token: String) -> anyhow::Result<String> {
+    if !check::is_discord_token(&token) {
+        anyhow::bail!("That doesn't look like a bot token");
+    }
+    let user = Http::new(&token)
+        .get_current_user()
+        .await
+        .context("Discord didn't accept it")?;
+    Ok(format!("Logged in as {}", user.name))
+}
+
+async fn test_database(url: String) -> anyhow::Result<String> {
+    if Backend::of(&url).is_none() {
+        anyhow::bail!("That isn't a Postgres or SQLite URL");
+    }
+    tokio::time::timeout(Duration::from_secs(5), database::open(&url))
+        .await
+        .context("Couldn't connect in time")?
+        .context("Couldn't connect")?;
+    Ok(String::from("Connected"))
+}
+
+/// Check that the API key in `url` is accepted.
+async fn test_key(url: String) -> anyhow::Result<String> {
+    let status = reqwest::get(url).await?.status();
+    if !status.is_success() {
+        anyhow::bail!("It was rejected ({status})");
+    }
+    Ok(String::from("It works"))
+}
+
+struct Prompt {
+    editor: DefaultEditor,
+}
+
+impl Prompt {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            editor: DefaultEditor::new().map_err(Error::ReadLine)?,
+        })
+    }
+
+    /// Ask `question` until there's an answer.
+    fn line(&mut self, question: &str) -> Result<String> {
+        loop {
+            if let Some(answer) = self.optional(question)? {
+                return Ok(answer);
+            }
+        }
+    }
+
+    /// Ask `question`, an empty answer is `None`.
+    fn optional(&mut self, question: &str) -> Result<Option<String>> {
+        let answer = self
+            .editor
+            .readline(&format!("{question}: "))
+            .map_err(Error::ReadLine)?;
+        let answer = answer.trim();
+        Ok((!answer.is_empty()).then(|| answer.to_string()))
+    }
+
+    /// Ask a yes or no `question`, an empty answer is `default`.
+    fn confirm(&mut self, question: &str, default: bool) -> Result<bool> {
+        let choices = if default { "Y/n" } else { "y/N" };
+        loop {
+            let answer = self.optional(&format!("{question} [{choices}]"))?;
+            match answer.map(|x| x.to_lowercase()).as_deref() {
+                None => return Ok(default),
+                Some("y" | "yes") => return Ok(true),
+                Some("n" | "no") => return Ok(false),
+                Some(_) => println!("  Answer yes or no."),
+            }
+        }
+    }
+
+    /// Ask `question` and try the answer out with `test`, asking again if it doesn't work
+    /// and the answer isn't kept anyway.
+    async fn tested<F, Fut>(&mut self, question: &str, optional: bool, test: F) -> Result<Option<String>>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = anyhow::Result<String>>,
+    {
+        loop {
+            let answer = if optional {
+                self.optional(question)?
+            } else {
+                Some(self.line(question)?)
+            };
+            let Some(answer) = answer else {
+                return Ok(None);
+            };
+
+            match test(answer.clone()).await {
+                Ok(message) => {
+                    println!("  {message}.");
+                    return Ok(Some(answer));
+                }
+                Err(e) => {
+                    println!("  {e:#}.");
+                    if self.confirm("Use it anyway?", false)? {
+                        return Ok(Some(answer));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut config = Configuration::default();
+        config.secrets.token = Some(String::from("MTA5.GhX1_a.abc-DEF_123"));
+        config.secrets.database = Some(String::from("postgres://tara@localhost/tara"));
+        config.secrets.omdb_api_key = Some(String::from("4b447405"));
+        config.endpoints.wikipedia = String::from("https://wikipedia.example.com");
+        config.presence.interval = 60;
+        config.ai = Some(Ai {
+            llm: Some(Llm {
+                model:                PathBuf::from("/models/model.bin"),
+                context_token_length: None,
+                architecture:         None,
+                prefer_mmap:          None,
+                use_gpu:              Some(false),
+                gpu_layers:           None,
+                thread_count:         Some(4),
+                batch_size:           None,
+            }),
+        });
+
+        let rendered = render(&config).unwrap();
+        assert!(rendered.contains("# currency_api_key =\n"));
+        assert!(rendered.contains("token = \"MTA5.GhX1_a.abc-DEF_123\"\n"));
+
+        // Reading it back gives the same configuration.
+        let parsed: Configuration = toml::from_str(&rendered).unwrap();
+        assert_eq!(
+            serde_json::to_value(parsed).unwrap(),
+            serde_json::to_value(config).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_default() {
+        let rendered = render(&Configuration::default()).unwrap();
+        assert!(!rendered.contains("[endpoints]"));
+        assert!(rendered.contains("# [ai.llm]"));
+        assert!(rendered.contains("# [presence]"));
+
+        let parsed: Configuration = toml::from_str(&rendered).unwrap();
+        assert!(parsed.ai.is_none());
+        assert_eq!(parsed.music, Some(Music::default()));
+    }
+}
@@ -1,12 +1,19 @@
-use std::{env, path::Path};
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+};
 
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use tara_util::paths;
+use tara_util::{paths, presence::Presence};
 use tokio::fs;
 
 use crate::{Error, Result};
 
 pub mod ai;
+pub mod check;
+pub mod endpoints;
+pub mod init;
 pub mod music;
 
 /// Configurations required to host the bot
@@ -16,27 +23,89 @@ pub struct Configuration {
     pub random_error_message: ConfigurationRandomErrorMessages,
     pub music:                Option<music::Music>,
     pub ai:                   Option<ai::Ai>,
+    /// Where to find the web APIs used by commands.
+    #[serde(default)]
+    pub endpoints:            endpoints::Endpoints,
+    /// The status and activities Tara shows.
+    #[serde(default)]
+    pub presence:             Presence,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Default)]
-/// API keys and other secrets
+#[derive(Clone, Deserialize, Serialize, Default)]
+/// API keys and other secrets. Each one can be read from a file instead, named by the
+/// `_file` key after it, which is used when the secret itself isn't set.
 pub struct ConfigurationSecrets {
     /// Discord bot token (overridden at runtime by the `TARA_TOKEN` env variable if
     /// present).
-    pub token:            Option<String>,
-    /// Postgres Database URL (overridden at runtime by the `TARA_POSTGRES` env variable
-    /// if present).
-    pub postgres:         Option<String>,
+    pub token:                 Option<String>,
+    pub token_file:            Option<PathBuf>,
+    /// Database URL, `postgres://` or `sqlite://` (overridden at runtime by the
+    /// `TARA_DATABASE` env variable if present). This used to be called `postgres`. When
+    /// it's not set, a SQLite database in the data directory is used.
+    pub database:              Option<String>,
+    pub database_file:         Option<PathBuf>,
     /// API key for access to `currencyapi.com` (overridden at runtime by the
     /// `TARA_CURRENCY_KEY` env variable if present).
-    pub currency_api_key: Option<String>,
+    pub currency_api_key:      Option<String>,
+    pub currency_api_key_file: Option<PathBuf>,
     /// API key for access to OMDb (overridden at runtime by the
     /// `TARA_OMDB_KEY` env variable if present), this is completely optional, if
     /// it's not provided builtin ones will be used instead.
-    pub omdb_api_key:     Option<String>,
+    pub omdb_api_key:          Option<String>,
+    pub omdb_api_key_file:     Option<PathBuf>,
     /// API key for access to Unsplash (overridden at runtime by the
     /// `TARA_UNSPLASH_KEY` env variable if present).
-    pub unsplash_key:     Option<String>,
+    pub unsplash_key:          Option<String>,
+    pub unsplash_key_file:     Option<PathBuf>,
+}
+
+impl ConfigurationSecrets {
+    /// Read every secret that isn't set from its file, if it has one. Relative paths are
+    /// looked up in `$CREDENTIALS_DIRECTORY` when systemd's `LoadCredential=` provides
+    /// one.
+    async fn read_files(&mut self) -> anyhow::Result<()> {
+        let credentials = env::var_os("CREDENTIALS_DIRECTORY").map(PathBuf::from);
+        let secrets = [
+            (&mut self.token, &self.token_file),
+            (&mut self.database, &self.database_file),
+            (&mut self.currency_api_key, &self.currency_api_key_file),
+            (&mut self.omdb_api_key, &self.omdb_api_key_file),
+            (&mut self.unsplash_key, &self.unsplash_key_file),
+        ];
+        for (secret, file) in secrets {
+            let Some(file) = file.as_ref().filter(|_| secret.is_none()) else {
+                continue;
+            };
+            let file = match &credentials {
+                Some(credentials) if file.is_relative() => credentials.join(file),
+                _ => file.clone(),
+            };
+            let contents = fs::read_to_string(&file)
+                .await
+                .with_context(|| format!("Couldn't read the secret in \"{}\"", file.display()))?;
+            *secret = Some(contents.trim_end_matches(['\r', '\n']).to_string());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ConfigurationSecrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The secrets themselves must never end up in the logs.
+        let redact = |x: &Option<String>| x.as_ref().map(|_| "<redacted>");
+        f.debug_struct("ConfigurationSecrets")
+            .field("token", &redact(&self.token))
+            .field("token_file", &self.token_file)
+            .field("database", &redact(&self.database))
+            .field("database_file", &self.database_file)
+            .field("currency_api_key", &redact(&self.currency_api_key))
+            .field("currency_api_key_file", &self.currency_api_key_file)
+            .field("omdb_api_key", &redact(&self.omdb_api_key))
+            .field("omdb_api_key_file", &self.omdb_api_key_file)
+            .field("unsplash_key", &redact(&self.unsplash_key))
+            .field("unsplash_key_file", &self.unsplash_key_file)
+            .finish()
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -48,8 +117,10 @@ pub enum ConfigurationRandomErrorMessages {
 }
 
 impl Configuration {
-    /// Read a `Configuration` from toml located at `path`, anything not found in the file
-    /// will be grabbed from the corresponding environment variables.
+    /// Read a `Configuration` from toml located at `path`, then apply the environment
+    /// over it: every `TARA__SECTION__KEY` variable sets that key (see `overlay`), and
+    /// the older `TARA_TOKEN`-style variables set their secret. Secrets that still aren't
+    /// set are read from their files.
     ///
     /// # Usage
     ///
@@ -68,56 +139,56 @@ impl Configuration {
     /// Will error when:
     ///
     /// - `Path` cannoth be read from successfully
-    /// - `Path`'s contents cannot be parsed into a `Configuration`
+    /// - `Path`'s contents, with the environment applied, cannot be parsed into a
+    ///   `Configuration`
+    /// - A secret's file cannot be read
     pub async fn parse(path: Option<impl AsRef<Path>>) -> anyhow::Result<Self> {
         // Get the configuration file path and read the configuration from it.
-        let path = path
-            .as_ref()
-            .map(|x| x.as_ref())
-            .or_else(|| paths::TARA_CONFIGURATION_FILE.as_ref().map(|x| x.as_path()));
-
-        let Self {
-            secrets:
-                ConfigurationSecrets {
-                    token,
-                    postgres,
-                    currency_api_key,
-                    omdb_api_key,
-                    unsplash_key,
-                },
-            random_error_message,
-            music,
-            ai,
-        } = if let Some(path) = path {
+        let path = Self::location(path.as_ref().map(|x| x.as_ref()));
+
+        let mut table = if let Some(path) = path {
             let file_contents = fs::read_to_string(path).await.map_err(Error::Io)?;
             tracing::info!("Loaded configuration from \"{}\"", path.display());
-            let parsed: Self = toml::from_str(&file_contents).map_err(|e| {
+            file_contents.parse::<toml::Table>().map_err(|e| {
                 Error::ConfigurationParse {
                     path:  path.to_path_buf(),
                     error: Box::new(e),
                 }
-            })?;
-            parsed
+            })?
         } else {
-            Self::default()
+            match toml::Value::try_from(Self::default())? {
+                toml::Value::Table(table) => table,
+                _ => unreachable!("a struct is always a table"),
+            }
         };
+        overlay(&mut table, env::vars());
 
-        let config = Self {
-            secrets: ConfigurationSecrets {
-                token:            env::var("TARA_TOKEN").ok().or(token),
-                postgres:         env::var("TARA_POSTGRES").ok().or(postgres),
-                currency_api_key: env::var("TARA_CURRENCY_KEY").ok().or(currency_api_key),
-                omdb_api_key:     env::var("TARA_OMDB_KEY").ok().or(omdb_api_key),
-                unsplash_key:     env::var("TARA_UNSPLASH_KEY").ok().or(unsplash_key),
-            },
-            random_error_message,
-            music,
-            ai,
+        let mut config: Self = match toml::Value::Table(table).try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                return Err(match path {
+                    Some(path) => {
+                        Error::ConfigurationParse {
+                            path:  path.to_path_buf(),
+                            error: Box::new(e),
+                        }
+                        .into()
+                    }
+                    None => anyhow::Error::new(e).context("The `TARA__` environment variables are invalid"),
+                })
+            }
         };
+        config.secrets.read_files().await?;
 
         tracing::debug!("Parsed config: {config:#?}");
         Ok(config)
     }
+
+    /// The configuration file to read: `path` if there is one, otherwise the default
+    /// configuration file if it exists.
+    pub fn location(path: Option<&Path>) -> Option<&Path> {
+        path.or_else(|| paths::TARA_CONFIGURATION_FILE.as_deref())
+    }
 }
 
 impl Default for Configuration {
@@ -127,10 +198,95 @@ impl Default for Configuration {
             random_error_message: ConfigurationRandomErrorMessages::Boolean(false),
             music:                Some(music::Music::default()),
             ai:                   None,
+            endpoints:            endpoints::Endpoints::default(),
+            presence:             Presence::default(),
+        }
+    }
+}
+
+/// Secrets that were renamed, by their old name.
+const RENAMED_SECRETS: &[(&str, &str)] = &[("postgres", "database"), ("postgres_file", "database_file")];
+
+/// The older environment variables for secrets and the keys they set.
+const SECRET_VARIABLES: &[(&str, &str)] = &[
+    ("TARA_TOKEN", "token"),
+    ("TARA_DATABASE", "database"),
+    ("TARA_POSTGRES", "database"),
+    ("TARA_CURRENCY_KEY", "currency_api_key"),
+    ("TARA_OMDB_KEY", "omdb_api_key"),
+    ("TARA_UNSPLASH_KEY", "unsplash_key"),
+];
+
+/// Apply the environment variables in `vars` to the configuration in `table`.
+///
+/// `TARA__SECTION__KEY=value` sets `key` in `[section]`, with as many sections as it
+/// takes, so `TARA__AI__LLM__USE_GPU=true` sets `use_gpu` in `[ai.llm]`. Values are read
+/// as TOML, or as a string if they aren't valid TOML, except in `[secrets]` where
+/// they're always strings. `TARA_TOKEN` and the other older variables set their secret
+/// too, but `TARA__` variables take precedence over them.
+///
+/// Secrets under their old names are moved to their new ones, unless those are set too.
+pub(crate) fn overlay(table: &mut toml::Table, vars: impl IntoIterator<Item = (String, String)>) {
+    if let Some(toml::Value::Table(secrets)) = table.get_mut("secrets") {
+        for (old, new) in RENAMED_SECRETS {
+            if let Some(value) = secrets.remove(*old) {
+                secrets.entry(*new).or_insert(value);
+            }
+        }
+    }
+
+    let mut overlays = Vec::new();
+    for (name, value) in vars {
+        if let Some(path) = name.strip_prefix("TARA__") {
+            let mut path = path.split("__").map(str::to_lowercase).collect::<Vec<_>>();
+            if let [section, key] = &mut path[..] {
+                let renamed = RENAMED_SECRETS.iter().find(|(old, _)| key.as_str() == *old);
+                if let Some((_, new)) = renamed.filter(|_| section.as_str() == "secrets") {
+                    *key = new.to_string();
+                }
+            }
+            if !path.iter().any(String::is_empty) {
+                overlays.push((path, value));
+            }
+        } else if let Some((_, key)) = SECRET_VARIABLES.iter().find(|(x, _)| *x == name) {
+            // Applied first so they can be overridden.
+            overlays.insert(0, (vec![String::from("secrets"), key.to_string()], value));
         }
     }
+
+    for (path, value) in overlays {
+        let value = if path[0] == "secrets" {
+            toml::Value::String(value)
+        } else {
+            parse_value(value)
+        };
+
+        let (key, sections) = path.split_last().unwrap();
+        let mut table = &mut *table;
+        for section in sections {
+            let entry = table
+                .entry(section.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if !entry.is_table() {
+                *entry = toml::Value::Table(toml::Table::new());
+            }
+            let toml::Value::Table(section) = entry else {
+                unreachable!()
+            };
+            table = section;
+        }
+        table.insert(key.clone(), value);
+    }
 }
 
+/// `value` as a TOML value, or a string if it isn't one.
+fn parse_value(value: String) -> toml::Value {
+    format!("value = {value}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut x| x.remove("value"))
+        .unwrap_or(toml::Value::String(value))
+}
 
 #[derive(Debug, Clone, PartialEq)]
 /// Error messages parsed from the file provided in the `Configuration`
@@ -185,3 +341,95 @@ impl Default for ErrorMessages {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut table: toml::Table = indoc::indoc! {r#"
+            random_error_message = false
+
+            [secrets]
+            token = "from the file"
+            postgres = "postgres://tara@localhost/tara"
+            postgres_file = "/run/secrets/database"
+
+            [music]
+            enabled = true
+        "#}
+        .parse()
+        .unwrap();
+        overlay(
+            &mut table,
+            vars(&[
+                ("TARA__SECRETS__TOKEN", "from the environment"),
+                ("TARA_TOKEN", "from the older variable"),
+                ("TARA__SECRETS__OMDB_API_KEY", "12345678"),
+                ("TARA__SECRETS__POSTGRES_FILE", "/run/secrets/postgres"),
+                ("TARA__MUSIC__ENABLED", "false"),
+                ("TARA__AI__LLM__MODEL", "/models/model.bin"),
+                ("TARA__AI__LLM__THREAD_COUNT", "4"),
+                ("TARA__", "ignored"),
+                ("HOME", "/root"),
+            ]),
+        );
+        let config: Configuration = toml::Value::Table(table).try_into().unwrap();
+
+        assert_eq!(config.secrets.token.as_deref(), Some("from the environment"));
+        assert_eq!(config.secrets.omdb_api_key.as_deref(), Some("12345678"));
+        // `postgres` is now called `database`, under either name it's the same secret.
+        assert_eq!(
+            config.secrets.database.as_deref(),
+            Some("postgres://tara@localhost/tara")
+        );
+        assert_eq!(
+            config.secrets.database_file,
+            Some(PathBuf::from("/run/secrets/postgres"))
+        );
+        assert_eq!(config.music, Some(music::Music { enabled: false }));
+        let llm = config.ai.unwrap().llm.unwrap();
+        assert_eq!(llm.model, PathBuf::from("/models/model.bin"));
+        assert_eq!(llm.thread_count, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_secret_files() {
+        let file = std::env::temp_dir().join(format!("tara-test-token-{}", std::process::id()));
+        fs::write(&file, "token.from.file\n").await.unwrap();
+        let mut secrets = ConfigurationSecrets {
+            token_file: Some(file.clone()),
+            database: Some(String::from("postgres://tara@localhost/tara")),
+            database_file: Some(PathBuf::from("/nonexistent")),
+            ..Default::default()
+        };
+        secrets.read_files().await.unwrap();
+        fs::remove_file(&file).await.unwrap();
+
+        assert_eq!(secrets.token.as_deref(), Some("token.from.file"));
+        // A secret that's set doesn't need its file.
+        assert_eq!(
+            secrets.database.as_deref(),
+            Some("postgres://tara@localhost/tara")
+        );
+    }
+
+    #[test]
+    fn test_redacted() {
+        let secrets = ConfigurationSecrets {
+            token: Some(String::from("MTA5.GhX1_a.abc-DEF_123")),
+            ..Default::default()
+        };
+        let debug = format!("{secrets:?}");
+
+        assert!(!debug.contains("MTA5"));
+        assert!(debug.contains("<redacted>"));
+    }
+}
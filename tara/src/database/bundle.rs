@@ -0,0 +1,466 @@
+//! Everything Tara stores about a guild as one versioned document, so the guild can be
+//! moved to another Tara instance. It's exported and imported with `/settings export` and
+//! `/settings import`, or over IPC.
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::all::{ChannelId, Guild, GuildId, RoleId};
+use tara_util::ipc::BundleFormat;
+
+use super::{Database, GuildRows, StoredComponent};
+use crate::{
+    componet::{self, Authorization},
+    moderation::Case,
+    roles::{RoleGroup, RoleRules, TemporaryRole},
+    settings::{Setting, Value},
+    Error, Result,
+};
+
+/// The version of the bundles this Tara writes. It goes up whenever a bundle written by
+/// it couldn't be read by an older Tara, e.g. when a table is added.
+pub const VERSION: u32 = 4;
+
+/// A guild's rows from every per-guild table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuildBundle {
+    pub version:               u32,
+    pub guild_id:              GuildId,
+    pub exported:              DateTime<Utc>,
+    #[serde(default)]
+    pub self_assignable_roles: Vec<RoleId>,
+    /// Since version 2.
+    #[serde(default)]
+    pub role_groups:           Vec<RoleGroup>,
+    /// Since version 3.
+    #[serde(default)]
+    pub role_rules:            Vec<RoleRules>,
+    /// Since version 3.
+    #[serde(default)]
+    pub auto_roles:            Vec<RoleId>,
+    /// Since version 3.
+    #[serde(default)]
+    pub temporary_roles:       Vec<TemporaryRole>,
+    /// The moderation log. Since version 4.
+    #[serde(default)]
+    pub cases:                 Vec<Case>,
+    /// The settings that were changed from their defaults, by name.
+    #[serde(default)]
+    pub settings:              BTreeMap<String, Value>,
+    #[serde(default)]
+    pub components:            Vec<BundledComponent>,
+}
+
+/// A [`StoredComponent`] of the guild a [`GuildBundle`] is for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledComponent {
+    pub id:            String,
+    pub channel_id:    ChannelId,
+    pub kind:          String,
+    pub state:         Vec<u8>,
+    pub authorization: Option<String>,
+    pub expiry:        Option<DateTime<Utc>>,
+}
+
+impl GuildBundle {
+    /// Everything `database` has for `guild_id`. Components that already expired, and
+    /// settings that can't be read, are left out.
+    pub async fn export(database: &Database, guild_id: GuildId) -> Result<Self> {
+        let mut settings = BTreeMap::new();
+        for (key, value) in database.guild_settings(guild_id).await? {
+            match serde_json::from_str(&value) {
+                Ok(value) => {
+                    settings.insert(key, value);
+                }
+                Err(e) => tracing::warn!("Not exporting the invalid {key} setting of guild {guild_id}: {e}"),
+            }
+        }
+
+        let now = Utc::now();
+        let components = database
+            .components()
+            .await?
+            .into_iter()
+            .filter(|x| x.guild_id == Some(guild_id) && x.expiry.map_or(true, |x| x > now))
+            .map(|x| {
+                BundledComponent {
+                    id:            x.id,
+                    channel_id:    x.channel_id,
+                    kind:          x.kind,
+                    state:         x.state,
+                    authorization: x.authorization,
+                    expiry:        x.expiry,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            version: VERSION,
+            guild_id,
+            exported: now,
+            self_assignable_roles: database.self_assignable_roles(guild_id).await?,
+            role_groups: database.role_groups(guild_id).await?,
+            role_rules: database.role_rules(guild_id).await?,
+            auto_roles: database.auto_roles(guild_id).await?,
+            temporary_roles: database.temporary_roles(guild_id).await?,
+            cases: database.cases(guild_id).await?,
+            settings,
+            components,
+        })
+    }
+
+    /// Read a bundle written in either [`BundleFormat`].
+    ///
+    /// # Errors
+    ///
+    /// [`Error::CommandMisuse`] when `text` isn't a bundle, or is one from a newer Tara.
+    pub fn parse(text: &str) -> Result<Self> {
+        let bundle: Self = if text.trim_start().starts_with('{') {
+            serde_json::from_str(text).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(text).map_err(|e| e.to_string())
+        }
+        .map_err(|e| Error::CommandMisuse(format!("That isn't an exported server: {e}")))?;
+
+        if bundle.version > VERSION {
+            return Err(Error::CommandMisuse(format!(
+                "This server was exported by a newer Tara (version {} bundle), update Tara to import it",
+                bundle.version
+            )));
+        }
+        Ok(bundle)
+    }
+
+    pub fn write(&self, format: BundleFormat) -> Result<String> {
+        match format {
+            BundleFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|e| Error::JsonParse(e.to_string()))
+            }
+            BundleFormat::Toml => {
+                // Only a bug can make it unrepresentable.
+                toml::to_string_pretty(self)
+                    .map_err(|_| Error::Unexpected("The bundle can't be written as TOML"))
+            }
+        }
+    }
+
+    /// Check that the bundle can be imported into `guild`: it has to be from the same
+    /// guild, its roles and channels have to still exist, and its settings and components
+    /// have to be valid there.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::CommandMisuse`] listing everything that's wrong with the bundle.
+    pub fn validate(&self, guild: &Guild) -> Result<()> {
+        if self.guild_id != guild.id {
+            return Err(Error::CommandMisuse(format!(
+                "This was exported from another server ({}), not {}",
+                self.guild_id, guild.name
+            )));
+        }
+
+        let mut problems = Vec::new();
+        for role_id in &self.self_assignable_roles {
+            if !guild.roles.contains_key(role_id) {
+                problems.push(format!(
+                    "The self-assignable role {role_id} doesn't exist anymore"
+                ));
+            }
+        }
+        for (i, group) in self.role_groups.iter().enumerate() {
+            if let Err(Error::CommandMisuse(why)) = group.validate() {
+                problems.push(why);
+            }
+            if self.role_groups[..i].iter().any(|x| x.name == group.name) {
+                problems.push(format!("There are two role groups called `{}`", group.name));
+            }
+            for role_id in &group.roles {
+                if !self.self_assignable_roles.contains(role_id) {
+                    problems.push(format!(
+                        "The role {role_id} in `{}` isn't self-assignable",
+                        group.name
+                    ));
+                }
+            }
+        }
+        for rules in &self.role_rules {
+            if !self.self_assignable_roles.contains(&rules.role) {
+                problems.push(format!(
+                    "The role {} has rules but isn't self-assignable",
+                    rules.role
+                ));
+            }
+            if let Some(required) = rules.requires.filter(|x| !guild.roles.contains_key(x)) {
+                problems.push(format!(
+                    "The role {required}, which {} requires, doesn't exist anymore",
+                    rules.role
+                ));
+            }
+        }
+        for role_id in &self.auto_roles {
+            if !guild.roles.contains_key(role_id) {
+                problems.push(format!("The auto-role {role_id} doesn't exist anymore"));
+            }
+        }
+        for (i, case) in self.cases.iter().enumerate() {
+            if case.guild_id != self.guild_id {
+                problems.push(format!("Case {} is from another server", case.number));
+            }
+            if case.number == 0 {
+                problems.push(String::from("Cases are numbered from 1"));
+            } else if self.cases[..i].iter().any(|x| x.number == case.number) {
+                problems.push(format!("There are two cases numbered {}", case.number));
+            }
+        }
+        for role in &self.temporary_roles {
+            if role.guild_id != self.guild_id {
+                problems.push(format!(
+                    "The temporary role {} of <@{}> is from another server",
+                    role.role_id, role.user_id
+                ));
+            } else if !guild.roles.contains_key(&role.role_id) {
+                problems.push(format!(
+                    "The temporary role {} doesn't exist anymore",
+                    role.role_id
+                ));
+            }
+        }
+        for component in &self.components {
+            if !componet::has_kind(&component.kind) {
+                problems.push(format!(
+                    "The component {} is a `{}`, which this Tara doesn't have",
+                    component.id, component.kind
+                ));
+            }
+            let channel_id = component.channel_id;
+            if !guild.channels.contains_key(&channel_id) && !guild.threads.iter().any(|x| x.id == channel_id)
+            {
+                problems.push(format!(
+                    "The channel <#{channel_id}> of the component {} doesn't exist anymore",
+                    component.id
+                ));
+            }
+            let authorization = component
+                .authorization
+                .as_deref()
+                .map(serde_json::from_str::<Authorization>);
+            if let Some(Err(e)) = authorization {
+                problems.push(format!(
+                    "The component {} has an invalid authorization: {e}",
+                    component.id
+                ));
+            }
+        }
+        for (name, value) in &self.settings {
+            match Setting::find(name) {
+                Some(setting) => {
+                    match setting.validate(value, guild) {
+                        Ok(()) => {}
+                        Err(Error::CommandMisuse(why)) => problems.push(format!("`{name}`: {why}")),
+                        Err(_) => problems.push(format!("`{name}` can't be {value}")),
+                    }
+                }
+                None => problems.push(format!("There's no setting called `{name}`")),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::CommandMisuse(format!(
+                "Nothing was imported:\n- {}",
+                problems.join("\n- ")
+            )))
+        }
+    }
+
+    /// Replace everything `database` has for the bundle's guild with the bundle, all at
+    /// once. The bundle should be validated first.
+    pub async fn import(&self, database: &Database) -> Result<()> {
+        let settings = self
+            .settings
+            .iter()
+            .map(|(key, value)| {
+                let json = serde_json::to_string(value).map_err(|e| Error::JsonParse(e.to_string()))?;
+                Ok((key.clone(), json))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let components = self
+            .components
+            .iter()
+            .cloned()
+            .map(|x| {
+                StoredComponent {
+                    id:            x.id,
+                    guild_id:      Some(self.guild_id),
+                    channel_id:    x.channel_id,
+                    kind:          x.kind,
+                    state:         x.state,
+                    authorization: x.authorization,
+                    expiry:        x.expiry,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let rows = GuildRows {
+            roles:           &self.self_assignable_roles,
+            role_rules:      &self.role_rules,
+            role_groups:     &self.role_groups,
+            auto_roles:      &self.auto_roles,
+            temporary_roles: &self.temporary_roles,
+            cases:           &self.cases,
+            settings:        &settings,
+            components:      &components,
+        };
+        database.import_guild(self.guild_id, rows).await
+    }
+
+    /// The name to give the bundle when it's saved as a file.
+    pub fn file_name(&self, format: BundleFormat) -> String {
+        let extension = match format {
+            BundleFormat::Json => "json",
+            BundleFormat::Toml => "toml",
+        };
+        format!("tara-{}.{extension}", self.guild_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use chrono::TimeZone;
+    use serenity::all::UserId;
+
+    use super::*;
+    use crate::{
+        database::{id, open},
+        moderation::Action,
+    };
+
+    fn bundle() -> GuildBundle {
+        GuildBundle {
+            version:               VERSION,
+            guild_id:              GuildId(id(1).unwrap()),
+            exported:              Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            self_assignable_roles: vec![RoleId(id(2).unwrap())],
+            role_groups:           vec![RoleGroup {
+                exclusive: true,
+                roles: vec![RoleId(id(2).unwrap())],
+                ..RoleGroup::new("region")
+            }],
+            role_rules:            vec![RoleRules {
+                lifetime: Some(3600),
+                ..RoleRules::new(RoleId(id(2).unwrap()))
+            }],
+            auto_roles:            vec![RoleId(id(6).unwrap())],
+            temporary_roles:       vec![TemporaryRole {
+                guild_id: GuildId(id(1).unwrap()),
+                user_id:  UserId(id(7).unwrap()),
+                role_id:  RoleId(id(2).unwrap()),
+                expiry:   Utc.timestamp_opt(1_700_003_600, 0).unwrap(),
+            }],
+            cases:                 vec![Case {
+                number: 1,
+                created: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+                ..Case::new(
+                    GuildId(id(1).unwrap()),
+                    UserId(id(7).unwrap()),
+                    Action::Warn,
+                    "spam",
+                )
+            }],
+            settings:              BTreeMap::from([
+                (
+                    String::from("safe_search"),
+                    Value::Choice(Cow::Borrowed("strict")),
+                ),
+                (String::from("llm_enabled"), Value::Boolean(false)),
+            ]),
+            components:            vec![BundledComponent {
+                id:            String::from("paginator/1"),
+                channel_id:    ChannelId(id(3).unwrap()),
+                kind:          String::from("paginator_button"),
+                state:         vec![1, 2, 3],
+                authorization: None,
+                expiry:        None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        let bundle = bundle();
+        for format in [BundleFormat::Json, BundleFormat::Toml] {
+            let text = bundle.write(format).unwrap();
+            assert_eq!(GuildBundle::parse(&text).unwrap(), bundle, "{format:?}");
+        }
+
+        let mut newer = bundle;
+        newer.version = VERSION + 1;
+        let text = newer.write(BundleFormat::Json).unwrap();
+        assert!(matches!(GuildBundle::parse(&text), Err(Error::CommandMisuse(_))));
+        assert!(matches!(
+            GuildBundle::parse("[1, 2]"),
+            Err(Error::CommandMisuse(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_export_import() {
+        let database = open("sqlite::memory:").await.unwrap();
+        database.migrate().await.unwrap();
+        let bundle = bundle();
+
+        // Whatever was there before is replaced.
+        database
+            .add_self_assignable_role(bundle.guild_id, RoleId(id(4).unwrap()))
+            .await
+            .unwrap();
+        database
+            .set_guild_setting(bundle.guild_id, "safe_search", "{\"Choice\":\"off\"}")
+            .await
+            .unwrap();
+        bundle.import(&database).await.unwrap();
+
+        let mut exported = GuildBundle::export(&database, bundle.guild_id).await.unwrap();
+        exported.exported = bundle.exported;
+        assert_eq!(exported, bundle);
+
+        // Other guilds have nothing to export.
+        let other = GuildBundle::export(&database, GuildId(id(5).unwrap()))
+            .await
+            .unwrap();
+        assert!(other.self_assignable_roles.is_empty() && other.settings.is_empty());
+        assert!(other.auto_roles.is_empty() && other.temporary_roles.is_empty());
+        assert!(other.cases.is_empty());
+        assert!(other.components.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_import_conflict() {
+        let database = open("sqlite::memory:").await.unwrap();
+        database.migrate().await.unwrap();
+        let bundle = bundle();
+        let other = GuildId(id(5).unwrap());
+        database.add_guild(other, None).await.unwrap();
+        let component = StoredComponent {
+            id:            String::from("paginator/1"),
+            guild_id:      Some(other),
+            channel_id:    ChannelId(id(8).unwrap()),
+            kind:          String::from("paginator_button"),
+            state:         Vec::new(),
+            authorization: None,
+            expiry:        None,
+        };
+        database.save_component(component.clone()).await.unwrap();
+
+        // Another guild's component isn't taken over, and nothing else is imported either.
+        assert!(matches!(
+            bundle.import(&database).await,
+            Err(Error::CommandMisuse(_))
+        ));
+        assert_eq!(database.components().await.unwrap(), [component]);
+        assert!(database.cases(bundle.guild_id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,686 @@
+//! Where Tara keeps its data. Everything goes through [`Storage`], which is implemented
+//! for Postgres and SQLite, and the backend is picked by the scheme of the database URL.
+use std::{num::NonZeroU64, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serenity::all::{ChannelId, GuildId, RoleId, UserId};
+use sqlx::{migrate::Migrator, FromRow};
+use tara_util::paths;
+
+use crate::{
+    moderation::{Action, Case, Note},
+    roles::{RoleGroup, RoleRules, TemporaryRole},
+    Error, Result,
+};
+
+mod bundle;
+mod postgres;
+mod sqlite;
+
+pub use self::{bundle::GuildBundle, postgres::PostgresStorage, sqlite::SqliteStorage};
+
+/// A connection to whichever database Tara was started with. Cheap to clone.
+pub type Database = Arc<dyn Storage>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Postgres,
+    Sqlite,
+}
+
+impl Backend {
+    /// The backend for a database `url`, `postgres://` or `sqlite:`.
+    pub fn of(url: &str) -> Option<Self> {
+        match url.split_once(':')?.0 {
+            "postgres" | "postgresql" => Some(Self::Postgres),
+            "sqlite" => Some(Self::Sqlite),
+            _ => None,
+        }
+    }
+
+    /// The migrations that set up this backend's database.
+    pub fn migrator(self) -> &'static Migrator {
+        match self {
+            Self::Postgres => &postgres::MIGRATOR,
+            Self::Sqlite => &sqlite::MIGRATOR,
+        }
+    }
+}
+
+/// Open the database at `url`, see [`Backend::of`]. The migrations aren't run.
+///
+/// # Errors
+///
+/// [`Error::DatabaseUrl`] when the URL isn't for a supported database, or
+/// [`Error::Database`] when it can't be connected to.
+pub async fn open(url: &str) -> Result<Database> {
+    match Backend::of(url) {
+        Some(Backend::Postgres) => Ok(Arc::new(PostgresStorage::connect(url).await?)),
+        Some(Backend::Sqlite) => Ok(Arc::new(SqliteStorage::connect(url).await?)),
+        None => {
+            Err(Error::DatabaseUrl(
+                url.split(':').next().unwrap_or_default().to_string(),
+            ))
+        }
+    }
+}
+
+/// Open the database at `url`, or the SQLite database in [`paths::DATABASE_FILE`] when
+/// there's no URL.
+///
+/// # Errors
+///
+/// The errors of [`open`] and [`open_file`], or [`Error::DatabaseFile`] when there's no
+/// URL and nowhere to put the database file.
+pub async fn open_configured(url: Option<&str>) -> Result<Database> {
+    match url {
+        Some(url) => open(url).await,
+        None => {
+            let path = paths::DATABASE_FILE.as_deref().ok_or(Error::DatabaseFile)?;
+            tracing::info!("Using the SQLite database in \"{}\"", path.display());
+            open_file(path).await
+        }
+    }
+}
+
+/// Open the SQLite database file at `path`, creating it and its directory when they
+/// don't exist yet. The migrations aren't run.
+pub async fn open_file(path: &Path) -> Result<Database> {
+    if let Some(directory) = path.parent() {
+        tokio::fs::create_dir_all(directory).await.map_err(Error::Io)?;
+    }
+    Ok(Arc::new(SqliteStorage::open(path).await?))
+}
+
+/// Open the database at `url` to look at it. A SQLite database is opened read-only, so
+/// it isn't created or changed.
+///
+/// # Errors
+///
+/// The errors of [`open`], a SQLite database that doesn't exist fails to open.
+pub async fn inspect(url: &str) -> Result<Database> {
+    match Backend::of(url) {
+        Some(Backend::Sqlite) => Ok(Arc::new(SqliteStorage::connect_read_only(url).await?)),
+        _ => open(url).await,
+    }
+}
+
+/// Open the SQLite database file at `path` read-only, see [`inspect`].
+pub async fn inspect_file(path: &Path) -> Result<Database> {
+    Ok(Arc::new(SqliteStorage::open_read_only(path).await?))
+}
+
+/// A component registration as it's persisted, see [`crate::componet::ComponentMap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredComponent {
+    pub id:            String,
+    pub guild_id:      Option<GuildId>,
+    pub channel_id:    ChannelId,
+    /// The [`crate::componet::Component::kind`] that handles it.
+    pub kind:          String,
+    /// Whatever the handler needs to pick up where it left off, bincode encoded.
+    pub state:         Vec<u8>,
+    /// Who may use the component, JSON encoded.
+    pub authorization: Option<String>,
+    pub expiry:        Option<DateTime<Utc>>,
+}
+
+/// A row of `registered_components`, which looks the same in every backend.
+#[derive(FromRow)]
+struct ComponentRow {
+    componet_id:   String,
+    guild_id:      Option<i64>,
+    channel_id:    i64,
+    kind:          String,
+    state:         Vec<u8>,
+    authorization: Option<String>,
+    expiry_date:   Option<NaiveDateTime>,
+}
+
+impl ComponentRow {
+    /// The component, unless the row has an invalid channel ID.
+    fn into_stored(self) -> Option<StoredComponent> {
+        Some(StoredComponent {
+            id:            self.componet_id,
+            guild_id:      self.guild_id.and_then(id).map(GuildId),
+            channel_id:    ChannelId(id(self.channel_id)?),
+            kind:          self.kind,
+            state:         self.state,
+            authorization: self.authorization,
+            expiry:        self.expiry_date.map(|x| Utc.from_utc_datetime(&x)),
+        })
+    }
+}
+
+/// A row of `role_groups`, which looks the same in every backend.
+#[derive(FromRow)]
+struct RoleGroupRow {
+    name:        String,
+    description: String,
+    min_roles:   i32,
+    max_roles:   Option<i32>,
+    exclusive:   bool,
+}
+
+/// The groups in `rows`, with the roles in `members` (`roles.id` and `roles.group_name`).
+fn role_groups(rows: Vec<RoleGroupRow>, members: &[(i64, String)]) -> Vec<RoleGroup> {
+    rows.into_iter()
+        .map(|row| {
+            let roles = members
+                .iter()
+                .filter(|(_, group)| *group == row.name)
+                .filter_map(|(x, _)| id(*x))
+                .map(RoleId)
+                .collect();
+            RoleGroup {
+                name: row.name,
+                description: row.description,
+                min_roles: u32::try_from(row.min_roles).unwrap_or_default(),
+                max_roles: row.max_roles.map(|x| u32::try_from(x).unwrap_or_default()),
+                exclusive: row.exclusive,
+                roles,
+            }
+        })
+        .collect()
+}
+
+/// The rules in `rows` of `roles.id`, `roles.required_role` and `roles.lifetime`.
+fn role_rules(rows: Vec<(i64, Option<i64>, Option<i64>)>) -> Vec<RoleRules> {
+    rows.into_iter()
+        .filter_map(|(role, requires, lifetime)| {
+            Some(RoleRules {
+                role:     RoleId(id(role)?),
+                requires: requires.and_then(id).map(RoleId),
+                lifetime: lifetime.map(|x| u64::try_from(x).unwrap_or_default()),
+            })
+        })
+        .collect()
+}
+
+/// A row of `temporary_roles`, which looks the same in every backend.
+#[derive(FromRow)]
+struct TemporaryRoleRow {
+    guild_id:    i64,
+    user_id:     i64,
+    role_id:     i64,
+    expiry_date: NaiveDateTime,
+}
+
+impl TemporaryRoleRow {
+    /// The temporary role, unless the row has an invalid ID.
+    fn into_role(self) -> Option<TemporaryRole> {
+        Some(TemporaryRole {
+            guild_id: GuildId(id(self.guild_id)?),
+            user_id:  UserId(id(self.user_id)?),
+            role_id:  RoleId(id(self.role_id)?),
+            expiry:   Utc.from_utc_datetime(&self.expiry_date),
+        })
+    }
+}
+
+/// A row of `moderation_log`, which looks the same in every backend.
+#[derive(FromRow)]
+struct CaseRow {
+    guild_id:     i64,
+    number:       i32,
+    user_id:      i64,
+    moderator_id: Option<i64>,
+    action:       String,
+    reason:       String,
+    channel_id:   Option<i64>,
+    content:      Option<String>,
+    duration:     Option<i64>,
+    created_date: NaiveDateTime,
+    expiry_date:  Option<NaiveDateTime>,
+}
+
+impl CaseRow {
+    /// The case, unless the row has an invalid ID or action.
+    fn into_case(self) -> Option<Case> {
+        Some(Case {
+            number:       u32::try_from(self.number).ok()?,
+            guild_id:     GuildId(id(self.guild_id)?),
+            user_id:      UserId(id(self.user_id)?),
+            moderator_id: self.moderator_id.and_then(id).map(UserId),
+            action:       self.action.parse().ok()?,
+            reason:       self.reason,
+            channel_id:   self.channel_id.and_then(id).map(ChannelId),
+            content:      self.content,
+            duration:     self.duration.map(|x| u64::try_from(x).unwrap_or_default()),
+            created:      Utc.from_utc_datetime(&self.created_date),
+            expiry:       self.expiry_date.map(|x| Utc.from_utc_datetime(&x)),
+            notes:        Vec::new(),
+        })
+    }
+}
+
+/// A row of `case_notes`, which looks the same in every backend.
+#[derive(FromRow)]
+struct NoteRow {
+    number:       i32,
+    moderator_id: i64,
+    note:         String,
+    created_date: NaiveDateTime,
+}
+
+/// The cases in `rows` with their `notes`, leaving out invalid rows.
+fn cases(rows: Vec<CaseRow>, notes: Vec<NoteRow>) -> Vec<Case> {
+    let mut cases = rows
+        .into_iter()
+        .filter_map(CaseRow::into_case)
+        .collect::<Vec<_>>();
+    for row in notes {
+        let Some(moderator_id) = id(row.moderator_id).map(UserId) else {
+            continue;
+        };
+        if let Some(case) = cases
+            .iter_mut()
+            .find(|x| i64::from(x.number) == i64::from(row.number))
+        {
+            case.notes.push(Note {
+                moderator_id,
+                text: row.note,
+                created: Utc.from_utc_datetime(&row.created_date),
+            });
+        }
+    }
+    cases
+}
+
+/// A role lifetime as it's stored, in a `BIGINT`.
+fn lifetime(x: u64) -> i64 { i64::try_from(x).unwrap_or(i64::MAX) }
+
+/// Everything in the per-guild tables for one guild, see [`Storage::import_guild`].
+#[derive(Debug, Clone, Copy)]
+pub struct GuildRows<'a> {
+    pub roles:           &'a [RoleId],
+    pub role_rules:      &'a [RoleRules],
+    pub role_groups:     &'a [RoleGroup],
+    pub auto_roles:      &'a [RoleId],
+    pub temporary_roles: &'a [TemporaryRole],
+    pub cases:           &'a [Case],
+    /// The key and JSON encoded value of every setting that was changed.
+    pub settings:        &'a [(String, String)],
+    pub components:      &'a [StoredComponent],
+}
+
+/// A role group limit as it's stored, in an `INTEGER`.
+fn limit(x: u32) -> i32 { i32::try_from(x).unwrap_or(i32::MAX) }
+
+/// An ID stored as a `BIGINT`, `None` for the zero no ID can be.
+fn id(x: i64) -> Option<NonZeroU64> { NonZeroU64::new(x as u64) }
+
+/// Everything Tara reads from and writes to its database.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    fn backend(&self) -> Backend;
+
+    /// Apply the migrations the database doesn't have yet.
+    async fn migrate(&self) -> Result<()>;
+
+    /// The version, whether it succeeded, and the checksum of every migration applied to
+    /// the database, or `None` when none were ever applied and there's no table of them.
+    async fn applied_migrations(&self) -> Result<Option<Vec<(i64, bool, Vec<u8>)>>>;
+
+    /// Add `guild_id` if it isn't there yet, and rename it to `name` if there is one.
+    async fn add_guild(&self, guild_id: GuildId, name: Option<&str>) -> Result<()>;
+
+    /// Remove `guild_id` and everything that belongs to it.
+    async fn remove_guild(&self, guild_id: GuildId) -> Result<()>;
+
+    async fn self_assignable_roles(&self, guild_id: GuildId) -> Result<Vec<RoleId>>;
+
+    /// Make `role_id` self-assignable, returning whether it wasn't already.
+    async fn add_self_assignable_role(&self, guild_id: GuildId, role_id: RoleId) -> Result<bool>;
+
+    /// Stop `role_id` being self-assignable, returning whether it was.
+    async fn remove_self_assignable_role(&self, role_id: RoleId) -> Result<bool>;
+
+    /// The role groups of `guild_id` with the roles in each, sorted by name.
+    async fn role_groups(&self, guild_id: GuildId) -> Result<Vec<RoleGroup>>;
+
+    /// Add `group`, or replace the group with its name. Which roles are in it is left
+    /// alone, see [`Storage::set_role_group`].
+    async fn save_role_group(&self, guild_id: GuildId, group: &RoleGroup) -> Result<()>;
+
+    /// Remove the group called `name`, returning whether there was one. Its roles stay
+    /// self-assignable.
+    async fn remove_role_group(&self, guild_id: GuildId, name: &str) -> Result<bool>;
+
+    /// Move the self-assignable `role_id` into the group called `group`, or out of its
+    /// group when it's `None`. Returns whether the role is self-assignable.
+    async fn set_role_group(&self, role_id: RoleId, group: Option<&str>) -> Result<bool>;
+
+    /// The rules of the self-assignable roles of `guild_id` that have any.
+    async fn role_rules(&self, guild_id: GuildId) -> Result<Vec<RoleRules>>;
+
+    /// Replace the rules of the self-assignable `rules.role`, returning whether it is
+    /// self-assignable.
+    async fn save_role_rules(&self, rules: &RoleRules) -> Result<bool>;
+
+    /// The roles members of `guild_id` get when they join.
+    async fn auto_roles(&self, guild_id: GuildId) -> Result<Vec<RoleId>>;
+
+    /// Give `role_id` to whoever joins `guild_id`, returning whether it wasn't already.
+    async fn add_auto_role(&self, guild_id: GuildId, role_id: RoleId) -> Result<bool>;
+
+    /// Stop giving `role_id` to whoever joins, returning whether it was.
+    async fn remove_auto_role(&self, role_id: RoleId) -> Result<bool>;
+
+    /// When the members of `guild_id` lose their temporary roles.
+    async fn temporary_roles(&self, guild_id: GuildId) -> Result<Vec<TemporaryRole>>;
+
+    /// Insert `role`, or replace the expiry of the same member's same role.
+    async fn add_temporary_role(&self, role: TemporaryRole) -> Result<()>;
+
+    async fn remove_temporary_role(&self, user_id: UserId, role_id: RoleId) -> Result<()>;
+
+    /// The temporary roles of every guild that expire by `now`.
+    async fn expired_roles(&self, now: DateTime<Utc>) -> Result<Vec<TemporaryRole>>;
+
+    /// Add `case` to the moderation log of its guild, returning the number it got. The
+    /// number `case` has is ignored.
+    async fn add_case(&self, case: &Case) -> Result<u32>;
+
+    /// The moderation log of `guild_id` with the notes on each case, oldest first.
+    async fn cases(&self, guild_id: GuildId) -> Result<Vec<Case>>;
+
+    /// Case `number` of `guild_id` with its notes.
+    async fn case(&self, guild_id: GuildId, number: u32) -> Result<Option<Case>>;
+
+    /// Replace the reason of case `number`, returning whether there is one.
+    async fn edit_case(&self, guild_id: GuildId, number: u32, reason: &str) -> Result<bool>;
+
+    /// Add `note` to case `number`, returning whether there is one.
+    async fn add_case_note(&self, guild_id: GuildId, number: u32, note: &Note) -> Result<bool>;
+
+    /// The timed cases of every guild that are up by `now`, without their notes.
+    async fn expired_cases(&self, now: DateTime<Utc>) -> Result<Vec<Case>>;
+
+    /// Stop undoing the timed `action`s taken against `user_id`.
+    async fn end_cases(&self, guild_id: GuildId, user_id: UserId, action: Action) -> Result<()>;
+
+    /// Insert or replace the registration of `component.id`.
+    async fn save_component(&self, component: StoredComponent) -> Result<()>;
+
+    /// Replace the state persisted with the registration of `id`, if there is one.
+    async fn save_component_state(&self, id: &str, state: Vec<u8>) -> Result<()>;
+
+    async fn forget_component(&self, id: &str) -> Result<()>;
+
+    async fn components(&self) -> Result<Vec<StoredComponent>>;
+
+    /// The key and JSON encoded value of every setting `guild_id` changed.
+    async fn guild_settings(&self, guild_id: GuildId) -> Result<Vec<(String, String)>>;
+
+    async fn set_guild_setting(&self, guild_id: GuildId, key: &str, value: &str) -> Result<()>;
+
+    async fn reset_guild_setting(&self, guild_id: GuildId, key: &str) -> Result<()>;
+
+    /// Replace everything `guild_id` has in the per-guild tables with `rows` in one
+    /// transaction, see [`GuildBundle::import`]. Nothing is imported when a temporary
+    /// role or component is another guild's, that's [`crate::Error::CommandMisuse`].
+    async fn import_guild(&self, guild_id: GuildId, rows: GuildRows<'_>) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend() {
+        assert_eq!(
+            Backend::of("postgres://tara@localhost/tara"),
+            Some(Backend::Postgres)
+        );
+        assert_eq!(Backend::of("postgresql://localhost"), Some(Backend::Postgres));
+        assert_eq!(
+            Backend::of("sqlite:///var/lib/tara/tara.db"),
+            Some(Backend::Sqlite)
+        );
+        assert_eq!(Backend::of("sqlite::memory:"), Some(Backend::Sqlite));
+        assert_eq!(Backend::of("mysql://tara@localhost/tara"), None);
+        assert_eq!(Backend::of("tara.db"), None);
+    }
+
+    #[tokio::test]
+    async fn test_applied_migrations() {
+        let database = open("sqlite::memory:").await.unwrap();
+        assert!(database.applied_migrations().await.unwrap().is_none());
+        database.migrate().await.unwrap();
+        let applied = database.applied_migrations().await.unwrap().unwrap();
+        assert_eq!(applied.len(), sqlite::MIGRATOR.iter().count());
+
+        // Looking at a database doesn't create it.
+        let path = std::env::temp_dir().join(format!("tara-{}.db", rand::random::<u32>()));
+        assert!(inspect(&format!("sqlite://{}", path.display())).await.is_err());
+        assert!(inspect_file(&path).await.is_err());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite() {
+        let database = open("sqlite::memory:").await.unwrap();
+        database.migrate().await.unwrap();
+        let guild_id = GuildId(id(1).unwrap());
+        let role_id = RoleId(id(2).unwrap());
+
+        database.add_guild(guild_id, Some("Test Guild")).await.unwrap();
+        assert!(database
+            .add_self_assignable_role(guild_id, role_id)
+            .await
+            .unwrap());
+        assert!(!database
+            .add_self_assignable_role(guild_id, role_id)
+            .await
+            .unwrap());
+        assert_eq!(database.self_assignable_roles(guild_id).await.unwrap(), [role_id]);
+        assert!(database.remove_self_assignable_role(role_id).await.unwrap());
+        assert!(database.self_assignable_roles(guild_id).await.unwrap().is_empty());
+
+        let component = StoredComponent {
+            id:            String::from("paginator/1"),
+            guild_id:      Some(guild_id),
+            channel_id:    ChannelId(id(3).unwrap()),
+            kind:          String::from("paginator"),
+            state:         vec![1, 2, 3],
+            authorization: None,
+            expiry:        Some(Utc.timestamp_opt(1_700_000_000, 0).unwrap()),
+        };
+        database.save_component(component.clone()).await.unwrap();
+        database.save_component(component.clone()).await.unwrap();
+        assert_eq!(database.components().await.unwrap(), [component.clone()]);
+        database
+            .save_component_state("paginator/1", vec![4, 5])
+            .await
+            .unwrap();
+        assert_eq!(
+            database.components().await.unwrap(),
+            [StoredComponent {
+                state: vec![4, 5],
+                ..component
+            }]
+        );
+        database.forget_component("paginator/1").await.unwrap();
+        assert!(database.components().await.unwrap().is_empty());
+
+        database
+            .set_guild_setting(guild_id, "safe_search", "\"strict\"")
+            .await
+            .unwrap();
+        database
+            .set_guild_setting(guild_id, "safe_search", "\"off\"")
+            .await
+            .unwrap();
+        assert_eq!(
+            database.guild_settings(guild_id).await.unwrap(),
+            [(String::from("safe_search"), String::from("\"off\""))]
+        );
+        database
+            .reset_guild_setting(guild_id, "safe_search")
+            .await
+            .unwrap();
+        assert!(database.guild_settings(guild_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_temporary_roles() {
+        let database = open("sqlite::memory:").await.unwrap();
+        database.migrate().await.unwrap();
+        let guild_id = GuildId(id(1).unwrap());
+        let expiry = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let role = |user, role| {
+            TemporaryRole {
+                guild_id,
+                user_id: UserId(id(user).unwrap()),
+                role_id: RoleId(id(role).unwrap()),
+                expiry,
+            }
+        };
+
+        database.add_guild(guild_id, None).await.unwrap();
+        database.add_temporary_role(role(2, 3)).await.unwrap();
+        database.add_temporary_role(role(2, 4)).await.unwrap();
+        // Taking the role again starts over.
+        let later = TemporaryRole {
+            expiry: expiry + chrono::Duration::hours(1),
+            ..role(2, 4)
+        };
+        database.add_temporary_role(later).await.unwrap();
+
+        assert_eq!(database.expired_roles(expiry).await.unwrap(), [role(2, 3)]);
+        assert_eq!(database.temporary_roles(guild_id).await.unwrap().len(), 2);
+        database
+            .remove_temporary_role(UserId(id(2).unwrap()), RoleId(id(3).unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(database.expired_roles(later.expiry).await.unwrap(), [later]);
+    }
+
+    #[tokio::test]
+    async fn test_cases() {
+        let database = open("sqlite::memory:").await.unwrap();
+        database.migrate().await.unwrap();
+        let (guild_id, other_guild_id) = (GuildId(id(1).unwrap()), GuildId(id(2).unwrap()));
+        let case = |guild_id| {
+            Case {
+                channel_id: Some(ChannelId(id(4).unwrap())),
+                content: Some(String::from("heck")),
+                created: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+                ..Case::new(guild_id, UserId(id(3).unwrap()), Action::Delete, "profane")
+            }
+        };
+
+        // Cases are numbered per guild.
+        assert_eq!(database.add_case(&case(guild_id)).await.unwrap(), 1);
+        assert_eq!(database.add_case(&case(guild_id)).await.unwrap(), 2);
+        assert_eq!(database.add_case(&case(other_guild_id)).await.unwrap(), 1);
+
+        let cases = database.cases(guild_id).await.unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(
+            cases[1],
+            Case {
+                number: 2,
+                ..case(guild_id)
+            }
+        );
+
+        let note = Note {
+            moderator_id: UserId(id(5).unwrap()),
+            text:         String::from("They apologized"),
+            created:      Utc.timestamp_opt(1_700_000_600, 0).unwrap(),
+        };
+        assert!(database.edit_case(guild_id, 1, "spam").await.unwrap());
+        assert!(database.add_case_note(guild_id, 1, &note).await.unwrap());
+        assert!(!database.add_case_note(guild_id, 3, &note).await.unwrap());
+        let edited = database.case(guild_id, 1).await.unwrap().unwrap();
+        assert_eq!(edited.reason, "spam");
+        assert_eq!(edited.notes, [note]);
+        assert_eq!(database.case(other_guild_id, 2).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_expired_cases() {
+        let database = open("sqlite::memory:").await.unwrap();
+        database.migrate().await.unwrap();
+        let guild_id = GuildId(id(1).unwrap());
+        let created = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let ban = |user| {
+            Case {
+                created,
+                ..Case::new(guild_id, UserId(id(user).unwrap()), Action::Ban, "spam")
+            }
+            .lasting(std::time::Duration::from_secs(3600))
+        };
+
+        database.add_case(&ban(2)).await.unwrap();
+        database.add_case(&ban(3)).await.unwrap();
+        let expiry = created + chrono::Duration::hours(1);
+        assert!(database.expired_cases(created).await.unwrap().is_empty());
+        assert_eq!(database.expired_cases(expiry).await.unwrap().len(), 2);
+
+        // Ended cases are left alone, whatever other action was taken.
+        database
+            .end_cases(guild_id, UserId(id(2).unwrap()), Action::Ban)
+            .await
+            .unwrap();
+        database
+            .end_cases(guild_id, UserId(id(3).unwrap()), Action::Timeout)
+            .await
+            .unwrap();
+        let expired = database.expired_cases(expiry).await.unwrap();
+        assert_eq!(expired, [Case { number: 2, ..ban(3) }]);
+    }
+
+    #[tokio::test]
+    async fn test_remove_guild() {
+        let database = open("sqlite::memory:").await.unwrap();
+        database.migrate().await.unwrap();
+        let (guild_id, other_guild_id) = (GuildId(id(1).unwrap()), GuildId(id(2).unwrap()));
+
+        database.add_guild(guild_id, None).await.unwrap();
+        database.add_guild(guild_id, Some("Renamed")).await.unwrap();
+        database.add_guild(guild_id, None).await.unwrap();
+        database.add_guild(other_guild_id, Some("Other")).await.unwrap();
+        database
+            .add_self_assignable_role(guild_id, RoleId(id(3).unwrap()))
+            .await
+            .unwrap();
+        database
+            .add_self_assignable_role(other_guild_id, RoleId(id(4).unwrap()))
+            .await
+            .unwrap();
+        database
+            .set_guild_setting(guild_id, "safe_search", "\"strict\"")
+            .await
+            .unwrap();
+        database
+            .save_component(StoredComponent {
+                id:            String::from("paginator/1"),
+                guild_id:      Some(guild_id),
+                channel_id:    ChannelId(id(5).unwrap()),
+                kind:          String::from("paginator"),
+                state:         vec![],
+                authorization: None,
+                expiry:        None,
+            })
+            .await
+            .unwrap();
+
+        database.remove_guild(guild_id).await.unwrap();
+        assert!(database.self_assignable_roles(guild_id).await.unwrap().is_empty());
+        assert!(database.guild_settings(guild_id).await.unwrap().is_empty());
+        assert!(database.components().await.unwrap().is_empty());
+        // Other guilds are left alone.
+        assert_eq!(
+            database
+                .self_assignable_roles(other_guild_id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
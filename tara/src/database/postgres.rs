@@ -0,0 +1,646 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serenity::all::{GuildId, RoleId, UserId};
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, Pool, Postgres, Transaction};
+
+use super::{
+    cases, id, lifetime, limit, role_groups, role_rules, Backend, CaseRow, ComponentRow, GuildRows, NoteRow,
+    RoleGroupRow, Storage, StoredComponent, TemporaryRoleRow,
+};
+use crate::{
+    moderation::{Action, Case, Note},
+    roles::{RoleGroup, RoleRules, TemporaryRole},
+    Error, IdUtil, Result,
+};
+
+/// The columns a [`CaseRow`] is read from.
+const CASE_COLUMNS: &str = "guild_id, number, user_id, moderator_id, action, reason, channel_id, content, \
+                            duration, created_date, expiry_date";
+
+pub(super) static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// [`Storage`] in a Postgres database.
+#[derive(Debug, Clone)]
+pub struct PostgresStorage {
+    pool: Pool<Postgres>,
+}
+
+impl PostgresStorage {
+    pub fn new(pool: Pool<Postgres>) -> Self { Self { pool } }
+
+    /// Connect to the database at `url`.
+    pub async fn connect(url: &str) -> Result<Self> {
+        Ok(Self::new(PgPoolOptions::new().connect(url).await?))
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    fn backend(&self) -> Backend { Backend::Postgres }
+
+    async fn migrate(&self) -> Result<()> {
+        MIGRATOR.run(&self.pool).await.map_err(sqlx::Error::from)?;
+        Ok(())
+    }
+
+    async fn applied_migrations(&self) -> Result<Option<Vec<(i64, bool, Vec<u8>)>>> {
+        let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        if !exists {
+            return Ok(None);
+        }
+        Ok(Some(
+            sqlx::query_as("SELECT version, success, checksum FROM _sqlx_migrations")
+                .fetch_all(&self.pool)
+                .await?,
+        ))
+    }
+
+    async fn add_guild(&self, guild_id: GuildId, name: Option<&str>) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO guilds (id, name) VALUES ($1, $2)
+            ON CONFLICT (id) DO UPDATE SET name = COALESCE(EXCLUDED.name, guilds.name)",
+        )
+        .bind(guild_id.toint())
+        .bind(name)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_guild(&self, guild_id: GuildId) -> Result<()> {
+        sqlx::query("DELETE FROM guilds WHERE id = $1")
+            .bind(guild_id.toint())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn self_assignable_roles(&self, guild_id: GuildId) -> Result<Vec<RoleId>> {
+        let ids: Vec<(i64,)> = sqlx::query_as("SELECT id FROM roles WHERE guild_id = $1")
+            .bind(guild_id.toint())
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(ids.into_iter().filter_map(|(x,)| id(x)).map(RoleId).collect())
+    }
+
+    async fn add_self_assignable_role(&self, guild_id: GuildId, role_id: RoleId) -> Result<bool> {
+        let inserted = sqlx::query(
+            "INSERT INTO roles (id, guild_id) VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            RETURNING id",
+        )
+        .bind(role_id.toint())
+        .bind(guild_id.toint())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(inserted.is_some())
+    }
+
+    async fn remove_self_assignable_role(&self, role_id: RoleId) -> Result<bool> {
+        let removed = sqlx::query("DELETE FROM roles WHERE id = $1")
+            .bind(role_id.toint())
+            .execute(&self.pool)
+            .await?;
+        Ok(removed.rows_affected() > 0)
+    }
+
+    async fn role_groups(&self, guild_id: GuildId) -> Result<Vec<RoleGroup>> {
+        let rows: Vec<RoleGroupRow> = sqlx::query_as(
+            "SELECT name, description, min_roles, max_roles, exclusive FROM role_groups
+            WHERE guild_id = $1 ORDER BY name",
+        )
+        .bind(guild_id.toint())
+        .fetch_all(&self.pool)
+        .await?;
+        let members: Vec<(i64, String)> =
+            sqlx::query_as("SELECT id, group_name FROM roles WHERE guild_id = $1 AND group_name IS NOT NULL")
+                .bind(guild_id.toint())
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(role_groups(rows, &members))
+    }
+
+    async fn save_role_group(&self, guild_id: GuildId, group: &RoleGroup) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("INSERT INTO guilds (id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(guild_id.toint())
+            .execute(&mut *transaction)
+            .await?;
+        insert_role_group(&mut transaction, guild_id.toint(), group).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn remove_role_group(&self, guild_id: GuildId, name: &str) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("UPDATE roles SET group_name = NULL WHERE guild_id = $1 AND group_name = $2")
+            .bind(guild_id.toint())
+            .bind(name)
+            .execute(&mut *transaction)
+            .await?;
+        let removed = sqlx::query("DELETE FROM role_groups WHERE guild_id = $1 AND name = $2")
+            .bind(guild_id.toint())
+            .bind(name)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(removed.rows_affected() > 0)
+    }
+
+    async fn set_role_group(&self, role_id: RoleId, group: Option<&str>) -> Result<bool> {
+        let updated = sqlx::query("UPDATE roles SET group_name = $2 WHERE id = $1")
+            .bind(role_id.toint())
+            .bind(group)
+            .execute(&self.pool)
+            .await?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn role_rules(&self, guild_id: GuildId) -> Result<Vec<RoleRules>> {
+        let rows: Vec<(i64, Option<i64>, Option<i64>)> = sqlx::query_as(
+            "SELECT id, required_role, lifetime FROM roles
+            WHERE guild_id = $1 AND (required_role IS NOT NULL OR lifetime IS NOT NULL)",
+        )
+        .bind(guild_id.toint())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(role_rules(rows))
+    }
+
+    async fn save_role_rules(&self, rules: &RoleRules) -> Result<bool> {
+        let updated = sqlx::query("UPDATE roles SET required_role = $2, lifetime = $3 WHERE id = $1")
+            .bind(rules.role.toint())
+            .bind(rules.requires.map(IdUtil::toint))
+            .bind(rules.lifetime.map(lifetime))
+            .execute(&self.pool)
+            .await?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn auto_roles(&self, guild_id: GuildId) -> Result<Vec<RoleId>> {
+        let ids: Vec<(i64,)> = sqlx::query_as("SELECT id FROM auto_roles WHERE guild_id = $1")
+            .bind(guild_id.toint())
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(ids.into_iter().filter_map(|(x,)| id(x)).map(RoleId).collect())
+    }
+
+    async fn add_auto_role(&self, guild_id: GuildId, role_id: RoleId) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("INSERT INTO guilds (id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(guild_id.toint())
+            .execute(&mut *transaction)
+            .await?;
+        let inserted = sqlx::query(
+            "INSERT INTO auto_roles (id, guild_id) VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            RETURNING id",
+        )
+        .bind(role_id.toint())
+        .bind(guild_id.toint())
+        .fetch_optional(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(inserted.is_some())
+    }
+
+    async fn remove_auto_role(&self, role_id: RoleId) -> Result<bool> {
+        let removed = sqlx::query("DELETE FROM auto_roles WHERE id = $1")
+            .bind(role_id.toint())
+            .execute(&self.pool)
+            .await?;
+        Ok(removed.rows_affected() > 0)
+    }
+
+    async fn temporary_roles(&self, guild_id: GuildId) -> Result<Vec<TemporaryRole>> {
+        let rows: Vec<TemporaryRoleRow> = sqlx::query_as(
+            "SELECT guild_id, user_id, role_id, expiry_date FROM temporary_roles WHERE guild_id = $1",
+        )
+        .bind(guild_id.toint())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(TemporaryRoleRow::into_role).collect())
+    }
+
+    async fn add_temporary_role(&self, role: TemporaryRole) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO temporary_roles (guild_id, user_id, role_id, expiry_date) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, role_id) DO UPDATE SET expiry_date = EXCLUDED.expiry_date",
+        )
+        .bind(role.guild_id.toint())
+        .bind(role.user_id.toint())
+        .bind(role.role_id.toint())
+        .bind(role.expiry.naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_temporary_role(&self, user_id: UserId, role_id: RoleId) -> Result<()> {
+        sqlx::query("DELETE FROM temporary_roles WHERE user_id = $1 AND role_id = $2")
+            .bind(user_id.toint())
+            .bind(role_id.toint())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn expired_roles(&self, now: DateTime<Utc>) -> Result<Vec<TemporaryRole>> {
+        let rows: Vec<TemporaryRoleRow> = sqlx::query_as(
+            "SELECT guild_id, user_id, role_id, expiry_date FROM temporary_roles WHERE expiry_date <= $1",
+        )
+        .bind(now.naive_utc())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(TemporaryRoleRow::into_role).collect())
+    }
+
+    async fn add_case(&self, case: &Case) -> Result<u32> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("INSERT INTO guilds (id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(case.guild_id.toint())
+            .execute(&mut *transaction)
+            .await?;
+        // Cases are numbered after the guild's last one, so they're added one at a time.
+        sqlx::query("SELECT id FROM guilds WHERE id = $1 FOR UPDATE")
+            .bind(case.guild_id.toint())
+            .execute(&mut *transaction)
+            .await?;
+        let number = insert_case(&mut transaction, case.guild_id.toint(), case, None).await?;
+        transaction.commit().await?;
+        Ok(number)
+    }
+
+    async fn cases(&self, guild_id: GuildId) -> Result<Vec<Case>> {
+        let rows: Vec<CaseRow> = sqlx::query_as(&format!(
+            "SELECT {CASE_COLUMNS} FROM moderation_log WHERE guild_id = $1 ORDER BY number"
+        ))
+        .bind(guild_id.toint())
+        .fetch_all(&self.pool)
+        .await?;
+        let notes: Vec<NoteRow> = sqlx::query_as(
+            "SELECT number, moderator_id, note, created_date FROM case_notes
+            WHERE guild_id = $1 ORDER BY created_date",
+        )
+        .bind(guild_id.toint())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(cases(rows, notes))
+    }
+
+    async fn case(&self, guild_id: GuildId, number: u32) -> Result<Option<Case>> {
+        let rows: Vec<CaseRow> = sqlx::query_as(&format!(
+            "SELECT {CASE_COLUMNS} FROM moderation_log WHERE guild_id = $1 AND number = $2"
+        ))
+        .bind(guild_id.toint())
+        .bind(limit(number))
+        .fetch_all(&self.pool)
+        .await?;
+        let notes: Vec<NoteRow> = sqlx::query_as(
+            "SELECT number, moderator_id, note, created_date FROM case_notes
+            WHERE guild_id = $1 AND number = $2 ORDER BY created_date",
+        )
+        .bind(guild_id.toint())
+        .bind(limit(number))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(cases(rows, notes).pop())
+    }
+
+    async fn edit_case(&self, guild_id: GuildId, number: u32, reason: &str) -> Result<bool> {
+        let updated =
+            sqlx::query("UPDATE moderation_log SET reason = $3 WHERE guild_id = $1 AND number = $2")
+                .bind(guild_id.toint())
+                .bind(limit(number))
+                .bind(reason)
+                .execute(&self.pool)
+                .await?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn add_case_note(&self, guild_id: GuildId, number: u32, note: &Note) -> Result<bool> {
+        let inserted = sqlx::query(
+            "INSERT INTO case_notes (guild_id, number, moderator_id, note, created_date)
+            SELECT guild_id, number, $3, $4, $5 FROM moderation_log WHERE guild_id = $1 AND number = $2",
+        )
+        .bind(guild_id.toint())
+        .bind(limit(number))
+        .bind(note.moderator_id.toint())
+        .bind(&note.text)
+        .bind(note.created.naive_utc())
+        .execute(&self.pool)
+        .await?;
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    async fn expired_cases(&self, now: DateTime<Utc>) -> Result<Vec<Case>> {
+        let rows: Vec<CaseRow> = sqlx::query_as(&format!(
+            "SELECT {CASE_COLUMNS} FROM moderation_log WHERE expiry_date <= $1"
+        ))
+        .bind(now.naive_utc())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(CaseRow::into_case).collect())
+    }
+
+    async fn end_cases(&self, guild_id: GuildId, user_id: UserId, action: Action) -> Result<()> {
+        sqlx::query(
+            "UPDATE moderation_log SET expiry_date = NULL
+            WHERE guild_id = $1 AND user_id = $2 AND action = $3 AND expiry_date IS NOT NULL",
+        )
+        .bind(guild_id.toint())
+        .bind(user_id.toint())
+        .bind(action.as_str())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn save_component(&self, component: StoredComponent) -> Result<()> {
+        let guild_id = component.guild_id.map(IdUtil::toint);
+
+        let mut transaction = self.pool.begin().await?;
+        if let Some(guild_id) = guild_id {
+            // The component can outlive the guild's row being created on `ready`.
+            sqlx::query("INSERT INTO guilds (id) VALUES ($1) ON CONFLICT DO NOTHING")
+                .bind(guild_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        sqlx::query(
+            "INSERT INTO registered_components
+            (componet_id, guild_id, channel_id, expiry_date, kind, state, authorization)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (componet_id) DO UPDATE
+            SET guild_id = $2, channel_id = $3, expiry_date = $4, kind = $5, state = $6, authorization = $7",
+        )
+        .bind(component.id)
+        .bind(guild_id)
+        .bind(component.channel_id.toint())
+        .bind(component.expiry.map(|x| x.naive_utc()))
+        .bind(component.kind)
+        .bind(component.state)
+        .bind(component.authorization)
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn save_component_state(&self, id: &str, state: Vec<u8>) -> Result<()> {
+        sqlx::query("UPDATE registered_components SET state = $2 WHERE componet_id = $1")
+            .bind(id)
+            .bind(state)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn forget_component(&self, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM registered_components WHERE componet_id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn components(&self) -> Result<Vec<StoredComponent>> {
+        let rows: Vec<ComponentRow> = sqlx::query_as(
+            "SELECT componet_id, guild_id, channel_id, kind, state, authorization, expiry_date
+            FROM registered_components",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().filter_map(ComponentRow::into_stored).collect())
+    }
+
+    async fn guild_settings(&self, guild_id: GuildId) -> Result<Vec<(String, String)>> {
+        Ok(
+            sqlx::query_as("SELECT key, value FROM guild_settings WHERE guild_id = $1")
+                .bind(guild_id.toint())
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    async fn set_guild_setting(&self, guild_id: GuildId, key: &str, value: &str) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("INSERT INTO guilds (id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(guild_id.toint())
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query(
+            "INSERT INTO guild_settings (guild_id, key, value) VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, key) DO UPDATE SET value = EXCLUDED.value",
+        )
+        .bind(guild_id.toint())
+        .bind(key)
+        .bind(value)
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn reset_guild_setting(&self, guild_id: GuildId, key: &str) -> Result<()> {
+        sqlx::query("DELETE FROM guild_settings WHERE guild_id = $1 AND key = $2")
+            .bind(guild_id.toint())
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn import_guild(&self, guild_id: GuildId, rows: GuildRows<'_>) -> Result<()> {
+        let guild_id = guild_id.toint();
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("INSERT INTO guilds (id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(guild_id)
+            .execute(&mut *transaction)
+            .await?;
+        let tables = [
+            "case_notes",
+            "roles",
+            "role_groups",
+            "auto_roles",
+            "temporary_roles",
+            "moderation_log",
+            "guild_settings",
+            "registered_components",
+        ];
+        for table in tables {
+            sqlx::query(&format!("DELETE FROM {table} WHERE guild_id = $1"))
+                .bind(guild_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        for role_id in rows.roles {
+            sqlx::query("INSERT INTO roles (id, guild_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(role_id.toint())
+                .bind(guild_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        for rules in rows.role_rules {
+            sqlx::query("UPDATE roles SET required_role = $2, lifetime = $3 WHERE id = $1 AND guild_id = $4")
+                .bind(rules.role.toint())
+                .bind(rules.requires.map(IdUtil::toint))
+                .bind(rules.lifetime.map(lifetime))
+                .bind(guild_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        for group in rows.role_groups {
+            insert_role_group(&mut transaction, guild_id, group).await?;
+            for role_id in &group.roles {
+                sqlx::query("UPDATE roles SET group_name = $2 WHERE id = $1 AND guild_id = $3")
+                    .bind(role_id.toint())
+                    .bind(&group.name)
+                    .bind(guild_id)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+        }
+        for role_id in rows.auto_roles {
+            sqlx::query("INSERT INTO auto_roles (id, guild_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(role_id.toint())
+                .bind(guild_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        // The guild's own rows are gone, so a conflict is with another guild's, which
+        // aren't touched.
+        for role in rows.temporary_roles {
+            let inserted = sqlx::query(
+                "INSERT INTO temporary_roles (guild_id, user_id, role_id, expiry_date) VALUES ($1, $2, $3, \
+                 $4)
+                ON CONFLICT (user_id, role_id) DO UPDATE
+                SET expiry_date = EXCLUDED.expiry_date WHERE temporary_roles.guild_id = EXCLUDED.guild_id",
+            )
+            .bind(guild_id)
+            .bind(role.user_id.toint())
+            .bind(role.role_id.toint())
+            .bind(role.expiry.naive_utc())
+            .execute(&mut *transaction)
+            .await?;
+            if inserted.rows_affected() == 0 {
+                return Err(Error::CommandMisuse(format!(
+                    "The temporary role {} of <@{}> belongs to another server",
+                    role.role_id, role.user_id
+                )));
+            }
+        }
+        for case in rows.cases {
+            insert_case(&mut transaction, guild_id, case, Some(case.number)).await?;
+            for note in &case.notes {
+                sqlx::query(
+                    "INSERT INTO case_notes (guild_id, number, moderator_id, note, created_date)
+                    VALUES ($1, $2, $3, $4, $5)",
+                )
+                .bind(guild_id)
+                .bind(limit(case.number))
+                .bind(note.moderator_id.toint())
+                .bind(&note.text)
+                .bind(note.created.naive_utc())
+                .execute(&mut *transaction)
+                .await?;
+            }
+        }
+        for (key, value) in rows.settings {
+            sqlx::query("INSERT INTO guild_settings (guild_id, key, value) VALUES ($1, $2, $3)")
+                .bind(guild_id)
+                .bind(key)
+                .bind(value)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        for component in rows.components {
+            let inserted = sqlx::query(
+                "INSERT INTO registered_components
+                (componet_id, guild_id, channel_id, expiry_date, kind, state, authorization)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (componet_id) DO UPDATE
+                SET channel_id = EXCLUDED.channel_id, expiry_date = EXCLUDED.expiry_date, kind = \
+                 EXCLUDED.kind,
+                state = EXCLUDED.state, authorization = EXCLUDED.authorization
+                WHERE registered_components.guild_id = EXCLUDED.guild_id",
+            )
+            .bind(&component.id)
+            .bind(guild_id)
+            .bind(component.channel_id.toint())
+            .bind(component.expiry.map(|x| x.naive_utc()))
+            .bind(&component.kind)
+            .bind(&component.state)
+            .bind(&component.authorization)
+            .execute(&mut *transaction)
+            .await?;
+            if inserted.rows_affected() == 0 {
+                return Err(Error::CommandMisuse(format!(
+                    "The component {} belongs to another server",
+                    component.id
+                )));
+            }
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+/// Insert `case` into `guild_id` as case `number`, or after its last case when that's
+/// `None`. Returns the number it got.
+async fn insert_case(
+    transaction: &mut Transaction<'_, Postgres>,
+    guild_id: i64,
+    case: &Case,
+    number: Option<u32>,
+) -> Result<u32> {
+    let (number,): (i32,) = sqlx::query_as(
+        "INSERT INTO moderation_log
+        (guild_id, number, user_id, moderator_id, action, reason, channel_id, content, duration,
+        created_date, expiry_date)
+        SELECT $1, COALESCE($10, MAX(number) + 1, 1), $2, $3, $4, $5, $6, $7, $8, $9, $11
+        FROM moderation_log WHERE guild_id = $1
+        RETURNING number",
+    )
+    .bind(guild_id)
+    .bind(case.user_id.toint())
+    .bind(case.moderator_id.map(IdUtil::toint))
+    .bind(case.action.as_str())
+    .bind(&case.reason)
+    .bind(case.channel_id.map(IdUtil::toint))
+    .bind(&case.content)
+    .bind(case.duration.map(lifetime))
+    .bind(case.created.naive_utc())
+    .bind(number.map(limit))
+    .bind(case.expiry.map(|x| x.naive_utc()))
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(u32::try_from(number).unwrap_or_default())
+}
+
+/// Insert `group` into `guild_id`, or replace the group with its name.
+async fn insert_role_group(
+    transaction: &mut Transaction<'_, Postgres>,
+    guild_id: i64,
+    group: &RoleGroup,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO role_groups (guild_id, name, description, min_roles, max_roles, exclusive)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (guild_id, name) DO UPDATE
+        SET description = EXCLUDED.description, min_roles = EXCLUDED.min_roles,
+        max_roles = EXCLUDED.max_roles, exclusive = EXCLUDED.exclusive",
+    )
+    .bind(guild_id)
+    .bind(&group.name)
+    .bind(&group.description)
+    .bind(limit(group.min_roles))
+    .bind(group.max_roles.map(limit))
+    .bind(group.exclusive)
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
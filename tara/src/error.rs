@@ -93,6 +93,9 @@ pub enum Error {
     #[error("DatabaseFileError: Couldn't find anywhere to open or create a database.")]
     DatabaseFile,
 
+    #[error("DatabaseURLError: \"{0}\" databases aren't supported, use Postgres or SQLite.")]
+    DatabaseUrl(String),
+
     #[error("ReadLineError: {0}")]
     ReadLine(rustyline::error::ReadlineError),
 
@@ -109,6 +112,18 @@ pub enum Error {
 
     #[error("SerenityError(backend framework): {0}")]
     SerenityErr(Box<serenity::Error>),
+
+    #[error("IncidentError: Something broke while handling this. Tell the host about incident {0}")]
+    Incident(String),
+
+    /// The member running a command lacks a permission it needs.
+    #[error("MissingPermissionsError: You need the {0} permission for this")]
+    MissingPermissions(String),
+
+    /// Discord refused a moderation action, usually because the member's highest role
+    /// isn't below Tara's.
+    #[error("ModerationError: {0}")]
+    Moderation(Box<serenity::Error>),
 }
 
 impl From<io::Error> for Error {
@@ -154,6 +169,10 @@ impl Error {
             #[cfg(feature = "music")]
             Error::YoutubeInfo(_) => 30,
             Error::SerenityErr(_) => 31,
+            Error::Incident(_) => 32,
+            Error::DatabaseUrl(_) => 33,
+            Error::MissingPermissions(_) => 34,
+            Error::Moderation(_) => 35,
         }
     }
 
@@ -0,0 +1,77 @@
+//! Keeps a panicking command or component handler from silently killing the interaction
+//! task. Handlers are run on their own task and a panic is turned into an [`Incident`]
+//! that gets logged and can be reported back to the user.
+use std::{any::Any, fmt::Display, future::Future};
+
+use tracing::error;
+
+/// A panic that was caught while running a handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Incident {
+    /// Short identifier shown to the user and written to the log so the host can find
+    /// what happened.
+    pub id:      String,
+    /// The panic message, if there was one.
+    pub message: String,
+}
+
+/// Run `future` on a separate task, catching any panic that happens while it runs.
+/// `what` describes the handler in the incident log.
+///
+/// # Errors
+///
+/// Returns an [`Incident`] if the future panicked or was cancelled.
+pub async fn catch_panic<F, T>(what: impl Display, future: F) -> Result<T, Incident>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let error = match tokio::spawn(future).await {
+        Ok(x) => return Ok(x),
+        Err(e) => e,
+    };
+
+    let message = if error.is_panic() {
+        panic_message(error.into_panic().as_ref())
+    } else {
+        String::from("The task was cancelled")
+    };
+    let id = format!("{:08X}", rand::random::<u32>());
+    error!(target: "tara::incident", "Incident {id}: {what} panicked: {message}");
+
+    Err(Incident { id, message })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::from("Unknown panic payload")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_catch_panic_passes_through() {
+        assert_eq!(catch_panic("test", async { 5 }).await, Ok(5));
+    }
+
+    #[tokio::test]
+    async fn test_catch_panic_catches() {
+        let incident = catch_panic::<_, ()>("test", async { panic!("malformed") })
+            .await
+            .unwrap_err();
+        assert_eq!(incident.message, "malformed");
+        assert_eq!(incident.id.len(), 8);
+
+        let incident = catch_panic::<_, ()>("test", async { panic!("{}", String::from("formatted")) })
+            .await
+            .unwrap_err();
+        assert_eq!(incident.message, "formatted");
+    }
+}
@@ -1,14 +1,38 @@
+use std::{num::NonZeroU64, sync::Arc};
+
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use csv_async::{AsyncReaderBuilder, StringRecord};
+use serenity::{all::GuildId, cache::Cache, http::Http};
 use tara_util::{
     ipc::{ActionMessage, ActionMessageReceiver, ResponseMessage},
     logging, paths,
+    presence::Presence,
 };
-use tokio::fs::File;
+use tokio::{fs::File, sync::watch};
 
-#[derive(Debug, Clone)]
-pub struct ActionReceiver {}
+use crate::{
+    commands::refresh_role_panels,
+    componet::ComponentMap,
+    database::{Database, GuildBundle},
+    settings::GuildSettings,
+};
+
+#[derive(Clone)]
+pub struct ActionReceiver {
+    /// Where presence changes go, to be shown by [`crate::presence::rotate`].
+    pub presence:      Arc<watch::Sender<Presence>>,
+    /// The presence from the configuration.
+    pub configured:    Presence,
+    pub database:      Database,
+    pub settings:      GuildSettings,
+    /// Where imported components are listened for.
+    pub component_map: ComponentMap,
+    /// Where imported bundles are checked against the guilds they're for.
+    pub cache:         Arc<Cache>,
+    /// What rebuilds the role panels of imported guilds.
+    pub http:          Arc<Http>,
+}
 
 
 #[async_trait]
@@ -28,14 +52,78 @@ impl ActionMessageReceiver for ActionReceiver {
                     Err(e) => e,
                 };
             }
+            ActionMessage::SetPresence(presence) => {
+                self.presence.send_replace(presence);
+                ResponseMessage::ActionCompleted
+            }
+            ActionMessage::ResetPresence => {
+                self.presence.send_replace(self.configured.clone());
+                ResponseMessage::ActionCompleted
+            }
+            ActionMessage::ExportGuild { guild_id, format } => {
+                let Some(guild_id) = NonZeroU64::new(guild_id).map(GuildId) else {
+                    return ResponseMessage::ActionFailed(String::from("0 isn't a guild ID"));
+                };
+                match GuildBundle::export(&self.database, guild_id).await {
+                    Ok(bundle) => {
+                        bundle
+                            .write(format)
+                            .map_or_else(ResponseMessage::from, ResponseMessage::GuildBundle)
+                    }
+                    Err(e) => e.into(),
+                }
+            }
+            ActionMessage::ImportGuild { guild_id, bundle } => {
+                match self.import_guild(guild_id, &bundle).await {
+                    Ok(()) => ResponseMessage::ActionCompleted,
+                    Err(e) => e.into(),
+                }
+            }
         }
     }
 }
 
+impl ActionReceiver {
+    async fn import_guild(&self, guild_id: u64, bundle: &str) -> crate::Result<()> {
+        let guild_id = NonZeroU64::new(guild_id)
+            .map(GuildId)
+            .ok_or_else(|| crate::Error::CommandMisuse(String::from("0 isn't a guild ID")))?;
+        let bundle = GuildBundle::parse(bundle)?;
+        // Validated against a copy so the cache isn't locked while the database is.
+        let guild = self
+            .cache
+            .guild(guild_id)
+            .map(|x| x.to_owned())
+            .ok_or_else(|| crate::Error::CommandMisuse(format!("Tara isn't in guild {guild_id}")))?;
+        bundle.validate(&guild)?;
+        bundle.import(&self.database).await?;
+        self.settings.forget(guild_id).await;
+        if let Err(e) = self.component_map.reload(guild_id).await {
+            tracing::warn!("Couldn't reload the components of guild {guild_id}: {e}");
+        }
+        let refreshed = refresh_role_panels(&self.http, &self.database, &self.component_map, &guild).await;
+        if let Err(e) = refreshed {
+            tracing::warn!("Couldn't refresh the role panels of guild {guild_id}: {e}");
+        }
+        Ok(())
+    }
+}
+
 async fn get_command_logs1(
     lower_cutoff: DateTime<Utc>,
     upper_cutoff: DateTime<Utc>,
 ) -> Result<ResponseMessage, ResponseMessage> {
+    match read_command_logs(lower_cutoff, upper_cutoff).await {
+        Ok(x) => Ok(ResponseMessage::CommandLogs(x)),
+        Err(e) => Err(ResponseMessage::ActionFailed(e.to_string())),
+    }
+}
+
+/// The commands logged between `lower_cutoff` and `upper_cutoff`.
+pub(crate) async fn read_command_logs(
+    lower_cutoff: DateTime<Utc>,
+    upper_cutoff: DateTime<Utc>,
+) -> anyhow::Result<Vec<logging::LoggedCommandEvent>> {
     let mut deserializer = AsyncReaderBuilder::new()
         .has_headers(false)
         .create_deserializer(File::open(paths::TARA_COMMAND_LOG_PATH.as_path()).await?);
@@ -47,7 +135,7 @@ async fn get_command_logs1(
             command_events.push(command_event);
         }
     }
-    Ok(ResponseMessage::CommandLogs(command_events))
+    Ok(command_events)
 }
 
 // TODO: Test
@@ -1,5 +1,4 @@
 use std::{
-    collections::HashMap,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -20,10 +19,13 @@ use tracing::info;
 // use flume
 use crate::{
     commands::CommandArguments,
-    componet::{self, Component},
+    componet::{Authorization, Component, ComponentMap, DynComponent, Registration},
     config,
 };
 
+/// The components registered for inference sessions.
+pub(crate) const COMPONENTS: &[DynComponent] = &[&cancel_handler];
+
 lazy_static! {
     static ref PARAMS: InferenceParameters = InferenceParameters {
         sampler: Arc::new(llm::samplers::TopPTopK {
@@ -42,8 +44,6 @@ lazy_static! {
         ..Default::default()
     };
     static ref TYPING: Arc<Mutex<Option<Typing>>> = Arc::new(Mutex::new(None));
-
-    static ref CANCELATION_MAP: Arc<Mutex<HashMap<MessageId, watch::Sender<bool>>>> = Arc::new(Mutex::new(HashMap::new()));
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -107,16 +107,16 @@ impl Llm {
             let cancelr = cancel_rx.clone();
             let channel_id = discord_message.channel_id;
             let message_id = discord_message.message_id;
-            let component_map = discord_message.component_map;
 
             let http = discord_message.http;
             let mut printer = DiscordPrinter::new(
                 message_id,
                 channel_id,
                 discord_message.guild_id,
+                discord_message.user_id,
                 token_rx,
-                component_map.clone(),
                 http.clone(),
+                discord_message.component_map,
             );
             let printer = tokio::spawn(async move { printer.print(cancel_tx).await });
 
@@ -183,8 +183,7 @@ impl Llm {
             }
             match printer.await.context("DiscordPrinter panicked").flatten() {
                 Ok((sent_message, content)) => {
-                    let canceled = CANCELATION_MAP.lock().await.remove(&sent_message);
-                    if let Some(canceled) = canceled && *canceled.borrow() {
+                    if *cancel_rx.borrow() {
                         let edit = EditMessage::new()
                             .content(content + "…\n**Canceled**!")
                             .components(vec![]);
@@ -205,10 +204,11 @@ impl Llm {
 #[derive(Clone)]
 pub struct LlmMessage {
     http:                 Arc<Http>,
-    component_map:        componet::ComponentMap,
+    component_map:        ComponentMap,
     guild_id:             Option<GuildId>,
     message_id:           MessageId,
     channel_id:           ChannelId,
+    user_id:              UserId,
     pub(super) content:   String,
     pub(super) user_name: String,
 }
@@ -219,6 +219,7 @@ impl std::fmt::Debug for LlmMessage {
             .field("guild_id", &self.guild_id)
             .field("message_id", &self.message_id)
             .field("channel_id", &self.channel_id)
+            .field("user_id", &self.user_id)
             .field("content", &self.content)
             .field("user_name", &self.user_name)
             .finish()
@@ -227,16 +228,17 @@ impl std::fmt::Debug for LlmMessage {
 
 impl LlmMessage {
     #[inline]
-    pub fn new(content: impl AsRef<str>, http: Arc<Http>, cmap: componet::ComponentMap, m: &Message) -> Self {
+    pub fn new(content: impl AsRef<str>, http: Arc<Http>, component_map: ComponentMap, m: &Message) -> Self {
         let content = content.as_ref().trim().to_string();
         Self {
             content,
             http,
+            component_map,
             message_id: m.id,
             channel_id: m.channel_id,
             guild_id: m.guild_id,
+            user_id: m.author.id,
             user_name: m.author.name.clone(),
-            component_map: cmap,
         }
     }
 }
@@ -255,11 +257,14 @@ struct DiscordPrinter {
     http:          Arc<Http>,
     /// LLM Token receiver.
     token_rx:      flume::Receiver<String>,
-    component_map: componet::ComponentMap,
+    /// Where the cancel button is registered.
+    component_map: ComponentMap,
 
     guild_id:     Option<GuildId>,
     message_id:   MessageId,
     channel_id:   ChannelId,
+    /// Who the reply is for, they're the only one that can cancel it.
+    user_id:      UserId,
     /// The reply message the `DiscordPrinter` sent
     sent_message: Option<MessageId>,
 
@@ -275,19 +280,21 @@ impl DiscordPrinter {
         mid: MessageId,
         cid: ChannelId,
         gid: Option<GuildId>,
+        uid: UserId,
         rx: flume::Receiver<String>,
-        cmap: componet::ComponentMap,
         http: Arc<Http>,
+        component_map: ComponentMap,
     ) -> Self {
         Self {
             http,
             token_rx: rx,
+            component_map,
             guild_id: gid,
+            user_id: uid,
             channel_id: cid,
             message_id: mid,
             last_update: Instant::now(),
             sent_message: None,
-            component_map: cmap,
             update_cooldown: Duration::from_millis(500),
             response: String::new(),
         }
@@ -311,10 +318,17 @@ impl DiscordPrinter {
                     self.update().await?;
                     *TYPING.lock().await = Some(self.http.start_typing(self.channel_id));
                     if let Some(id) = self.sent_message && !have_registered {
-                        let cid = format!("llm-cancel-message:{}/{id}", self.channel_id);
-                        self.component_map.insert(cid, &cancel_handler, None).await;
-                        CANCELATION_MAP.lock().await.insert(id, cancel_tx.unwrap());
-                        cancel_tx = None;
+                        // Generating can take a while, the button is timed out once it's done.
+                        let registration = Registration::new(self.guild_id, self.channel_id)
+                            .authorize(Authorization::User(self.user_id));
+                        self.component_map
+                            .insert(
+                                cancel_id(self.channel_id, id),
+                                cancel_handler.with_state(cancel_tx.take().unwrap()),
+                                Some(chrono::Duration::hours(1)),
+                                registration,
+                            )
+                            .await;
                         have_registered = true;
                     }
                 }
@@ -343,15 +357,18 @@ impl DiscordPrinter {
         *TYPING.lock().await = None;
         self.update().await?;
 
-
-        self.component_map
-            .timeout(format!(
-                "llm-cancel-message:{}/{}",
-                self.channel_id,
-                self.sent_message.unwrap()
-            ))
+        // There's nothing to cancel anymore.
+        let sent_message = self.sent_message.unwrap();
+        EditMessage::new()
+            .components(vec![])
+            .execute(&self.http, (self.channel_id, sent_message))
             .await?;
-        Ok((self.sent_message.unwrap(), self.response.clone()))
+        if have_registered {
+            self.component_map
+                .timeout(cancel_id(self.channel_id, sent_message))
+                .await?;
+        }
+        Ok((sent_message, self.response.clone()))
     }
 
     async fn update(&mut self) -> anyhow::Result<()> {
@@ -382,9 +399,12 @@ impl DiscordPrinter {
     }
 }
 
+#[inline]
+fn cancel_id(cid: ChannelId, mid: MessageId) -> String { format!("llm-cancel/{cid}/{mid}") }
+
 #[inline]
 fn build_components(cid: ChannelId, mid: MessageId, canceling: bool) -> Vec<CreateActionRow> {
-    let mut cancel = CreateButton::new(format!("llm-cancel-message:{cid}/{mid}"))
+    let mut cancel = CreateButton::new(cancel_id(cid, mid))
         .style(ButtonStyle::Danger)
         .label("Cancel")
         .emoji(ReactionType::Unicode(String::from("🇽")));
@@ -395,13 +415,13 @@ fn build_components(cid: ChannelId, mid: MessageId, canceling: bool) -> Vec<Crea
     vec![CreateActionRow::Buttons(vec![cancel])]
 }
 
-#[component]
-async fn cancel_handler(interaction: ComponentInteraction, _args: CommandArguments) -> anyhow::Result<()> {
-    let id = interaction.message.id;
-    let map = CANCELATION_MAP.lock().await;
-    let sender = map
-        .get(&id)
-        .context(format!("No message id '{id}' found in cancelation map",))?;
-    sender.send(true).context("Why is this closed?")?;
+// Sessions don't survive a restart, so neither does the button.
+#[component(state = watch::Sender<bool>, route = "llm-cancel/{channel}/{message}")]
+async fn cancel_handler(
+    cancel: &watch::Sender<bool>,
+    _interaction: ComponentInteraction,
+    _args: CommandArguments,
+) -> anyhow::Result<()> {
+    cancel.send(true).context("Why is this closed?")?;
     Ok(())
 }
@@ -1,30 +1,40 @@
 #![feature(stmt_expr_attributes, type_alias_impl_trait, result_flattening, let_chains)]
-use std::{num::NonZeroU64, path::PathBuf, str::FromStr, sync::Arc};
+use std::{num::NonZeroU64, path::PathBuf, str::FromStr, sync::Arc, time::Instant};
 
 use anyhow::Context as AnyhowContextWtfRust;
-use serenity::{all::*, async_trait, client, gateway::ActivityData, prelude::Context, Client};
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use serenity::{all::*, async_trait, client, prelude::Context, Client};
 use structopt::{
     clap::AppSettings::{ColorAuto, ColoredHelp, VersionlessSubcommands},
     StructOpt,
 };
 use tara_util::{ipc as ipcutil, logging as logutil, paths};
-use tokio::task;
-use tracing::{error, info, Level};
+use tokio::{
+    sync::{watch, Mutex},
+    task,
+};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::{filter, prelude::*, Layer};
 
 mod error;
 pub use error::{Error, Result};
 
 use crate::ipc::ActionReceiver;
+mod audit;
+mod automod;
 mod commands;
 mod componet;
 mod config;
+mod database;
 mod defaults;
+mod incident;
 mod ipc;
 #[cfg(feature = "ai")]
 mod llm;
 mod logging;
+mod moderation;
+mod presence;
+mod roles;
+mod settings;
 
 const NAME: &str = "Tara";
 const REPO_URL: &str = env!("CARGO_PKG_REPOSITORY");
@@ -35,6 +45,7 @@ const INTENTS: GatewayIntents = GatewayIntents::GUILD_MESSAGES
     .union(GatewayIntents::DIRECT_MESSAGES)
     .union(GatewayIntents::MESSAGE_CONTENT)
     .union(GatewayIntents::GUILDS)
+    .union(GatewayIntents::GUILD_MEMBERS)
     .union(GatewayIntents::GUILD_VOICE_STATES);
 
 #[derive(StructOpt, Debug, Clone)]
@@ -51,6 +62,17 @@ struct Options {
 
     #[structopt(short, long, name = "LOGLEVEL")]
     log_level: Option<LogLevel>,
+
+    #[structopt(subcommand)]
+    command: Option<Subcommand>,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+enum Subcommand {
+    /// Check the configuration and the database it points to for mistakes, then exit.
+    CheckConfig,
+    /// Set Tara up by answering some questions, then exit.
+    Init,
 }
 
 
@@ -113,7 +135,11 @@ impl serenity::prelude::TypeMapKey for HttpKey {
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> anyhow::Result<()> {
-    let Options { config, log_level } = Options::from_args();
+    let Options {
+        config,
+        log_level,
+        command,
+    } = Options::from_args();
     let log_level = log_level.unwrap_or(LogLevel::Info);
     let stdout = tracing_subscriber::fmt::layer()
         .pretty()
@@ -143,16 +169,31 @@ async fn main() -> anyhow::Result<()> {
         anyhow::Ok(())
     })
     .await??;
+
+    match command {
+        Some(Subcommand::CheckConfig) => return config::check::run(config.as_deref()).await,
+        Some(Subcommand::Init) => return config::init::run(config.as_deref()).await,
+        None => {}
+    }
+
     let config = Arc::new(config::Configuration::parse(config).await?);
+    let report = config::check::Report::validate(&config);
+    for diagnostic in report.diagnostics() {
+        match diagnostic.severity {
+            config::check::Severity::Error => error!("{diagnostic}"),
+            config::check::Severity::Warning => warn!("{diagnostic}"),
+            config::check::Severity::Ok => {}
+        }
+    }
+    if report.has_errors() {
+        anyhow::bail!("The configuration has mistakes, run `tara check-config` for details");
+    }
 
-    let postgres = config
-        .secrets
-        .postgres
-        .as_deref()
-        .context("Gimme a postgres database please!")?;
-    let database = PgPoolOptions::new().connect(postgres).await?;
-    sqlx::migrate!("./migrations")
-        .run(&database)
+    let database = database::open_configured(config.secrets.database.as_deref())
+        .await
+        .context("Couldn't open the database!")?;
+    database
+        .migrate()
         .await
         .context("Couldn't run database migrations!")?;
 
@@ -167,15 +208,7 @@ async fn main() -> anyhow::Result<()> {
     });
     info!("Initialized command logger");
 
-    let receiver = Arc::new(ActionReceiver {});
-    task::spawn(async move {
-        let receiver = receiver.clone();
-        if let Err(e) = ipcutil::start_server(receiver.as_ref()).await {
-            error!("IPC: {e}");
-        };
-    });
-    info!("Initialized IPC server");
-
+    let (presence, presence_rx) = watch::channel(config.presence.clone());
 
     #[cfg(feature = "ai")]
     let llm_channel = if let Some(llm_config) = config.ai.as_ref().and_then(|x| x.llm.clone()) {
@@ -193,12 +226,19 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    let guild_settings = settings::GuildSettings::new(database.clone());
+    let component_map = componet::ComponentMap::new(Some(database.clone()));
     let event_handler = EventHandler {
         config: config.clone(),
         logger: logger.clone(),
         error_messages: load_error_messages(config.clone()).await,
-        component_map: componet::ComponentMap::new(),
-        database,
+        component_map: component_map.clone(),
+        settings: guild_settings.clone(),
+        database: database.clone(),
+        presence: presence_rx,
+        presence_task: Mutex::new(None),
+        started: Instant::now(),
+        recent: audit::Recent::default(),
         #[cfg(feature = "ai")]
         llm_channel,
     };
@@ -212,6 +252,43 @@ async fn main() -> anyhow::Result<()> {
     )
     .await?;
 
+    // Pick up the components registered before the restart, expired ones get cleaned up by
+    // the watcher.
+    if let Err(e) = component_map.restore().await {
+        error!("Couldn't restore components: {e}");
+    }
+
+    // These outlive every connection, so they're started once rather than on `ready`.
+    let (watched, http, cache) = (component_map.clone(), client.http.clone(), client.cache.clone());
+    task::spawn(async move {
+        if let Err(e) = watched.timeout_watcher(http, cache).await {
+            error!("{e}");
+        }
+    });
+    task::spawn(roles::expiry_watcher(client.http.clone(), database.clone()));
+    task::spawn(moderation::expiry_watcher(
+        client.http.clone(),
+        database.clone(),
+        guild_settings.clone(),
+    ));
+
+    let receiver = Arc::new(ActionReceiver {
+        presence: Arc::new(presence),
+        configured: config.presence.clone(),
+        database,
+        settings: guild_settings,
+        component_map,
+        cache: client.cache.clone(),
+        http: client.http.clone(),
+    });
+    task::spawn(async move {
+        let receiver = receiver.clone();
+        if let Err(e) = ipcutil::start_server(receiver.as_ref()).await {
+            error!("IPC: {e}");
+        };
+    });
+    info!("Initialized IPC server");
+
     let _ = client.start().await.map_err(|why| error!("Error: {:?}", why));
 
     Ok(())
@@ -245,9 +322,17 @@ async fn build_client(
 struct EventHandler {
     config:         Arc<config::Configuration>,
     error_messages: Arc<config::ErrorMessages>,
-    database:       Pool<Postgres>,
+    database:       database::Database,
+    settings:       settings::GuildSettings,
     logger:         logutil::CommandLogger,
     component_map:  componet::ComponentMap,
+    /// The presence to show, changed over IPC.
+    presence:       watch::Receiver<tara_util::presence::Presence>,
+    /// What shows the presence for the current connection.
+    presence_task:  Mutex<Option<task::JoinHandle<()>>>,
+    started:        Instant,
+    /// Recent messages, for the audit log to show what edited and deleted ones said.
+    recent:         audit::Recent,
     #[cfg(feature = "ai")]
     llm_channel:    Option<flume::Sender<llm::LlmMessage>>,
 }
@@ -261,13 +346,14 @@ impl client::EventHandler for EventHandler {
                     .guild_id
                     .and_then(|guild_id| guild_id.to_guild_cached(&context.cache).map(|x| x.to_owned()));
 
-                let args = commands::CommandArguments {
-                    context: Arc::new(context),
+                let args = commands::CommandArguments::new(
+                    &context,
                     guild,
-                    config: self.config.clone(),
-                    component_map: self.component_map.clone(),
-                    database: self.database.clone(),
-                };
+                    self.config.clone(),
+                    self.component_map.clone(),
+                    self.database.clone(),
+                    self.settings.clone(),
+                );
 
                 let id = component.data.custom_id.clone();
                 match self.component_map.run(&id, component, args).await {
@@ -280,6 +366,27 @@ impl client::EventHandler for EventHandler {
                     None => tracing::warn!("No component handler regestered for component '{id}'"),
                 };
             }
+            Interaction::Modal(modal) => {
+                let guild: Option<Guild> = modal
+                    .guild_id
+                    .and_then(|guild_id| guild_id.to_guild_cached(&context.cache).map(|x| x.to_owned()));
+
+                let args = commands::CommandArguments::new(
+                    &context,
+                    guild,
+                    self.config.clone(),
+                    self.component_map.clone(),
+                    self.database.clone(),
+                    self.settings.clone(),
+                );
+
+                let id = modal.data.custom_id.clone();
+                match self.component_map.run_modal(&id, modal, args).await {
+                    Some(Err(e)) => tracing::error!("Error running modal handler for modal '{id}': {e}"),
+                    Some(Ok(_)) => tracing::trace!("Ran modal handler for modal '{id}'"),
+                    None => tracing::warn!("No modal handler routed for modal '{id}'"),
+                };
+            }
             Interaction::Command(command) => {
                 let guild: Option<Guild> = command
                     .guild_id
@@ -294,6 +401,23 @@ impl client::EventHandler for EventHandler {
                     self.logger.clone(),
                     self.component_map.clone(),
                     self.database.clone(),
+                    self.settings.clone(),
+                )
+                .await;
+            }
+            Interaction::Autocomplete(command) => {
+                let guild: Option<Guild> = command
+                    .guild_id
+                    .and_then(|guild_id| guild_id.to_guild_cached(&context.cache).map(|x| x.to_owned()));
+
+                commands::autocomplete(
+                    context,
+                    command,
+                    guild,
+                    self.config.clone(),
+                    self.component_map.clone(),
+                    self.database.clone(),
+                    self.settings.clone(),
                 )
                 .await;
             }
@@ -303,7 +427,15 @@ impl client::EventHandler for EventHandler {
 
     async fn ready(&self, context: Context, ready: Ready) {
         info!("{} is connected!", ready.user.name);
-        context.set_activity(Some(ActivityData::watching("El-Wumbus/Tara on GitHub")));
+        // Each connection gets its own context, so the presence is shown through the newest one.
+        let rotation = task::spawn(presence::rotate(
+            context.clone(),
+            self.presence.clone(),
+            self.started,
+        ));
+        if let Some(previous) = self.presence_task.lock().await.replace(rotation) {
+            previous.abort();
+        }
 
         info!("Registering commands...");
         // For each command in the map, run `.register()` on it.
@@ -322,53 +454,241 @@ impl client::EventHandler for EventHandler {
 
         for guild_id in guilds {
             let guild_name = context.cache.guild(guild_id).map(|x| x.name.clone());
-            let insert = if let Some(guild_name) = guild_name {
-                sqlx::query!(
-                    "INSERT INTO guilds (id, name) VALUES ($1, $2)
-                    ON CONFLICT DO NOTHING",
-                    guild_id.toint(),
-                    guild_name
-                )
-                .execute(&self.database)
-                .await
-            } else {
-                sqlx::query!(
-                    "INSERT INTO guilds (id) VALUES ($1)
-                    ON CONFLICT DO NOTHING",
-                    guild_id.0.toint()
-                )
-                .execute(&self.database)
-                .await
-            };
-
-            if let Err(e) = insert {
+            if let Err(e) = self.database.add_guild(guild_id, guild_name.as_deref()).await {
                 error!("DATABASE: {e}");
             };
         }
+    }
+
+    async fn guild_create(&self, _context: Context, guild: Guild, _is_new: Option<bool>) {
+        // This comes for every guild after `ready`, and whenever Tara joins one.
+        if let Err(e) = self.database.add_guild(guild.id, Some(&guild.name)).await {
+            error!("Couldn't add guild {}: {e}", guild.id);
+        }
+    }
 
+    async fn guild_update(&self, _context: Context, _old: Option<Guild>, guild: PartialGuild) {
+        if let Err(e) = self.database.add_guild(guild.id, Some(&guild.name)).await {
+            error!("Couldn't rename guild {}: {e}", guild.id);
+        }
+    }
 
-        let component_map = self.component_map.clone();
-        let http = context.http.clone();
-        let cache = context.cache.clone();
-        task::spawn(async move {
-            if let Err(e) = component_map.timeout_watcher(http, cache).await {
-                error!("{e}");
+    async fn guild_delete(&self, _context: Context, incomplete: UnavailableGuild, _full: Option<Guild>) {
+        // Guilds are unavailable during outages, their data is only deleted when Tara leaves.
+        if incomplete.unavailable {
+            return;
+        }
+        info!("Left guild {}, deleting its data", incomplete.id);
+        if let Err(e) = self.database.remove_guild(incomplete.id).await {
+            error!("Couldn't remove guild {}: {e}", incomplete.id);
+        }
+        self.settings.forget(incomplete.id).await;
+    }
+
+    async fn guild_role_delete(
+        &self,
+        context: Context,
+        guild_id: GuildId,
+        role_id: RoleId,
+        role: Option<Role>,
+    ) {
+        let embed = audit::role_delete(role_id, role.as_ref());
+        self.audit(&context, guild_id, audit::Event::Roles, embed).await;
+        match self.database.remove_self_assignable_role(role_id).await {
+            Ok(true) => self.refresh_role_panels(&context, guild_id).await,
+            Ok(false) => {}
+            Err(e) => error!("Couldn't remove deleted role {role_id}: {e}"),
+        }
+        if let Err(e) = self.database.remove_auto_role(role_id).await {
+            error!("Couldn't remove deleted auto-role {role_id}: {e}");
+        }
+        // Roles that required it can be taken by anyone now.
+        match self.database.role_rules(guild_id).await {
+            Ok(all) => {
+                for mut rules in all.into_iter().filter(|x| x.requires == Some(role_id)) {
+                    rules.requires = None;
+                    if let Err(e) = self.database.save_role_rules(&rules).await {
+                        error!("Couldn't drop the requirement of role {}: {e}", rules.role);
+                    }
+                }
             }
-        });
+            Err(e) => error!("Couldn't read the role rules of guild {guild_id}: {e}"),
+        }
+        match self.settings.get(guild_id).await {
+            Ok(values) if values.dj_role() == Some(role_id) => {
+                if let Err(e) = self.settings.reset(guild_id, &settings::DJ_ROLE).await {
+                    error!("Couldn't reset the DJ role of guild {guild_id}: {e}");
+                }
+            }
+            Ok(_) => {}
+            Err(e) => error!("Couldn't read the settings of guild {guild_id}: {e}"),
+        }
+    }
+
+    async fn guild_member_addition(&self, context: Context, mut member: Member) {
+        let embed = audit::member_join(&member.user);
+        self.audit(&context, member.guild_id, audit::Event::Joins, embed)
+            .await;
+
+        let roles = match self.database.auto_roles(member.guild_id).await {
+            Ok(roles) if roles.is_empty() => return,
+            Ok(roles) => roles,
+            Err(e) => {
+                error!("Couldn't read the auto-roles of guild {}: {e}", member.guild_id);
+                return;
+            }
+        };
+        if let Err(e) = member.add_roles(&context.http, &roles).await {
+            error!(
+                "Couldn't give {} the auto-roles of guild {}: {e}",
+                member.user.id, member.guild_id
+            );
+        }
+    }
+
+    async fn guild_member_removal(
+        &self,
+        context: Context,
+        guild_id: GuildId,
+        user: User,
+        member: Option<Member>,
+    ) {
+        let embed = audit::member_leave(&user, member.as_ref());
+        self.audit(&context, guild_id, audit::Event::Leaves, embed).await;
+    }
+
+    async fn guild_member_update(
+        &self,
+        context: Context,
+        old: Option<Member>,
+        _new: Option<Member>,
+        event: GuildMemberUpdateEvent,
+    ) {
+        // Without the member from before, there's nothing to compare with.
+        let Some(old) = old else {
+            return;
+        };
+        if let Some(embed) = audit::member_roles(&event.user, &old.roles, &event.roles) {
+            self.audit(&context, event.guild_id, audit::Event::Roles, embed)
+                .await;
+        }
+    }
+
+    async fn guild_role_create(&self, context: Context, role: Role) {
+        let embed = audit::role_create(&role);
+        self.audit(&context, role.guild_id, audit::Event::Roles, embed)
+            .await;
+    }
+
+    async fn guild_role_update(&self, context: Context, old: Option<Role>, role: Role) {
+        if let Some(embed) = old.as_ref().and_then(|x| audit::role_update(x, &role)) {
+            self.audit(&context, role.guild_id, audit::Event::Roles, embed)
+                .await;
+        }
+        // Role panels show the name and emoji of their roles.
+        if old.is_some_and(|x| x.name == role.name && x.unicode_emoji == role.unicode_emoji) {
+            return;
+        }
+        match self.database.self_assignable_roles(role.guild_id).await {
+            Ok(roles) if roles.contains(&role.id) => self.refresh_role_panels(&context, role.guild_id).await,
+            Ok(_) => {}
+            Err(e) => {
+                error!(
+                    "Couldn't read the self-assignable roles of guild {}: {e}",
+                    role.guild_id
+                )
+            }
+        }
     }
 
-    #[cfg(feature = "ai")]
     async fn message(&self, context: Context, message: Message) {
+        self.recent.remember(&message);
+        match automod::moderate(&context.http, &self.database, &self.settings, &message).await {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(e) => error!("Automod couldn't act on message {}: {e}", message.id),
+        }
+
+        #[cfg(feature = "ai")]
+        self.reply_with_llm(&context, &message).await;
+    }
+
+    async fn message_update(
+        &self,
+        context: Context,
+        _old: Option<Message>,
+        _new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        // Edits without content are embeds being added to links and the like.
+        let (Some(guild_id), Some(content)) = (event.guild_id, event.content.as_deref()) else {
+            return;
+        };
+        let before = self.recent.edit(event.id, content);
+        let author_id = match (&event.author, &before) {
+            (Some(author), _) if author.bot => return,
+            (Some(author), _) => author.id,
+            (None, Some(before)) => before.author_id,
+            (None, None) => return,
+        };
+        let before = before.as_ref().map(|x| x.content.as_str());
+        let embed = audit::message_edit(guild_id, event.channel_id, event.id, author_id, before, content);
+        if let Some(embed) = embed {
+            self.audit(&context, guild_id, audit::Event::MessageEdits, embed)
+                .await;
+        }
+    }
+
+    async fn message_delete(
+        &self,
+        context: Context,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        guild_id: Option<GuildId>,
+    ) {
+        let Some(guild_id) = guild_id else {
+            return;
+        };
+        let remembered = self.recent.forget(message_id);
+        let embed = audit::message_delete(channel_id, message_id, remembered.as_ref());
+        self.audit(&context, guild_id, audit::Event::MessageDeletes, embed)
+            .await;
+    }
+
+    async fn voice_state_update(&self, context: Context, old: Option<VoiceState>, new: VoiceState) {
+        let Some(guild_id) = new.guild_id else {
+            return;
+        };
+        if new.member.as_ref().is_some_and(|x| x.user.bot) {
+            return;
+        }
+        let before = old.and_then(|x| x.channel_id);
+        if let Some(embed) = audit::voice(new.user_id, before, new.channel_id) {
+            self.audit(&context, guild_id, audit::Event::Voice, embed).await;
+        }
+    }
+}
+
+impl EventHandler {
+    /// Hand replies to Tara's messages to the LLM, unless the guild turned it off.
+    #[cfg(feature = "ai")]
+    async fn reply_with_llm(&self, context: &Context, message: &Message) {
         match message.mentions_me(&context.http).await {
             Ok(true) if message.kind == MessageType::InlineReply => {
-                // TODO: allow configuration...
+                if let Some(guild_id) = message.guild_id {
+                    match self.settings.get(guild_id).await {
+                        Ok(settings) if !settings.llm_enabled() => return,
+                        Ok(_) => {}
+                        Err(e) => error!("Couldn't read the settings of {guild_id}: {e}"),
+                    }
+                }
                 if let Some(tx) = self.llm_channel.clone() {
                     let content = message.content_safe(&context.cache);
                     let message = llm::LlmMessage::new(
                         &content,
                         context.http.clone(),
                         self.component_map.clone(),
-                        &message,
+                        message,
                     );
                     if let Err(e) = tx.send_async(message.clone()).await {
                         error!("Couldn't send message to LLM task via sender: {e}");
@@ -380,6 +700,25 @@ impl client::EventHandler for EventHandler {
             _ => {}
         }
     }
+
+    /// Post `embed` about `event` in the audit channel of `guild_id`.
+    async fn audit(&self, context: &Context, guild_id: GuildId, event: audit::Event, embed: CreateEmbed) {
+        if let Err(e) = audit::post(&context.http, &self.settings, guild_id, event, embed).await {
+            error!("Couldn't post to the audit log of guild {guild_id}: {e}");
+        }
+    }
+
+    /// Rebuild the role panels of `guild_id` after one of their roles changed.
+    async fn refresh_role_panels(&self, context: &Context, guild_id: GuildId) {
+        let Some(guild) = context.cache.guild(guild_id).map(|x| x.to_owned()) else {
+            return;
+        };
+        let refreshed =
+            commands::refresh_role_panels(&context.http, &self.database, &self.component_map, &guild).await;
+        if let Err(e) = refreshed {
+            error!("Couldn't refresh the role panels of guild {guild_id}: {e}");
+        }
+    }
 }
 
 
@@ -409,7 +748,7 @@ macro_rules! impl_id_trait {
     };
 }
 
-impl_id_trait!(GuildId, RoleId, ChannelId);
+impl_id_trait!(GuildId, RoleId, ChannelId, MessageId, UserId, InteractionId);
 
 /// Returns a structure of error message responses from and `error_message` file
 /// possibly specified in `config`.
@@ -0,0 +1,285 @@
+//! The moderation log. Everything done to a member, whether by automod or a moderator, is
+//! recorded as a numbered [`Case`] and posted in the guild's log channel. Timed bans and
+//! timeouts are undone by [`expiry_watcher`].
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{ChannelId, GuildId, Timestamp, UserId},
+    builder::{CreateEmbed, CreateMessage},
+    http::Http,
+};
+
+use crate::{database::Database, settings::GuildSettings, Result};
+
+/// What was done to a member.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Their message was deleted.
+    Delete,
+    /// Their message was deleted and they were told why.
+    Warn,
+    Timeout,
+    Kick,
+    Ban,
+    Unban,
+}
+
+impl Action {
+    pub const ALL: [Self; 6] = [
+        Self::Delete,
+        Self::Warn,
+        Self::Timeout,
+        Self::Kick,
+        Self::Ban,
+        Self::Unban,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Delete => "delete",
+            Self::Warn => "warn",
+            Self::Timeout => "timeout",
+            Self::Kick => "kick",
+            Self::Ban => "ban",
+            Self::Unban => "unban",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.as_str()) }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.as_str() == s)
+            .ok_or_else(|| format!("`{s}` isn't a moderation action"))
+    }
+}
+
+/// An entry of the moderation log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Case {
+    /// Counts up from 1 in every guild, it's picked when the case is added.
+    pub number:       u32,
+    pub guild_id:     GuildId,
+    pub user_id:      UserId,
+    /// `None` when Tara took the action on its own, like automod does.
+    pub moderator_id: Option<UserId>,
+    pub action:       Action,
+    pub reason:       String,
+    /// Where the message the action was taken for was sent.
+    pub channel_id:   Option<ChannelId>,
+    /// The message the action was taken for.
+    pub content:      Option<String>,
+    /// How many seconds a timeout or ban lasts.
+    pub duration:     Option<u64>,
+    pub created:      DateTime<Utc>,
+    /// When a timed ban or timeout is undone. It's `None` once it was, or once another
+    /// one replaced it.
+    #[serde(default)]
+    pub expiry:       Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub notes:        Vec<Note>,
+}
+
+/// Something a moderator added to a case after the fact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    pub moderator_id: UserId,
+    pub text:         String,
+    pub created:      DateTime<Utc>,
+}
+
+impl Case {
+    /// A case for `action` against `user_id`, taken by Tara just now.
+    pub fn new(guild_id: GuildId, user_id: UserId, action: Action, reason: impl Into<String>) -> Self {
+        Self {
+            number: 0,
+            guild_id,
+            user_id,
+            moderator_id: None,
+            action,
+            reason: reason.into(),
+            channel_id: None,
+            content: None,
+            duration: None,
+            created: Utc::now(),
+            expiry: None,
+            notes: Vec::new(),
+        }
+    }
+
+    /// The same case, taken by `moderator_id`.
+    pub fn by(self, moderator_id: UserId) -> Self {
+        Self {
+            moderator_id: Some(moderator_id),
+            ..self
+        }
+    }
+
+    /// The same case, lasting `duration` from when it was created.
+    pub fn lasting(self, duration: Duration) -> Self {
+        let expiry = chrono::Duration::from_std(duration)
+            .ok()
+            .and_then(|x| self.created.checked_add_signed(x));
+        Self {
+            duration: Some(duration.as_secs()),
+            expiry,
+            ..self
+        }
+    }
+
+    /// The case as it's shown in the log channel.
+    pub fn embed(&self) -> CreateEmbed {
+        let by = self
+            .moderator_id
+            .map_or_else(|| String::from("Tara"), |x| format!("<@{x}>"));
+        let mut embed = CreateEmbed::new()
+            .title(format!("Case {}: {}", self.number, self.action))
+            .field("Member", format!("<@{}>", self.user_id), true)
+            .field("By", by, true)
+            .field("Reason", &self.reason, false);
+        if let Ok(created) = Timestamp::from_unix_timestamp(self.created.timestamp()) {
+            embed = embed.timestamp(created);
+        }
+        if let Some(channel_id) = self.channel_id {
+            embed = embed.field("Channel", format!("<#{channel_id}>"), true);
+        }
+        if let Some(duration) = self.duration {
+            let duration = humantime::format_duration(std::time::Duration::from_secs(duration));
+            embed = embed.field("Duration", duration.to_string(), true);
+        }
+        if let Some(expiry) = self.expiry {
+            embed = embed.field("Ends", format!("<t:{}:R>", expiry.timestamp()), true);
+        }
+        for note in &self.notes {
+            embed = embed.field(
+                format!("Note <t:{}:d>", note.created.timestamp()),
+                format!("{}\n— <@{}>", note.text, note.moderator_id),
+                false,
+            );
+        }
+        if let Some(content) = self.content.as_deref().filter(|x| !x.is_empty()) {
+            embed = embed.description(format!(">>> {}", content.chars().take(1000).collect::<String>()));
+        }
+        embed
+    }
+}
+
+/// Add `case` to the moderation log of its guild, and post it in the log channel when
+/// there is one. Returns the case with its number. A ban or timeout replaces the timed
+/// one the member had, and an unban ends their timed ban.
+pub async fn record(
+    http: &Http,
+    database: &Database,
+    settings: &GuildSettings,
+    mut case: Case,
+) -> Result<Case> {
+    let replaced = match case.action {
+        Action::Ban | Action::Unban => Some(Action::Ban),
+        Action::Timeout => Some(Action::Timeout),
+        Action::Delete | Action::Warn | Action::Kick => None,
+    };
+    if let Some(action) = replaced {
+        database.end_cases(case.guild_id, case.user_id, action).await?;
+    }
+    case.number = database.add_case(&case).await?;
+
+    let log_channel = settings.get(case.guild_id).await?.log_channel();
+    if let Some(channel_id) = log_channel {
+        // The case is recorded even when the log channel can't be posted in.
+        let message = CreateMessage::new().embed(case.embed());
+        if let Err(e) = channel_id.send_message(http, message).await {
+            tracing::warn!(
+                "Couldn't post case {} in the log channel of {}: {e}",
+                case.number,
+                case.guild_id
+            );
+        }
+    }
+    Ok(case)
+}
+
+/// Lift the timed bans whose time is up, and stop tracking the timeouts that Discord
+/// lifted on its own, forever. Bans that can't be lifted because the member or guild is
+/// gone are forgotten. Whatever fails is logged and tried again next time.
+pub async fn expiry_watcher(http: Arc<Http>, database: Database, settings: GuildSettings) {
+    loop {
+        let expired = match database.expired_cases(Utc::now()).await {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::error!("Couldn't look up the expired cases: {e}");
+                Vec::new()
+            }
+        };
+        for case in expired {
+            let undone = match case.action {
+                Action::Ban => {
+                    http.remove_ban(case.guild_id, case.user_id, Some("The ban expired"))
+                        .await
+                }
+                _ => Ok(()),
+            };
+            match undone {
+                Ok(()) => {}
+                Err(serenity::Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {}
+                Err(e) => {
+                    // It's tried again next time.
+                    tracing::warn!("Couldn't undo case {} of {}: {e}", case.number, case.guild_id);
+                    continue;
+                }
+            }
+            if let Err(e) = database.end_cases(case.guild_id, case.user_id, case.action).await {
+                tracing::error!("Couldn't end case {} of {}: {e}", case.number, case.guild_id);
+                continue;
+            }
+
+            if case.action == Action::Ban {
+                let reason = format!("The ban of case {} expired", case.number);
+                let unban = Case::new(case.guild_id, case.user_id, Action::Unban, reason);
+                if let Err(e) = record(&http, &database, &settings, unban).await {
+                    tracing::error!(
+                        "Couldn't record the end of case {} of {}: {e}",
+                        case.number,
+                        case.guild_id
+                    );
+                }
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_names() {
+        for action in Action::ALL {
+            assert_eq!(action.as_str().parse::<Action>().unwrap(), action);
+            assert_eq!(serde_json::to_string(&action).unwrap(), format!("\"{action}\""));
+        }
+        assert!("nothing".parse::<Action>().is_err());
+    }
+
+    #[test]
+    fn test_lasting() {
+        let case = Case::new(GuildId::new(1), UserId::new(2), Action::Ban, "spam").by(UserId::new(3));
+        let case = case.lasting(Duration::from_secs(3600));
+
+        assert_eq!(case.moderator_id, Some(UserId::new(3)));
+        assert_eq!(case.duration, Some(3600));
+        assert_eq!(case.expiry, Some(case.created + chrono::Duration::hours(1)));
+    }
+}
@@ -0,0 +1,131 @@
+//! Shows the configured [`Presence`] on Discord, rotating through its activities and
+//! filling in their template variables.
+use std::time::{Duration, Instant};
+
+use chrono::{TimeZone, Utc};
+use serenity::{
+    all::{ActivityData, OnlineStatus},
+    cache::Cache,
+    prelude::Context,
+};
+use tara_util::presence::{Activity, ActivityKind, Presence, Status};
+use tokio::sync::watch;
+
+/// The values the template variables in an activity's text are replaced with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Variables {
+    /// `{guilds}`, how many guilds Tara is in.
+    guilds:         usize,
+    /// `{members}`, how many members those guilds have together.
+    members:        u64,
+    /// `{uptime}`, how long Tara has been running.
+    uptime:         Duration,
+    /// `{commands_today}`, how many commands were run since midnight (UTC).
+    commands_today: usize,
+}
+
+impl Variables {
+    async fn gather(cache: &Cache, started: Instant) -> Self {
+        let guilds = cache.guilds();
+        let members = guilds
+            .iter()
+            .filter_map(|x| cache.guild(*x).map(|x| x.member_count))
+            .sum();
+
+        let now = Utc::now();
+        let midnight = Utc.from_utc_datetime(&now.date_naive().and_hms_opt(0, 0, 0).unwrap());
+        // There's no log until the first command of the month is run.
+        let commands_today = crate::ipc::read_command_logs(midnight, now)
+            .await
+            .map_or(0, |x| x.len());
+
+        Self {
+            guilds: guilds.len(),
+            members,
+            uptime: started.elapsed(),
+            commands_today,
+        }
+    }
+
+    /// `text` with the variables in it filled in, unknown ones are left alone.
+    fn fill(&self, text: &str) -> String {
+        // Seconds change too often to be worth showing.
+        let uptime = Duration::from_secs(self.uptime.as_secs() / 60 * 60);
+        text.replace("{guilds}", &self.guilds.to_string())
+            .replace("{members}", &self.members.to_string())
+            .replace("{uptime}", &humantime::format_duration(uptime).to_string())
+            .replace("{commands_today}", &self.commands_today.to_string())
+            .replace("{version}", env!("CARGO_PKG_VERSION"))
+    }
+}
+
+fn activity_data(activity: &Activity, variables: &Variables) -> ActivityData {
+    let text = variables.fill(&activity.text);
+    match activity.kind {
+        ActivityKind::Playing => ActivityData::playing(text),
+        ActivityKind::Listening => ActivityData::listening(text),
+        ActivityKind::Watching => ActivityData::watching(text),
+        ActivityKind::Competing => ActivityData::competing(text),
+        ActivityKind::Custom => ActivityData::custom(text),
+    }
+}
+
+fn online_status(status: Status) -> OnlineStatus {
+    match status {
+        Status::Online => OnlineStatus::Online,
+        Status::Idle => OnlineStatus::Idle,
+        Status::DoNotDisturb => OnlineStatus::DoNotDisturb,
+        Status::Invisible => OnlineStatus::Invisible,
+    }
+}
+
+/// Show the latest presence in `presence`, moving to the next activity every interval
+/// and starting over whenever it changes. This only returns once the sender is gone.
+pub async fn rotate(context: Context, mut presence: watch::Receiver<Presence>, started: Instant) {
+    loop {
+        let current = presence.borrow_and_update().clone();
+        let interval = Duration::from_secs(current.interval.max(1));
+        let mut activities = current.activities.iter().cycle();
+
+        loop {
+            let variables = Variables::gather(&context.cache, started).await;
+            let activity = activities.next().map(|x| activity_data(x, &variables));
+            context.set_presence(activity, online_status(current.status));
+
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                changed = presence.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill() {
+        let variables = Variables {
+            guilds:         3,
+            members:        120,
+            uptime:         Duration::from_secs(2 * 3600 + 5 * 60 + 42),
+            commands_today: 7,
+        };
+
+        assert_eq!(
+            variables.fill("{guilds} servers, {members} members"),
+            "3 servers, 120 members"
+        );
+        assert_eq!(variables.fill("up for {uptime}"), "up for 2h 5m");
+        assert_eq!(
+            variables.fill("{commands_today} commands today {unknown}"),
+            "7 commands today {unknown}"
+        );
+    }
+}
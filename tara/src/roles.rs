@@ -0,0 +1,404 @@
+//! Self-assignable roles and the [`RoleGroup`]s they're sorted into. The rules for taking
+//! and giving up a role live here so everything that hands out roles follows them.
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{GuildId, RoleId, UserId},
+    http::Http,
+};
+
+use crate::{database::Database, Error, Result};
+
+/// A category of self-assignable roles, like pronouns or regions, which limits how many
+/// of its roles a member can have.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleGroup {
+    pub name:        String,
+    pub description: String,
+    /// How many of the roles a member has to keep once they picked some.
+    pub min_roles:   u32,
+    pub max_roles:   Option<u32>,
+    /// Whether taking a role swaps out the one the member had, which makes `max_roles` 1.
+    pub exclusive:   bool,
+    /// The self-assignable roles in the group.
+    #[serde(default)]
+    pub roles:       Vec<RoleId>,
+}
+
+impl RoleGroup {
+    /// A group without any limits.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name:        name.into(),
+            description: String::new(),
+            min_roles:   0,
+            max_roles:   None,
+            exclusive:   false,
+            roles:       Vec::new(),
+        }
+    }
+
+    /// The most roles of the group a member can have.
+    pub fn max(&self) -> Option<u32> {
+        if self.exclusive {
+            Some(1)
+        } else {
+            self.max_roles
+        }
+    }
+
+    /// The limits of the group, for people picking roles.
+    pub fn rules(&self) -> String {
+        match (self.min_roles, self.max()) {
+            (1, Some(1)) => String::from("Pick one"),
+            (0, Some(1)) => String::from("Pick at most one"),
+            (0, Some(max)) => format!("Pick up to {max}"),
+            (min, Some(max)) => format!("Pick {min} to {max}"),
+            (0, None) => String::from("Pick any"),
+            (min, None) => format!("Pick at least {min}"),
+        }
+    }
+
+    /// Check that the limits make sense.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::CommandMisuse`] explaining what's wrong with the group.
+    pub fn validate(&self) -> Result<()> {
+        let misuse = |x: String| Err(Error::CommandMisuse(x));
+        // Group names are option values and embed field names.
+        if self.name.is_empty() || self.name.chars().count() > 32 {
+            return misuse(String::from("Role group names are 1 to 32 characters long"));
+        }
+        if self.description.chars().count() > 100 {
+            return misuse(String::from(
+                "Role group descriptions are at most 100 characters long",
+            ));
+        }
+        if self.exclusive && self.max_roles.is_some_and(|x| x > 1) {
+            return misuse(format!(
+                "`{}` is exclusive, so members can't have {} of its roles",
+                self.name,
+                self.max_roles.unwrap_or_default()
+            ));
+        }
+        match self.max() {
+            Some(max) if max < self.min_roles => {
+                misuse(format!(
+                    "Members of `{}` can't keep at least {} roles when they can have only {max}",
+                    self.name, self.min_roles
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What it takes to have a self-assignable role, and how long members keep it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleRules {
+    pub role:     RoleId,
+    /// The role members need before they can take this one.
+    pub requires: Option<RoleId>,
+    /// How many seconds members keep the role before it's taken away again.
+    pub lifetime: Option<u64>,
+}
+
+impl RoleRules {
+    /// A role anyone can take and keep.
+    pub fn new(role: RoleId) -> Self {
+        Self {
+            role,
+            requires: None,
+            lifetime: None,
+        }
+    }
+}
+
+/// When a member loses a temporary role they took.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemporaryRole {
+    pub guild_id: GuildId,
+    pub user_id:  UserId,
+    pub role_id:  RoleId,
+    pub expiry:   DateTime<Utc>,
+}
+
+/// The self-assignable roles of a guild, their groups and their rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelfAssignable {
+    roles:  Vec<RoleId>,
+    groups: Vec<RoleGroup>,
+    rules:  Vec<RoleRules>,
+}
+
+impl SelfAssignable {
+    pub fn new(roles: Vec<RoleId>, groups: Vec<RoleGroup>) -> Self {
+        Self {
+            roles,
+            groups,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn with_rules(mut self, rules: Vec<RoleRules>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub async fn load(database: &Database, guild_id: GuildId) -> Result<Self> {
+        Ok(Self::new(
+            database.self_assignable_roles(guild_id).await?,
+            database.role_groups(guild_id).await?,
+        )
+        .with_rules(database.role_rules(guild_id).await?))
+    }
+
+    pub fn contains(&self, role_id: RoleId) -> bool { self.roles.contains(&role_id) }
+
+    pub fn groups(&self) -> &[RoleGroup] { &self.groups }
+
+    pub fn group_of(&self, role_id: RoleId) -> Option<&RoleGroup> {
+        self.groups.iter().find(|x| x.roles.contains(&role_id))
+    }
+
+    /// The rules of `role_id`, which has none when it isn't self-assignable.
+    pub fn rules(&self, role_id: RoleId) -> RoleRules {
+        self.rules
+            .iter()
+            .copied()
+            .find(|x| x.role == role_id)
+            .unwrap_or_else(|| RoleRules::new(role_id))
+    }
+
+    /// The roles that aren't in a group.
+    pub fn ungrouped(&self) -> impl Iterator<Item = RoleId> + '_ {
+        self.roles.iter().copied().filter(|x| self.group_of(*x).is_none())
+    }
+
+    /// The roles a member with `member_roles` loses when they take `role_id`, which are
+    /// the other roles of an exclusive group.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::CommandMisuse`] when the member lacks the role `role_id` requires, or
+    /// already has as many roles of its group as they can.
+    pub fn take(&self, role_id: RoleId, member_roles: &[RoleId]) -> Result<Vec<RoleId>> {
+        if let Some(required) = self.rules(role_id).requires.filter(|x| !member_roles.contains(x)) {
+            return Err(Error::CommandMisuse(format!(
+                "You need <@&{required}> before you can take <@&{role_id}>"
+            )));
+        }
+        let Some(group) = self.group_of(role_id) else {
+            return Ok(Vec::new());
+        };
+        let held = group
+            .roles
+            .iter()
+            .copied()
+            .filter(|x| *x != role_id && member_roles.contains(x))
+            .collect::<Vec<_>>();
+
+        if group.exclusive {
+            return Ok(held);
+        }
+        match group.max() {
+            Some(max) if held.len() >= max as usize => {
+                Err(Error::CommandMisuse(format!(
+                    "You can only have {max} of the `{}` roles, remove one first",
+                    group.name
+                )))
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Check that a member with `member_roles` may give up `role_id`.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::CommandMisuse`] when it'd leave them with fewer roles of its group than
+    /// they have to keep.
+    pub fn give_up(&self, role_id: RoleId, member_roles: &[RoleId]) -> Result<()> {
+        let Some(group) = self.group_of(role_id) else {
+            return Ok(());
+        };
+        if !member_roles.contains(&role_id) {
+            return Ok(());
+        }
+
+        let held = group.roles.iter().filter(|x| member_roles.contains(x)).count();
+        if held <= group.min_roles as usize {
+            let swap = if group.exclusive {
+                ", take another one instead"
+            } else {
+                ""
+            };
+            Err(Error::CommandMisuse(format!(
+                "You have to keep at least {} of the `{}` roles{swap}",
+                group.min_roles, group.name
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Remember when the member `user_id`, who just took `role_id`, loses it again.
+    /// Returns when that is, or `None` when the role isn't temporary.
+    pub async fn took(
+        &self,
+        database: &Database,
+        guild_id: GuildId,
+        user_id: UserId,
+        role_id: RoleId,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let Some(lifetime) = self.rules(role_id).lifetime else {
+            return Ok(None);
+        };
+        let lifetime =
+            chrono::Duration::from_std(Duration::from_secs(lifetime)).map_err(|_| Error::InternalLogic)?;
+        let expiry = Utc::now() + lifetime;
+        database
+            .add_temporary_role(TemporaryRole {
+                guild_id,
+                user_id,
+                role_id,
+                expiry,
+            })
+            .await?;
+        Ok(Some(expiry))
+    }
+}
+
+/// Take away the temporary roles whose time is up, forever. Roles that can't be taken
+/// away because the member left, or the role was deleted, are forgotten. Whatever fails
+/// is logged and tried again next time.
+pub async fn expiry_watcher(http: Arc<Http>, database: Database) {
+    loop {
+        let expired = match database.expired_roles(Utc::now()).await {
+            Ok(x) => x,
+            Err(e) => {
+                tracing::error!("Couldn't look up the expired temporary roles: {e}");
+                Vec::new()
+            }
+        };
+        for role in expired {
+            let removed = http
+                .remove_member_role(
+                    role.guild_id,
+                    role.user_id,
+                    role.role_id,
+                    Some("The temporary role expired"),
+                )
+                .await;
+            match removed {
+                Ok(()) => {}
+                Err(serenity::Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {}
+                Err(e) => {
+                    // It's tried again next time.
+                    tracing::warn!(
+                        "Couldn't take the temporary role {} from {}: {e}",
+                        role.role_id,
+                        role.user_id
+                    );
+                    continue;
+                }
+            }
+            if let Err(e) = database.remove_temporary_role(role.user_id, role.role_id).await {
+                tracing::error!(
+                    "Couldn't forget the temporary role {} of {}: {e}",
+                    role.role_id,
+                    role.user_id
+                );
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use super::*;
+
+    fn role(x: u64) -> RoleId { RoleId(NonZeroU64::new(x).unwrap()) }
+
+    fn roles() -> SelfAssignable {
+        let region = RoleGroup {
+            min_roles: 1,
+            exclusive: true,
+            roles: vec![role(1), role(2), role(3)],
+            ..RoleGroup::new("region")
+        };
+        let pronouns = RoleGroup {
+            max_roles: Some(2),
+            roles: vec![role(4), role(5), role(6)],
+            ..RoleGroup::new("pronouns")
+        };
+        let rules = RoleRules {
+            requires: Some(role(4)),
+            ..RoleRules::new(role(8))
+        };
+        SelfAssignable::new((1..=8).map(role).collect(), vec![region, pronouns]).with_rules(vec![rules])
+    }
+
+    #[test]
+    fn test_take() {
+        let roles = roles();
+
+        // Exclusive groups swap the role out.
+        assert_eq!(roles.take(role(2), &[role(1), role(4)]).unwrap(), [role(1)]);
+        assert!(roles.take(role(1), &[role(1)]).unwrap().is_empty());
+
+        assert!(roles.take(role(5), &[role(4)]).unwrap().is_empty());
+        assert!(matches!(
+            roles.take(role(6), &[role(4), role(5)]),
+            Err(Error::CommandMisuse(_))
+        ));
+        assert!(roles.take(role(7), &[role(4), role(5)]).unwrap().is_empty());
+
+        // Some roles need another one first.
+        assert!(matches!(
+            roles.take(role(8), &[role(5)]),
+            Err(Error::CommandMisuse(_))
+        ));
+        assert!(roles.take(role(8), &[role(4)]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_give_up() {
+        let roles = roles();
+
+        assert!(matches!(
+            roles.give_up(role(1), &[role(1)]),
+            Err(Error::CommandMisuse(_))
+        ));
+        assert!(roles.give_up(role(4), &[role(4)]).is_ok());
+        assert!(roles.give_up(role(7), &[role(7)]).is_ok());
+        assert_eq!(roles.ungrouped().collect::<Vec<_>>(), [role(7), role(8)]);
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut group = RoleGroup::new("region");
+        assert_eq!(group.rules(), "Pick any");
+        group.validate().unwrap();
+
+        group.exclusive = true;
+        group.min_roles = 1;
+        assert_eq!(group.rules(), "Pick one");
+        group.validate().unwrap();
+
+        group.max_roles = Some(3);
+        assert!(group.validate().is_err());
+
+        group.exclusive = false;
+        group.min_roles = 4;
+        assert!(group.validate().is_err());
+    }
+}
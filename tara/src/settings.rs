@@ -0,0 +1,577 @@
+//! Per-guild settings. Every setting is declared once in [`SETTINGS`], which `/settings`
+//! is generated from, and read through [`GuildSettings`], which caches them so commands
+//! can look them up on every use.
+use std::{borrow::Cow, collections::HashMap, fmt, num::NonZeroU64, sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{
+    ChannelId, ChannelType, CommandDataOptionValue, CommandOptionType, Guild, GuildId, RoleId,
+};
+use tokio::sync::RwLock;
+
+use crate::{audit::Event, automod::Category, database::Database, moderation::Action, Error, IdUtil, Result};
+
+/// Every setting a guild has.
+pub static SETTINGS: &[&Setting] = &[
+    &LOG_CHANNEL,
+    &SAFE_SEARCH,
+    &DJ_ROLE,
+    &LLM_ENABLED,
+    &AUTOMOD_ACTION,
+    &AUTOMOD_CATEGORIES,
+    &AUTOMOD_TIMEOUT,
+    &AUTOMOD_EXEMPT_ROLES,
+    &AUTOMOD_EXEMPT_CHANNELS,
+    &AUTOMOD_WORDS,
+    &AUDIT_CHANNEL,
+    &AUDIT_EVENTS,
+];
+
+pub static LOG_CHANNEL: Setting = Setting {
+    name:        "log_channel",
+    description: "The channel Tara logs moderation cases in",
+    kind:        Kind::Channel,
+    default:     None,
+};
+
+pub static SAFE_SEARCH: Setting = Setting {
+    name:        "safe_search",
+    description: "How strictly searches are filtered",
+    kind:        Kind::Choice(&["off", "moderate", "strict"]),
+    default:     Some(Value::Choice(Cow::Borrowed("moderate"))),
+};
+
+pub static DJ_ROLE: Setting = Setting {
+    name:        "dj_role",
+    description: "The role needed to skip, remove, move or stop tracks, anyone can when it isn't set",
+    kind:        Kind::Role,
+    default:     None,
+};
+
+pub static LLM_ENABLED: Setting = Setting {
+    name:        "llm_enabled",
+    description: "Whether Tara answers replies with the LLM",
+    kind:        Kind::Boolean,
+    default:     Some(Value::Boolean(true)),
+};
+
+pub static AUTOMOD_ACTION: Setting = Setting {
+    name:        "automod_action",
+    description: "What automod does with the messages it flags, it's turned off by default",
+    kind:        Kind::Choice(&["off", "delete", "warn", "timeout"]),
+    default:     Some(Value::Choice(Cow::Borrowed("off"))),
+};
+
+pub static AUTOMOD_CATEGORIES: Setting = Setting {
+    name:        "automod_categories",
+    description: "What automod flags: profane, sexual, offensive or spam, separated by commas. All by \
+                  default",
+    kind:        Kind::Choices(&["profane", "sexual", "offensive", "spam"]),
+    default:     None,
+};
+
+pub static AUTOMOD_TIMEOUT: Setting = Setting {
+    name:        "automod_timeout",
+    description: "How long automod times members out for",
+    kind:        Kind::Choice(&["1m", "10m", "1h", "1d", "1w"]),
+    default:     Some(Value::Choice(Cow::Borrowed("10m"))),
+};
+
+pub static AUTOMOD_EXEMPT_ROLES: Setting = Setting {
+    name:        "automod_exempt_roles",
+    description: "The roles whose members automod leaves alone, as mentions",
+    kind:        Kind::Roles,
+    default:     None,
+};
+
+pub static AUTOMOD_EXEMPT_CHANNELS: Setting = Setting {
+    name:        "automod_exempt_channels",
+    description: "The channels automod leaves alone, as mentions",
+    kind:        Kind::Channels,
+    default:     None,
+};
+
+pub static AUTOMOD_WORDS: Setting = Setting {
+    name:        "automod_words",
+    description: "More words and phrases automod flags, separated by commas",
+    kind:        Kind::Words,
+    default:     None,
+};
+
+pub static AUDIT_CHANNEL: Setting = Setting {
+    name:        "audit_channel",
+    description: "The channel Tara posts server events in, nothing is posted until it's set",
+    kind:        Kind::Channel,
+    default:     None,
+};
+
+pub static AUDIT_EVENTS: Setting = Setting {
+    name:        "audit_events",
+    description: "Audit log events, separated by commas: message_edits, message_deletes, joins, leaves, \
+                  roles, voice",
+    kind:        Kind::Choices(&[
+        "message_edits",
+        "message_deletes",
+        "joins",
+        "leaves",
+        "roles",
+        "voice",
+    ]),
+    default:     None,
+};
+
+/// A setting every guild has, which is its `default` until it's set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    pub name:        &'static str,
+    pub description: &'static str,
+    pub kind:        Kind,
+    /// `None` when the setting is unset by default.
+    pub default:     Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A text channel in the guild.
+    Channel,
+    /// A role in the guild other than `@everyone`.
+    Role,
+    Boolean,
+    /// One of the listed strings.
+    Choice(&'static [&'static str]),
+    /// Any of the listed strings, given separated by commas.
+    Choices(&'static [&'static str]),
+    /// Roles in the guild, given as mentions.
+    Roles,
+    /// Text channels in the guild, given as mentions.
+    Channels,
+    /// Words or phrases, given separated by commas.
+    Words,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Value {
+    Channel(ChannelId),
+    Role(RoleId),
+    Boolean(bool),
+    Choice(Cow<'static, str>),
+    Choices(Vec<String>),
+    Roles(Vec<RoleId>),
+    Channels(Vec<ChannelId>),
+    Words(Vec<String>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Channel(x) => write!(f, "<#{x}>"),
+            Value::Role(x) => write!(f, "<@&{x}>"),
+            Value::Boolean(true) => write!(f, "on"),
+            Value::Boolean(false) => write!(f, "off"),
+            Value::Choice(x) => write!(f, "{x}"),
+            Value::Choices(x) | Value::Words(x) if x.is_empty() => write!(f, "none"),
+            Value::Choices(x) | Value::Words(x) => write!(f, "{}", x.join(", ")),
+            Value::Roles(x) => list(f, x.iter().map(|x| format!("<@&{x}>"))),
+            Value::Channels(x) => list(f, x.iter().map(|x| format!("<#{x}>"))),
+        }
+    }
+}
+
+/// Write the mentions in `x` separated by spaces, or "none" when there aren't any.
+fn list(f: &mut fmt::Formatter<'_>, x: impl Iterator<Item = String>) -> fmt::Result {
+    let x = x.collect::<Vec<_>>();
+    if x.is_empty() {
+        write!(f, "none")
+    } else {
+        write!(f, "{}", x.join(" "))
+    }
+}
+
+/// The IDs mentioned in `text`, like `<@&1> <#2>`, or given as plain numbers.
+///
+/// # Errors
+///
+/// [`Error::CommandMisuse`] when there's something else in `text`.
+fn mentions(text: &str) -> Result<Vec<NonZeroU64>> {
+    text.split(|x: char| x.is_whitespace() || x == ',')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            x.trim_start_matches(['<', '@', '&', '#'])
+                .trim_end_matches('>')
+                .parse()
+                .map_err(|_| Error::CommandMisuse(format!("`{x}` isn't a mention")))
+        })
+        .collect()
+}
+
+/// The comma separated items of `text`, lowercase and without duplicates.
+fn items(text: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    for item in text.split(',').map(|x| x.trim().to_lowercase()) {
+        if !item.is_empty() && !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    items
+}
+
+impl Setting {
+    /// Find the setting called `name`.
+    pub fn find(name: &str) -> Option<&'static Self> { SETTINGS.iter().find(|x| x.name == name).copied() }
+
+    /// The type of the option `/settings set` takes for this setting.
+    pub fn option_type(&self) -> CommandOptionType {
+        match self.kind {
+            Kind::Channel => CommandOptionType::Channel,
+            Kind::Role => CommandOptionType::Role,
+            Kind::Boolean => CommandOptionType::Boolean,
+            Kind::Choice(_) | Kind::Choices(_) | Kind::Roles | Kind::Channels | Kind::Words => {
+                CommandOptionType::String
+            }
+        }
+    }
+
+    /// The value of the option given to `/settings set`.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::CommandMisuse`] when a list can't be read, or [`Error::InternalLogic`]
+    /// when the option is the wrong type.
+    pub fn value_of(&self, option: &CommandDataOptionValue) -> Result<Value> {
+        match (self.kind, option) {
+            (Kind::Channel, CommandDataOptionValue::Channel(x)) => Ok(Value::Channel(*x)),
+            (Kind::Role, CommandDataOptionValue::Role(x)) => Ok(Value::Role(*x)),
+            (Kind::Boolean, CommandDataOptionValue::Boolean(x)) => Ok(Value::Boolean(*x)),
+            (Kind::Choice(_), CommandDataOptionValue::String(x)) => Ok(Value::Choice(Cow::Owned(x.clone()))),
+            (Kind::Choices(_), CommandDataOptionValue::String(x)) => Ok(Value::Choices(items(x))),
+            (Kind::Words, CommandDataOptionValue::String(x)) => Ok(Value::Words(items(x))),
+            (Kind::Roles, CommandDataOptionValue::String(x)) => {
+                Ok(Value::Roles(mentions(x)?.into_iter().map(RoleId).collect()))
+            }
+            (Kind::Channels, CommandDataOptionValue::String(x)) => {
+                Ok(Value::Channels(mentions(x)?.into_iter().map(ChannelId).collect()))
+            }
+            _ => Err(Error::InternalLogic),
+        }
+    }
+
+    /// Check that `value` can be used for this setting in `guild`.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::CommandMisuse`] explaining what's wrong with `value`.
+    pub fn validate(&self, value: &Value, guild: &Guild) -> Result<()> {
+        let misuse = |x: String| Err(Error::CommandMisuse(x));
+        let channel = |id: &ChannelId| {
+            match guild.channels.get(id) {
+                Some(channel) if matches!(channel.kind, ChannelType::Text | ChannelType::News) => Ok(()),
+                Some(_) => misuse(format!("<#{id}> isn't a text channel")),
+                None => misuse(format!("<#{id}> isn't a channel in this server")),
+            }
+        };
+        let role = |id: &RoleId| {
+            if id.touint() == guild.id.touint() {
+                misuse(String::from("`@everyone` can't be used for this"))
+            } else if !guild.roles.contains_key(id) {
+                misuse(format!("<@&{id}> isn't a role in this server"))
+            } else {
+                Ok(())
+            }
+        };
+        let choice = |choices: &[&str], x: &str| {
+            if choices.contains(&x) {
+                Ok(())
+            } else {
+                misuse(format!(
+                    "`{x}` isn't one of {}",
+                    choices
+                        .iter()
+                        .map(|x| format!("`{x}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+        };
+
+        match (self.kind, value) {
+            (Kind::Channel, Value::Channel(id)) => channel(id),
+            (Kind::Role, Value::Role(id)) => role(id),
+            (Kind::Boolean, Value::Boolean(_)) => Ok(()),
+            (Kind::Choice(choices), Value::Choice(x)) => choice(choices, x),
+            (Kind::Choices(choices), Value::Choices(x)) => x.iter().try_for_each(|x| choice(choices, x)),
+            (Kind::Roles, Value::Roles(x)) => x.iter().try_for_each(role),
+            (Kind::Channels, Value::Channels(x)) => x.iter().try_for_each(channel),
+            (Kind::Words, Value::Words(x)) => {
+                if x.iter().any(|x| x.chars().count() > 100) {
+                    misuse(String::from("Words are at most 100 characters long"))
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Err(Error::InternalLogic),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafeSearch {
+    Off,
+    Moderate,
+    Strict,
+}
+
+/// The settings of a guild, the ones that aren't set are their default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Values {
+    set: HashMap<&'static str, Value>,
+}
+
+impl Values {
+    /// The value of `setting`, or its default if it isn't set.
+    pub fn get(&self, setting: &'static Setting) -> Option<&Value> {
+        self.set.get(setting.name).or(setting.default.as_ref())
+    }
+
+    /// Whether `setting` was set, rather than being its default.
+    pub fn is_set(&self, setting: &Setting) -> bool { self.set.contains_key(setting.name) }
+
+    pub fn log_channel(&self) -> Option<ChannelId> {
+        match self.get(&LOG_CHANNEL) {
+            Some(Value::Channel(x)) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn safe_search(&self) -> SafeSearch {
+        match self.get(&SAFE_SEARCH) {
+            Some(Value::Choice(x)) if x == "off" => SafeSearch::Off,
+            Some(Value::Choice(x)) if x == "strict" => SafeSearch::Strict,
+            _ => SafeSearch::Moderate,
+        }
+    }
+
+    pub fn dj_role(&self) -> Option<RoleId> {
+        match self.get(&DJ_ROLE) {
+            Some(Value::Role(x)) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn llm_enabled(&self) -> bool { !matches!(self.get(&LLM_ENABLED), Some(Value::Boolean(false))) }
+
+    /// What automod does with the messages it flags, `None` when it's off.
+    pub fn automod_action(&self) -> Option<Action> {
+        match self.get(&AUTOMOD_ACTION) {
+            Some(Value::Choice(x)) => x.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn automod_categories(&self) -> Vec<Category> {
+        match self.get(&AUTOMOD_CATEGORIES) {
+            Some(Value::Choices(x)) => x.iter().filter_map(|x| x.parse().ok()).collect(),
+            _ => Category::ALL.to_vec(),
+        }
+    }
+
+    pub fn automod_timeout(&self) -> Duration {
+        match self.get(&AUTOMOD_TIMEOUT) {
+            Some(Value::Choice(x)) => humantime::parse_duration(x).unwrap_or(Duration::from_secs(600)),
+            _ => Duration::from_secs(600),
+        }
+    }
+
+    pub fn automod_exempt_roles(&self) -> &[RoleId] {
+        match self.get(&AUTOMOD_EXEMPT_ROLES) {
+            Some(Value::Roles(x)) => x,
+            _ => &[],
+        }
+    }
+
+    pub fn automod_exempt_channels(&self) -> &[ChannelId] {
+        match self.get(&AUTOMOD_EXEMPT_CHANNELS) {
+            Some(Value::Channels(x)) => x,
+            _ => &[],
+        }
+    }
+
+    pub fn automod_words(&self) -> &[String] {
+        match self.get(&AUTOMOD_WORDS) {
+            Some(Value::Words(x)) => x,
+            _ => &[],
+        }
+    }
+
+    pub fn audit_channel(&self) -> Option<ChannelId> {
+        match self.get(&AUDIT_CHANNEL) {
+            Some(Value::Channel(x)) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn audit_events(&self) -> Vec<Event> {
+        match self.get(&AUDIT_EVENTS) {
+            Some(Value::Choices(x)) => x.iter().filter_map(|x| x.parse().ok()).collect(),
+            _ => Event::ALL.to_vec(),
+        }
+    }
+}
+
+/// The settings of every guild, cached after they're first read. Cheap to clone.
+#[derive(Clone)]
+pub struct GuildSettings {
+    inner: Arc<GuildSettingsInner>,
+}
+
+struct GuildSettingsInner {
+    database: Database,
+    cache:    RwLock<HashMap<GuildId, Arc<Values>>>,
+}
+
+impl GuildSettings {
+    pub fn new(database: Database) -> Self {
+        Self {
+            inner: Arc::new(GuildSettingsInner {
+                database,
+                cache: RwLock::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// The settings of `guild_id`, read from the database the first time.
+    pub async fn get(&self, guild_id: GuildId) -> Result<Arc<Values>> {
+        if let Some(values) = self.inner.cache.read().await.get(&guild_id) {
+            return Ok(values.clone());
+        }
+
+        let rows = self.inner.database.guild_settings(guild_id).await?;
+        let mut set = HashMap::new();
+        for (key, value) in rows {
+            // Settings that were removed are left alone.
+            let Some(setting) = Setting::find(&key) else {
+                continue;
+            };
+            match serde_json::from_str(&value) {
+                Ok(value) => {
+                    set.insert(setting.name, value);
+                }
+                Err(e) => tracing::warn!("Ignoring the invalid {key} setting of guild {guild_id}: {e}"),
+            }
+        }
+
+        let values = Arc::new(Values { set });
+        self.inner.cache.write().await.insert(guild_id, values.clone());
+        Ok(values)
+    }
+
+    /// Set `setting` to `value` in `guild_id`. The value should be validated first.
+    pub async fn set(&self, guild_id: GuildId, setting: &Setting, value: Value) -> Result<()> {
+        let json = serde_json::to_string(&value).map_err(|e| Error::JsonParse(e.to_string()))?;
+        self.inner
+            .database
+            .set_guild_setting(guild_id, setting.name, &json)
+            .await?;
+
+        self.forget(guild_id).await;
+        Ok(())
+    }
+
+    /// Put `setting` back to its default in `guild_id`.
+    pub async fn reset(&self, guild_id: GuildId, setting: &Setting) -> Result<()> {
+        self.inner
+            .database
+            .reset_guild_setting(guild_id, setting.name)
+            .await?;
+
+        self.forget(guild_id).await;
+        Ok(())
+    }
+
+    /// Drop the cached settings of `guild_id`, they're read again next time.
+    pub async fn forget(&self, guild_id: GuildId) { self.inner.cache.write().await.remove(&guild_id); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let mut names = SETTINGS.iter().map(|x| x.name).collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), SETTINGS.len(), "setting names are unique");
+
+        for setting in SETTINGS {
+            // Discord's limits for option names and descriptions.
+            assert!(setting.name.len() <= 32 && setting.name == setting.name.to_lowercase());
+            assert!(setting.description.len() <= 100);
+            if let (Kind::Choice(choices), Some(Value::Choice(default))) = (setting.kind, &setting.default) {
+                assert!(choices.contains(&default.as_ref()), "{}", setting.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        let values = Values::default();
+
+        assert_eq!(values.log_channel(), None);
+        assert_eq!(values.audit_channel(), None);
+        assert_eq!(values.safe_search(), SafeSearch::Moderate);
+        assert_eq!(values.dj_role(), None);
+        assert!(values.llm_enabled());
+        assert!(!values.is_set(&SAFE_SEARCH));
+        assert_eq!(values.automod_action(), None);
+        assert_eq!(values.automod_categories(), Category::ALL);
+        assert_eq!(values.automod_timeout(), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn test_values() {
+        let mut values = Values::default();
+        values
+            .set
+            .insert("safe_search", Value::Choice(Cow::Borrowed("strict")));
+        values.set.insert("llm_enabled", Value::Boolean(false));
+        values
+            .set
+            .insert("log_channel", Value::Channel(ChannelId::new(3)));
+
+        assert_eq!(values.safe_search(), SafeSearch::Strict);
+        assert!(!values.llm_enabled());
+        assert!(values.is_set(&SAFE_SEARCH));
+        // The audit log is opt-in, it isn't posted in the log channel.
+        assert_eq!(values.audit_channel(), None);
+    }
+
+    #[test]
+    fn test_lists() {
+        let value = AUTOMOD_EXEMPT_ROLES
+            .value_of(&CommandDataOptionValue::String(String::from("<@&1>, <@&2> 3")))
+            .unwrap();
+        assert_eq!(value, Value::Roles((1..=3).map(RoleId::new).collect()));
+        assert_eq!(value.to_string(), "<@&1> <@&2> <@&3>");
+        assert!(AUTOMOD_EXEMPT_ROLES
+            .value_of(&CommandDataOptionValue::String(String::from("<@&1> mods")))
+            .is_err());
+
+        let value = AUTOMOD_WORDS
+            .value_of(&CommandDataOptionValue::String(String::from(
+                "Heck, darn it,, heck",
+            )))
+            .unwrap();
+        assert_eq!(
+            value,
+            Value::Words(vec![String::from("heck"), String::from("darn it")])
+        );
+    }
+
+    #[test]
+    fn test_value_round_trip() {
+        let value = Value::Choice(Cow::Borrowed("strict"));
+        let json = serde_json::to_string(&value).unwrap();
+
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+    }
+}